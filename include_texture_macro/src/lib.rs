@@ -7,7 +7,7 @@ use std::path::PathBuf;
 use std::{env, process};
 
 use litrs::StringLit;
-use proc_macro::TokenStream;
+use proc_macro::{TokenStream, TokenTree};
 use quote::quote;
 
 #[proc_macro]
@@ -24,9 +24,17 @@ pub fn include_texture(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
 fn include_texture_impl(input: TokenStream) -> Result<TokenStream, Box<dyn Error>> {
     let tokens: Vec<_> = input.into_iter().collect();
 
-    if tokens.len() != 1 {
-        return Err(format!("expected exactly one input token, got {}", tokens.len()).into());
-    }
+    let format = match tokens.as_slice() {
+        [_] => TextureFormat::Rgba8,
+        [_, TokenTree::Punct(comma), TokenTree::Ident(format)] if comma.as_char() == ',' => {
+            TextureFormat::from_name(&format.to_string())?
+        }
+        _ => return Err(format!(
+            "expected a path and optionally a format, e.g. `\"tex.png\", rgb565`, got {} tokens",
+            tokens.len()
+        )
+        .into()),
+    };
 
     let texture_source_filename = &tokens[0];
 
@@ -83,6 +91,8 @@ fn include_texture_impl(input: TokenStream) -> Result<TokenStream, Box<dyn Error
     let tex3ds = devkitpro.join("tools/bin/tex3ds");
 
     let output = process::Command::new(&tex3ds)
+        .arg("-f")
+        .arg(format.tex3ds_name())
         .arg("-z")
         .arg("none")
         .arg("-r")
@@ -104,9 +114,24 @@ fn include_texture_impl(input: TokenStream) -> Result<TokenStream, Box<dyn Error
         .into());
     }
 
-    let bytes = &std::fs::read(&out_path)
+    let source = std::fs::read(&texture_source_file)
+        .map_err(|err| format!("unable to read {texture_source_file:?}: {err}"))?;
+    let (width, height) = png_size(&source).ok_or_else(|| {
+        format!("{texture_source_file:?} isn't a PNG small enough to be a texture")
+    })?;
+
+    let pixels = &std::fs::read(&out_path)
         .map_err(|err| format!("unable to read output file {out_path:?}: {err}"))?[4..];
 
+    // header read by `Texture::from_embedded`, padded to keep the pixels
+    // u32 aligned
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + pixels.len());
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes.push(format as u8);
+    bytes.resize(HEADER_SIZE, 0);
+    bytes.extend_from_slice(pixels);
+
     let source_file_path = texture_source_file.to_string_lossy();
 
     let result = quote! {
@@ -134,3 +159,63 @@ fn include_texture_impl(input: TokenStream) -> Result<TokenStream, Box<dyn Error
 
     Ok(result.into())
 }
+
+/// Size of the header in front of the pixel data
+const HEADER_SIZE: usize = 8;
+
+/// Formats the texture can be converted to, numbered as in the header (the
+/// same numbering as the model cache).
+#[derive(Clone, Copy)]
+enum TextureFormat {
+    Rgba8 = 0,
+    Rgb565 = 1,
+    Etc1 = 2,
+    Rgba4 = 3,
+    A8 = 4,
+    Etc1A4 = 5,
+}
+
+impl TextureFormat {
+    const ALL: [TextureFormat; 6] = [
+        TextureFormat::Rgba8,
+        TextureFormat::Rgb565,
+        TextureFormat::Rgba4,
+        TextureFormat::A8,
+        TextureFormat::Etc1,
+        TextureFormat::Etc1A4,
+    ];
+
+    fn tex3ds_name(self) -> &'static str {
+        match self {
+            TextureFormat::Rgba8 => "rgba8",
+            TextureFormat::Rgb565 => "rgb565",
+            TextureFormat::Rgba4 => "rgba4",
+            TextureFormat::A8 => "a8",
+            TextureFormat::Etc1 => "etc1",
+            TextureFormat::Etc1A4 => "etc1a4",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.tex3ds_name() == name)
+            .ok_or_else(|| {
+                let names = Self::ALL.map(Self::tex3ds_name).join(", ");
+                format!("unknown texture format `{name}`, expected one of {names}")
+            })
+    }
+}
+
+/// Size of the texture made from a PNG, read from its IHDR chunk
+fn png_size(png: &[u8]) -> Option<(u16, u16)> {
+    let header = png.get(..24)?;
+    if &header[..8] != b"\x89PNG\r\n\x1a\n" || &header[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(header[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(header[20..24].try_into().ok()?);
+    // tex3ds pads the image out to a size the GPU accepts
+    let pad = |n: u32| n.next_power_of_two().max(8);
+    Some((pad(width).try_into().ok()?, pad(height).try_into().ok()?))
+}
@@ -0,0 +1,288 @@
+//! Where the scene is viewed from.
+
+use std::{
+    f32::consts::{PI, TAU},
+    fs, io,
+    mem::size_of,
+    path::Path,
+    time::Duration,
+};
+
+use citro3d::math::Matrix4;
+
+use crate::{
+    logging::log_warn,
+    math::{Rotation, Transform, Vec3},
+};
+
+/// How far [`Camera::rotate`] lets the camera look up or down by default,
+/// just short of straight up where yaw stops meaning anything
+const DEFAULT_PITCH_LIMIT: f32 = 89.0 * PI / 180.0;
+/// Radius of the camera for collisions unless it's changed, small enough to
+/// get right up to things
+const DEFAULT_COLLISION_RADIUS: f32 = 0.1;
+/// Start of a saved camera file
+const MAGIC: &[u8; 4] = b"TRGV";
+/// Bumped whenever the layout of a saved camera file changes
+const VERSION: u32 = 1;
+/// Floats saved after the header: the position, rotation and field of view
+const SAVED_FLOATS: usize = 7;
+
+/// Range [`ProjectionSettings::set_fov`] keeps the field of view in
+const MIN_FOV: f32 = 30.0 * PI / 180.0;
+const MAX_FOV: f32 = 90.0 * PI / 180.0;
+
+/// How the camera's view is projected onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectionMode {
+    /// Things shrink with distance, through the field of view
+    Perspective,
+    /// Things stay the same size however far away they are, with a view
+    /// `width` by `height` across
+    Ortho { width: f32, height: f32 },
+}
+
+/// Shape of the volume the camera sees, which its projection matrices are
+/// made from. Only changed through the setters, so it's always valid to
+/// build a matrix from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectionSettings {
+    mode: ProjectionMode,
+    fov: f32,
+    near: f32,
+    far: f32,
+}
+
+impl ProjectionSettings {
+    /// Projection with the vertical field of view `fov` in radians, clamped
+    /// like [`ProjectionSettings::set_fov`], between clip planes at `near`
+    /// and `far`, which have to be valid for
+    /// [`ProjectionSettings::set_clip_planes`]
+    pub fn new(fov: f32, near: f32, far: f32) -> Self {
+        debug_assert!(near > 0.0 && near < far, "invalid clip planes");
+        let mut settings = Self {
+            mode: ProjectionMode::Perspective,
+            fov,
+            near,
+            far,
+        };
+        settings.set_fov(fov);
+        settings
+    }
+
+    pub fn mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    /// False for an orthographic projection with no area
+    pub fn set_mode(&mut self, mode: ProjectionMode) -> bool {
+        if let ProjectionMode::Ortho { width, height } = mode {
+            if !(width > 0.0 && height > 0.0) {
+                return false;
+            }
+        }
+        self.mode = mode;
+        true
+    }
+
+    /// Vertical, in radians
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    /// Kept between 30 and 90 degrees
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    /// False unless `0 < near < far`
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) -> bool {
+        if !(near > 0.0 && near < far) {
+            return false;
+        }
+        self.near = near;
+        self.far = far;
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub pos: Vec3,
+    /// Pitch, yaw and roll in radians, yaw is always about the world's y
+    pub rot: Vec3,
+    pub projection: ProjectionSettings,
+    pub pitch_limit: f32,
+    /// Free flying rather than walking
+    pub fly: bool,
+    /// Kept out of models as a sphere `collision_radius` across
+    pub collide: bool,
+    pub collision_radius: f32,
+    /// Seconds the view takes to ease towards `pos` and `rot`, zero to jump
+    pub smoothing: f32,
+    /// Where the view is while it catches up
+    shown: Option<(Vec3, Rotation)>,
+}
+
+impl Camera {
+    pub fn new(projection: ProjectionSettings) -> Self {
+        Self {
+            pos: Vec3::splat(0.0),
+            rot: Vec3::splat(0.0),
+            projection,
+            pitch_limit: DEFAULT_PITCH_LIMIT,
+            fly: false,
+            collide: false,
+            collision_radius: DEFAULT_COLLISION_RADIUS,
+            smoothing: 0.0,
+            shown: None,
+        }
+    }
+
+    /// Ease the view towards `pos` and `rot` by `dt`, called once per frame.
+    /// Decays exponentially, so it moves the same over a second whatever the
+    /// frame rate
+    pub fn update(&mut self, dt: Duration) {
+        if self.smoothing <= 0.0 {
+            self.shown = None;
+            return;
+        }
+        let t = 1.0 - (-dt.as_secs_f32() / self.smoothing).exp();
+        let (pos, rot) = self.shown_pose();
+        self.shown = Some((
+            pos.lerp(&self.pos, t),
+            rot.slerp(&self.orientation(), t).normalized(),
+        ));
+    }
+
+    /// Jump the view to `pos` and `rot` without easing, for teleports
+    pub fn snap(&mut self) {
+        self.shown = None;
+    }
+
+    /// Where the view is actually from, see `smoothing`
+    fn shown_pose(&self) -> (Vec3, Rotation) {
+        self.shown
+            .clone()
+            .unwrap_or_else(|| (self.pos.clone(), self.orientation()))
+    }
+
+    /// Turn by `pitch`, `yaw` and `roll` radians, the way all look controls
+    /// should go. Pitch is kept within `pitch_limit` so the view never goes
+    /// upside down, and yaw wrapped to a single turn. Roll is ignored unless
+    /// flying.
+    pub fn rotate(&mut self, pitch: f32, yaw: f32, roll: f32) {
+        self.rot.x = (self.rot.x + pitch).clamp(-self.pitch_limit, self.pitch_limit);
+        self.rot.y = (self.rot.y + yaw).rem_euclid(TAU);
+        if self.fly {
+            self.rot.z = (self.rot.z + roll).rem_euclid(TAU);
+        }
+    }
+
+    /// Which way the camera faces, looking down its -z with y up
+    pub fn orientation(&self) -> Rotation {
+        Rotation::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), self.rot.y)
+            * Rotation::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), self.rot.x)
+            * Rotation::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), self.rot.z)
+    }
+
+    /// Move `forward`, `right` and `up` from where the camera's facing. Unless
+    /// flying only its yaw is followed, so looking up or down doesn't lift it
+    /// off the ground and up is always the world's
+    pub fn move_relative(&mut self, forward: f32, right: f32, up: f32) {
+        let turn = if self.fly {
+            self.orientation()
+        } else {
+            Rotation::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), self.rot.y)
+        };
+        let step = turn.rotate(&Vec3::new(right, up, -forward));
+        self.pos = self.pos.add(&step);
+    }
+
+    /// Transform moving the world into view space, the inverse of the
+    /// camera's own placement: moved by -pos, then turned by the inverse of
+    /// its orientation. Follows the smoothed view rather than `pos` and `rot`
+    pub fn transform(&self) -> Transform {
+        let (pos, rot) = self.shown_pose();
+        Transform::new(pos, rot, Vec3::splat(1.0)).inverse()
+    }
+
+    /// [`Camera::transform`] as a matrix, for the shader's camera uniform
+    pub fn view_matrix(&self) -> Matrix4 {
+        self.transform().to_matrix()
+    }
+
+    /// Unit direction in world space through the point `x`, `y` of the view,
+    /// `aspect` (width over height) times wider than it is high. The point
+    /// goes from -1 to 1 across the view, left to right and bottom to top, so
+    /// 0, 0 is straight ahead. Starts from [`Camera::eye`].
+    pub fn ray(&self, x: f32, y: f32, aspect: f32) -> Vec3 {
+        let half_height = (self.projection.fov() / 2.0).tan();
+        // view space looks down -z
+        let dir = Vec3::new(x * half_height * aspect, y * half_height, -1.0);
+        let dir = dir.scale(1.0 / dir.length());
+        self.shown_pose().1.rotate(&dir)
+    }
+
+    /// Write the pose and field of view to `path`, creating its directory if
+    /// needed
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        let Vec3 { x, y, z } = &self.pos;
+        let Vec3 {
+            x: rx,
+            y: ry,
+            z: rz,
+        } = &self.rot;
+        for f in [*x, *y, *z, *rx, *ry, *rz, self.projection.fov()] {
+            bytes.extend(f.to_le_bytes());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, bytes)
+    }
+
+    /// Jump to the pose saved at `path` by [`Camera::save`], returning
+    /// whether there was one. A file which can't be read (or is from another
+    /// version) is ignored.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let Ok(bytes) = fs::read(path) else {
+            return false;
+        };
+        let header = MAGIC.len() + size_of::<u32>();
+        let valid = bytes.len() == header + SAVED_FLOATS * size_of::<f32>()
+            && bytes.starts_with(MAGIC)
+            && bytes[MAGIC.len()..header] == VERSION.to_le_bytes();
+        let floats = bytes[header.min(bytes.len())..]
+            .chunks_exact(size_of::<f32>())
+            // UNWRAP: every chunk is the size of an f32
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        if !valid || floats.iter().any(|f| !f.is_finite()) {
+            log_warn!("ignoring unreadable camera file {}", path.display());
+            return false;
+        }
+        self.pos = Vec3::new(floats[0], floats[1], floats[2]);
+        self.rot = Vec3::new(floats[3], floats[4], floats[5]);
+        self.projection.set_fov(floats[6]);
+        self.snap();
+        true
+    }
+
+    /// Where the eye is in world space, as it's seen from after smoothing
+    pub fn eye(&self) -> Vec3 {
+        self.shown_pose().0
+    }
+}
@@ -0,0 +1,200 @@
+//! Debug readouts on the bottom screen, in pages cycled with SELECT.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ctru::services::hid::{Hid, KeyPad};
+
+use crate::model::{texture::shared::TextureCache, DrawStats};
+
+/// Button which cycles to the next page
+pub const CYCLE_KEY: KeyPad = KeyPad::SELECT;
+
+/// Number of frames frame time stats are calculated over
+const FRAME_HISTORY: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HudPage {
+    #[default]
+    Off,
+    Fps,
+    FrameStats,
+    Memory,
+    Materials,
+    Input,
+    /// No console, the scene's drawn on the bottom screen through the center
+    /// projection
+    Scene,
+}
+
+impl HudPage {
+    pub fn next(self) -> Self {
+        match self {
+            HudPage::Off => HudPage::Fps,
+            HudPage::Fps => HudPage::FrameStats,
+            HudPage::FrameStats => HudPage::Memory,
+            HudPage::Memory => HudPage::Materials,
+            HudPage::Materials => HudPage::Input,
+            HudPage::Input => HudPage::Scene,
+            HudPage::Scene => HudPage::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HudPage::Off => "off",
+            HudPage::Fps => "fps",
+            HudPage::FrameStats => "frame_stats",
+            HudPage::Memory => "memory",
+            HudPage::Materials => "materials",
+            HudPage::Input => "input",
+            HudPage::Scene => "scene",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "off" => HudPage::Off,
+            "fps" => HudPage::Fps,
+            "frame_stats" => HudPage::FrameStats,
+            "memory" => HudPage::Memory,
+            "materials" => HudPage::Materials,
+            "input" => HudPage::Input,
+            "scene" => HudPage::Scene,
+            _ => return None,
+        })
+    }
+}
+
+pub struct Hud {
+    page: HudPage,
+    last_frame: Instant,
+    frame_times: VecDeque<Duration>,
+    draw_stats: DrawStats,
+}
+
+impl Hud {
+    pub fn new(page: HudPage) -> Self {
+        Self {
+            page,
+            last_frame: Instant::now(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            draw_stats: DrawStats::default(),
+        }
+    }
+
+    pub fn page(&self) -> HudPage {
+        self.page
+    }
+
+    pub fn set_page(&mut self, page: HudPage) {
+        self.page = page;
+    }
+
+    /// Record the end of a frame and handle page cycling, should be called
+    /// once per frame after input has been scanned.
+    pub fn update(&mut self, hid: &Hid) {
+        let now = Instant::now();
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(now - self.last_frame);
+        self.last_frame = now;
+
+        if hid.keys_down().contains(CYCLE_KEY) {
+            self.page = self.page.next();
+            // clear whatever the previous page left behind
+            print!("\x1b[2J");
+        }
+    }
+
+    /// Record the work the last frame's drawing did
+    pub fn set_draw_stats(&mut self, draw_stats: DrawStats) {
+        self.draw_stats = draw_stats;
+    }
+
+    /// Draw the current page. `materials` is only called when the materials
+    /// page is showing, since building the table isn't free.
+    pub fn draw(&self, hid: &Hid, materials: impl FnOnce() -> Vec<String>) {
+        let lines = match self.page {
+            HudPage::Off | HudPage::Scene => return,
+            HudPage::Fps => vec![format!("FPS: {:.1}", self.fps())],
+            HudPage::FrameStats => self.frame_stats(),
+            HudPage::Memory => memory_stats(),
+            HudPage::Materials => materials(),
+            HudPage::Input => input_stats(hid),
+        };
+
+        // home the cursor and overwrite in place so the layout stays put
+        print!("\x1b[1;1H");
+        for line in lines {
+            println!("{line}\x1b[K");
+        }
+    }
+
+    fn fps(&self) -> f32 {
+        let total = self.frame_times.iter().sum::<Duration>();
+        if total.is_zero() {
+            0.0
+        } else {
+            self.frame_times.len() as f32 / total.as_secs_f32()
+        }
+    }
+
+    fn frame_stats(&self) -> Vec<String> {
+        let min = self.frame_times.iter().min().copied().unwrap_or_default();
+        let max = self.frame_times.iter().max().copied().unwrap_or_default();
+        let (processing, drawing, cmd_buf) = unsafe {
+            (
+                citro3d_sys::C3D_GetProcessingTime(),
+                citro3d_sys::C3D_GetDrawingTime(),
+                citro3d_sys::C3D_GetCmdBufUsage(),
+            )
+        };
+        vec![
+            format!("FPS: {:.1}", self.fps()),
+            format!("frame min: {:.2}ms", min.as_secs_f32() * 1000.0),
+            format!("frame max: {:.2}ms", max.as_secs_f32() * 1000.0),
+            format!("CPU: {processing:.2}ms"),
+            format!("GPU: {drawing:.2}ms"),
+            format!("cmd buf: {:.1}%", cmd_buf * 100.0),
+            format!(
+                "draw calls: {} ({} culled)",
+                self.draw_stats.draw_calls, self.draw_stats.shapes_culled
+            ),
+            format!(
+                "tex binds: {} ({} avoided)",
+                self.draw_stats.texture_binds, self.draw_stats.binds_avoided
+            ),
+        ]
+    }
+}
+
+fn memory_stats() -> Vec<String> {
+    let (linear_free, vram_free, app_free, app_used) = unsafe {
+        (
+            ctru_sys::linearSpaceFree(),
+            ctru_sys::vramSpaceFree(),
+            ctru_sys::osGetMemRegionFree(ctru_sys::MEMREGION_APPLICATION),
+            ctru_sys::osGetMemRegionUsed(ctru_sys::MEMREGION_APPLICATION),
+        )
+    };
+    let textures = TextureCache::stats();
+    vec![
+        format!("linear free: {}KiB", linear_free / 1024),
+        format!("VRAM free: {}KiB", vram_free / 1024),
+        format!("app free: {}KiB", app_free / 1024),
+        format!("app used: {}KiB", app_used / 1024),
+        format!("textures: {} ({}KiB)", textures.live, textures.bytes / 1024),
+    ]
+}
+
+fn input_stats(hid: &Hid) -> Vec<String> {
+    let (x, y) = hid.circlepad_position();
+    vec![
+        format!("circle pad: {x:4}, {y:4}"),
+        format!("held: {:?}", hid.keys_held()),
+    ]
+}
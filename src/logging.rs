@@ -0,0 +1,181 @@
+//! Logging to the console and a size-capped file on the SD card.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    panic,
+    sync::Mutex,
+};
+
+const LOG_DIR: &str = "sdmc:/trongle";
+const LOG_PATH: &str = "sdmc:/trongle/log.txt";
+const ROTATED_LOG_PATH: &str = "sdmc:/trongle/log.txt.1";
+
+/// Size at which the log file is rotated to `log.txt.1`
+const MAX_LOG_SIZE: u64 = 256 * 1024;
+/// Size of the in-memory write buffer, so routine logging doesn't hit the SD
+/// card every frame
+const WRITE_BUFFER_SIZE: usize = 4096;
+/// Number of recent lines kept in memory for the panic hook
+const RECENT_LINES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        })
+    }
+}
+
+struct LogFile {
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+impl LogFile {
+    fn open() -> std::io::Result<Self> {
+        fs::create_dir_all(LOG_DIR)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(LOG_PATH)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            writer: BufWriter::with_capacity(WRITE_BUFFER_SIZE, file),
+            size,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        writeln!(self.writer, "{line}")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+struct Logger {
+    file: Option<LogFile>,
+    recent: VecDeque<String>,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+    file: None,
+    recent: VecDeque::new(),
+});
+
+impl Logger {
+    fn log(&mut self, level: Level, line: String) {
+        if self.recent.len() == RECENT_LINES {
+            self.recent.pop_front();
+        }
+
+        if let Some(file) = &mut self.file {
+            let mut result = file.write_line(&line);
+            if result.is_ok() && level >= Level::Warn {
+                result = file.writer.flush();
+            }
+            if let Err(e) = result {
+                println!("failed to write to log file, disabling it: {e}");
+                self.file = None;
+            } else if file.size >= MAX_LOG_SIZE {
+                self.rotate();
+            }
+        }
+
+        self.recent.push_back(line);
+    }
+
+    fn rotate(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            let _ = file.writer.flush();
+        }
+        let _ = fs::remove_file(ROTATED_LOG_PATH);
+        if let Err(e) = fs::rename(LOG_PATH, ROTATED_LOG_PATH) {
+            println!("failed to rotate log file: {e}");
+        }
+        self.file = LogFile::open()
+            .map_err(|e| println!("failed to reopen log file: {e}"))
+            .ok();
+    }
+
+    fn flush(&mut self) {
+        if let Some(file) = &mut self.file {
+            let _ = file.writer.flush();
+        }
+    }
+}
+
+/// Set up logging, `file_logging` controls whether lines are also written to
+/// the SD card. Also installs a panic hook which dumps the panic and recent
+/// log lines to the log file before running the previous hook.
+pub fn init(file_logging: bool) {
+    if file_logging {
+        match LogFile::open() {
+            Ok(file) => LOGGER.lock().unwrap().file = Some(file),
+            Err(e) => println!("failed to open log file {LOG_PATH}: {e}"),
+        }
+    }
+
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // the panic may have happened while logging, don't deadlock on it
+        if let Ok(mut logger) = LOGGER.try_lock() {
+            let Logger { file, recent } = &mut *logger;
+            if let Some(file) = file {
+                let _ = file.write_line(&format!("[PANIC] {info}"));
+                let _ = file.write_line("last log lines:");
+                for line in recent.iter() {
+                    let _ = file.write_line(line);
+                }
+                let _ = file.writer.flush();
+            }
+        }
+        prev_hook(info);
+    }));
+}
+
+pub fn log(level: Level, args: fmt::Arguments) {
+    let line = format!("[{level}] {args}");
+    println!("{line}");
+    if let Ok(mut logger) = LOGGER.lock() {
+        logger.log(level, line);
+    }
+}
+
+/// Write out anything still buffered, should be called on clean exit.
+pub fn flush() {
+    if let Ok(mut logger) = LOGGER.lock() {
+        logger.flush();
+    }
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Error, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {log_error, log_info, log_warn};
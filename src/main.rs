@@ -1,7 +1,13 @@
 #![feature(allocator_api)]
 #![feature(new_uninit)]
 
-use std::{f32::consts::TAU, iter::repeat, mem::MaybeUninit, time::Duration};
+use std::{
+    error::Error,
+    f32::consts::{FRAC_PI_2, TAU},
+    mem::MaybeUninit,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use citro3d::{
     attrib::{self, Format},
@@ -31,65 +37,294 @@ use ctru::{
 };
 use ctru_sys::Handle;
 use include_texture_macro::include_texture;
-use model::{material::Material, shape::Shape, texture::Texture, Model};
+use model::{
+    animation::{Animation, Keyframe},
+    cache, deferred, find_by_name, find_by_name_mut,
+    fog::Fog,
+    material::{BlendMode, CullMode, Material},
+    physics::RigidBody,
+    primitives,
+    render_texture::RenderTexture,
+    scene::Scene,
+    shape::{Shape, ShapeError},
+    texture::Texture,
+    BillboardMode, DrawState, GeometryStats, Model,
+};
 use vert_attr::{VertAttrBuilder, VertAttrs};
 
-use crate::{model::colour::Colour, obj::parse_obj};
+use crate::{
+    camera::{Camera, ProjectionMode, ProjectionSettings},
+    hud::{Hud, HudPage},
+    logging::{log_error, log_info, log_warn},
+    math::{Aabb, Rotation, Transform, Vec2, Vec3},
+    model::colour::Colour,
+    obj::{parse_obj_with_sources, LoadError, LoadOptions, LoadStage, ObjVertex},
+    session::Session,
+};
 
 const DEADZONE: f32 = 0.01;
 const CIRCLE_DEADZONE: f32 = 15.0;
-
+/// Whether log output is also written to the SD card
+const FILE_LOGGING: bool = true;
+/// HUD page shown on startup
+const DEFAULT_HUD_PAGE: HudPage = HudPage::Off;
+/// Object which L/R spin around the vertical axis, unless the session selected
+/// another or one's been touched since
+const SPIN_MODEL: &str = "tall_box";
+/// Colour of the scene's light, RGBA
+const LIGHT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+/// Vertical field of view the camera starts with, in degrees
+const VERTICAL_FOV: f32 = 40.0;
+/// Size of each screen in pixels, the bottom one's what touches are picked
+/// through
+const TOP_SCREEN_SIZE: (f32, f32) = (400.0, 240.0);
+const BOTTOM_SCREEN_SIZE: (f32, f32) = (320.0, 240.0);
+/// Scene loaded when there's no session to restore
+const DEFAULT_SCENE: &str = "romfs:/textured-cornell-box.obj";
+/// Holding this on launch (or passing `--no-session`) skips restoring the
+/// last session
+const SKIP_SESSION_KEY: KeyPad = KeyPad::B;
+/// Cycles through hiding each shape in the scene in turn, then none
+const HIDE_SHAPE_KEY: KeyPad = KeyPad::DPAD_RIGHT;
+/// Toggles drawing everything as wireframes. Not SELECT, which cycles the
+/// HUD pages
+const WIREFRAME_KEY: KeyPad = KeyPad::DPAD_LEFT;
+/// Held to make the D-pad control the camera instead of what it usually does:
+/// left and right narrow and widen the field of view, up saves where it is
+/// and down goes back there. Pressing it still spins the selected model, and
+/// it wins over [`STEREO_KEY`] when both are held
+const CAMERA_KEY: KeyPad = KeyPad::L;
+/// Held along with [`CAMERA_KEY`] to look down on the whole scene like a map
+/// instead of moving the camera down
+const MAP_KEY: KeyPad = KeyPad::Y;
+/// Space left around the scene in the map view, as a fraction of its size
+const MAP_MARGIN: f32 = 0.1;
+/// Degrees the field of view changes by each frame the D-pad's held
+const FOV_STEP: f32 = 0.5;
+/// Held to grow and shrink the loaded scenes about the origin. Not L and R,
+/// which spin the selected model
+const SCENE_GROW_KEY: KeyPad = KeyPad::ZR;
+const SCENE_SHRINK_KEY: KeyPad = KeyPad::ZL;
+/// Factor the scene's scale changes by each frame either is held
+const SCENE_SCALE_STEP: f32 = 1.01;
+/// Held to make the D-pad adjust the 3D effect: up and down move the screen's
+/// depth, left and right how far apart the eyes are. Pressing it still spins
+/// the selected model
+const STEREO_KEY: KeyPad = KeyPad::R;
+/// Range the screen depth is kept in and how much it changes each frame
+const SCREEN_DEPTH_LIMITS: (f32, f32) = (0.5, 10.0);
+const SCREEN_DEPTH_STEP: f32 = 0.05;
+/// Range the slider's scale to the distance between the eyes is kept in and
+/// how much it changes each frame
+const INTEROCULAR_SCALE_LIMITS: (f32, f32) = (0.0, 2.0);
+const INTEROCULAR_SCALE_STEP: f32 = 0.01;
+/// Angle in radians L and R spin the selected model by, until it's changed.
+/// With [`CAMERA_KEY`] held ZL and ZR halve and double it instead of
+/// scaling the scene, within the limits
+const DEFAULT_SPIN_SNAP: f32 = 0.25;
+const SPIN_SNAP_LIMITS: (f32, f32) = (FRAC_PI_2 / 32.0, FRAC_PI_2);
+/// Where the camera's saved to, and restored from on startup when the
+/// session doesn't have it
+const CAMERA_PATH: &str = "sdmc:/draw-trongle/camera.bin";
+/// How long passing `--stress-swap` reloads the scene every frame for, to
+/// check dropped models are freed without corruption or leaks
+const STRESS_SWAP_DURATION: Duration = Duration::from_secs(5);
+/// How often the session is saved, so a crash loses at most this much
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Model the security camera screen is put in front of
+const SECURITY_SCREEN_WALL: &str = "back_wall";
+/// Name of the screen's model, hidden while drawing what it shows
+const SECURITY_SCREEN: &str = "security_screen";
+/// Size of the texture the security camera renders to
+const SECURITY_CAMERA_SIZE: u16 = 256;
+/// Tint of the glass in front of the scene
+const WINDOW_COLOUR: Colour = Colour::new(0xA0, 0xD0, 0xFF, 0x50);
+/// Tint the selected model flashes when it's spun
+const SELECTED_TINT: Colour = Colour::new(0xFF, 0x40, 0x40, 0xFF);
+/// How long the selected model flashes for
+const SELECTION_FLASH_DURATION: Duration = Duration::from_millis(150);
+/// Alpha below which the cutout's pixels are discarded
+const CUTOUT_ALPHA_THRESHOLD: u8 = 0x80;
+/// Subdivisions of the chrome sphere, around and from pole to pole
+const CHROME_SPHERE_SEGMENTS: u16 = 16;
+const CHROME_SPHERE_RINGS: u16 = 8;
+/// Cubes dropped into the scene at startup, each bouncier than the last
+const FALLING_CUBES: usize = 3;
+const FALLING_CUBE_COLOUR: Colour = Colour::new(0xFF, 0xE0, 0x40, 0xFF);
+/// Pillars along each side of the grid in front of the scene
+const PILLAR_GRID: usize = 10;
+const PILLAR_SEGMENTS: u16 = 12;
+/// Time the spinning cube takes to turn once and bob up and down
+const SPINNING_CUBE_PERIOD: Duration = Duration::from_secs(4);
+const SPINNING_CUBE_COLOUR: Colour = Colour::new(0x40, 0x80, 0xFF, 0xFF);
+/// Cone which turns to point at the camera every frame
+const POINTER_CONE: &str = "pointer_cone";
+const POINTER_CONE_SEGMENTS: u16 = 12;
+const POINTER_CONE_COLOUR: Colour = Colour::new(0xFF, 0xD0, 0x40, 0xFF);
+/// Glow of the lamp cube, bright enough to show it even with no light
+const LAMP_EMISSION: Colour = Colour::new(0xFF, 0xB0, 0x30, 0xFF);
+/// Seconds the view takes to get most of the way to where the controls put
+/// the camera, taking the jitter out of the gyro
+const CAMERA_SMOOTHING: f32 = 0.1;
+/// Distance to the near and far clip planes of the projection
+const NEAR_PLANE: f32 = 0.01;
+const FAR_PLANE: f32 = 100.0;
+/// Colour things fade to in the fog
+const FOG_COLOUR: Colour = Colour::new(0x20, 0x20, 0x28, 0xFF);
+/// Distance past which everything is lost in the fog
+const FOG_END: f32 = 20.0;
+/// How much D-pad up/down changes the fog density by per frame
+const FOG_DENSITY_STEP: f32 = 0.005;
+
+mod camera;
+mod hud;
+mod logging;
+mod math;
 mod model;
 mod obj;
+mod session;
 
-#[derive(Debug, Clone)]
+#[derive(VertAttrBuilder, Clone, Debug)]
 #[repr(C)]
-struct Vec3 {
-    x: f32,
-    y: f32,
-    z: f32,
+struct Vert {
+    pos: Vec3,
+    tex: Vec2,
 }
 
-impl Vec3 {
-    fn new(x: f32, y: f32, z: f32) -> Self {
-        Self { x, y, z }
-    }
+#[derive(VertAttrBuilder, Clone, Debug)]
+#[repr(C)]
+struct VertNorm {
+    pos: Vec3,
+    tex: Vec2,
+    norm: Vec3,
 }
 
-impl VertAttrs for Vec3 {
-    const FORMAT: Format = Format::Float;
-    const SIZE: u8 = 3;
+/// Vertex with a colour of its own, which the shader multiplies into the
+/// material colour. The normal stays in v2 so the shader inputs line up with
+/// [`VertNorm`].
+#[derive(VertAttrBuilder, Clone, Debug)]
+#[repr(C)]
+struct VertCol {
+    pos: Vec3,
+    tex: Vec2,
+    norm: Vec3,
+    colour: Colour,
 }
 
-#[derive(Debug, Clone)]
+/// Vertex with a second set of UVs for texture 1, e.g. a baked lightmap
+/// laid out differently to the diffuse texture. The colour keeps them in v4
+/// so the shader inputs line up with [`VertCol`].
+#[derive(VertAttrBuilder, Clone, Debug)]
 #[repr(C)]
-struct Vec2 {
-    x: f32,
-    y: f32,
+struct VertLightmap {
+    pos: Vec3,
+    tex: Vec2,
+    norm: Vec3,
+    colour: Colour,
+    tex1: Vec2,
+}
+
+/// Vertex layouts the shader can draw.
+pub trait Vertex: VertAttrBuilder + Clone + 'static {
+    /// Whether the layout has a colour in v3
+    const HAS_COLOUR: bool;
+    /// Whether the layout has UVs for texture 1 in v4, otherwise it shares
+    /// texture 0's
+    const HAS_SECOND_UV: bool;
+
+    fn position(&self) -> &Vec3;
+
+    fn uv(&self) -> &Vec2;
+
+    /// Copy of the vertex moved to `pos`
+    fn with_position(&self, pos: Vec3) -> Self;
+}
+
+impl Vertex for Vert {
+    const HAS_COLOUR: bool = false;
+    const HAS_SECOND_UV: bool = false;
+
+    fn position(&self) -> &Vec3 {
+        &self.pos
+    }
+
+    fn uv(&self) -> &Vec2 {
+        &self.tex
+    }
+
+    fn with_position(&self, pos: Vec3) -> Self {
+        Self {
+            pos,
+            ..self.clone()
+        }
+    }
 }
 
-impl Vec2 {
-    fn new(x: f32, y: f32) -> Self {
-        Self { x, y }
+impl Vertex for VertNorm {
+    const HAS_COLOUR: bool = false;
+    const HAS_SECOND_UV: bool = false;
+
+    fn position(&self) -> &Vec3 {
+        &self.pos
+    }
+
+    fn uv(&self) -> &Vec2 {
+        &self.tex
+    }
+
+    fn with_position(&self, pos: Vec3) -> Self {
+        Self {
+            pos,
+            ..self.clone()
+        }
     }
 }
 
-impl VertAttrs for Vec2 {
-    const FORMAT: Format = Format::Float;
-    const SIZE: u8 = 2;
+impl Vertex for VertCol {
+    const HAS_COLOUR: bool = true;
+    const HAS_SECOND_UV: bool = false;
+
+    fn position(&self) -> &Vec3 {
+        &self.pos
+    }
+
+    fn uv(&self) -> &Vec2 {
+        &self.tex
+    }
+
+    fn with_position(&self, pos: Vec3) -> Self {
+        Self {
+            pos,
+            ..self.clone()
+        }
+    }
 }
 
-#[derive(VertAttrBuilder, Clone, Debug)]
-#[repr(C)]
-struct Vert {
-    pos: Vec3,
-    tex: Vec2,
+impl Vertex for VertLightmap {
+    const HAS_COLOUR: bool = true;
+    const HAS_SECOND_UV: bool = true;
+
+    fn position(&self) -> &Vec3 {
+        &self.pos
+    }
+
+    fn uv(&self) -> &Vec2 {
+        &self.tex
+    }
+
+    fn with_position(&self, pos: Vec3) -> Self {
+        Self {
+            pos,
+            ..self.clone()
+        }
+    }
 }
 
 const SHADER: &[u8] = include_shader!("../shader.pica");
 
 const BOWSER: &[u8] = include_texture!("../bowser.png");
 const PEACH: &[u8] = include_texture!("../peach.png");
+const STUDIO: &[u8] = include_texture!("../studio.png", rgb565);
 
 const PACKET_INFO_SIZE: usize = 8;
 const MAX_PACKET_SIZE: usize = 32;
@@ -168,7 +403,7 @@ impl CirclePadPro {
                 .ir_user
                 .request_input_polling(CPP_CONNECTION_POLLING_PERIOD_MS)
             {
-                println!("Error: {e:?}");
+                log_warn!("Error: {e:?}");
             }
 
             let recv_event_result = self
@@ -199,7 +434,7 @@ impl CirclePadPro {
             .release_received_data(packet_count as u32)
             .unwrap();
         if let Err(e) = self.ir_user.request_input_polling(CPP_POLLING_PERIOD_MS) {
-            println!("Error: {e:?}");
+            log_warn!("Error: {e:?}");
         }
     }
 
@@ -241,16 +476,22 @@ pub struct Uniforms {
     pub material_ambient: Index,
     pub material_diffuse: Index,
     pub material_specular: Index,
+    pub uv_transform: Index,
+    pub use_vertex_colour: Index,
+    pub use_second_uv: Index,
+    pub use_env_map: Index,
 }
 
 fn main() {
     let apt = Apt::new().unwrap();
     let _fs = Fs::new().unwrap();
     let gfx = Gfx::new().unwrap();
-    let _console = Console::new(gfx.bottom_screen.borrow_mut());
+    // swapped for a render target while the scene's drawn on the bottom screen
+    let mut console = Some(Console::new(gfx.bottom_screen.borrow_mut()));
     let mut soc = Soc::new().unwrap();
     // will use `tty` if this fails
     let _ = soc.redirect_to_3dslink(true, true);
+    logging::init(FILE_LOGGING);
     let _romfs = RomFS::new().unwrap();
 
     //let mut cpp = CirclePadPro::new().unwrap();
@@ -273,24 +514,20 @@ fn main() {
             let r = ResultCode(ctru_sys::HIDUSER_GetGyroscopeRawToDpsCoefficient(
                 coeff.as_mut_ptr(),
             ));
-            println!("{:?}", r);
+            log_info!("{:?}", r);
             coeff.assume_init()
         }
     };
 
-    println!("coeff: {coeff}");
+    log_info!("coeff: {coeff}");
 
     let top_screen = TopScreen3D::from(&gfx.top_screen);
 
-    let (mut top_screen_left, mut top_screen_right) = top_screen.split_mut();
-
-    let RawFrameBuffer { width, height, .. } = top_screen_left.raw_framebuffer();
-    let mut top_left_target = render::Target::new(width, height, top_screen_left, Some(Depth16))
-        .expect("failed to create left render target");
-
-    let RawFrameBuffer { width, height, .. } = top_screen_right.raw_framebuffer();
-    let mut top_right_target = render::Target::new(width, height, top_screen_right, Some(Depth16))
-        .expect("failed to create right render target");
+    // swapped for a single wide target whenever the 3D is off, see below
+    let mut top_wide = false;
+    let mut top_targets = Some(
+        make_top_targets(&gfx, &top_screen, false).expect("failed to create top render targets"),
+    );
 
     let shader_lib = shader::Library::from_bytes(SHADER).expect("failed to load shader");
     let vert_shader = shader_lib.get(0).unwrap();
@@ -307,6 +544,10 @@ fn main() {
     let amb_uniform = vert_prog.get_uniform("mat_amb").unwrap();
     let dif_uniform = vert_prog.get_uniform("mat_dif").unwrap();
     let spe_uniform = vert_prog.get_uniform("mat_spe").unwrap();
+    let uv_uniform = vert_prog.get_uniform("uvXform").unwrap();
+    let vert_col_uniform = vert_prog.get_uniform("useVertCol").unwrap();
+    let second_uv_uniform = vert_prog.get_uniform("useTex1").unwrap();
+    let env_map_uniform = vert_prog.get_uniform("useEnvMap").unwrap();
 
     let uniforms = Uniforms {
         model_matrix: model_uniform,
@@ -317,25 +558,76 @@ fn main() {
         material_ambient: amb_uniform,
         material_diffuse: dif_uniform,
         material_specular: spe_uniform,
+        uv_transform: uv_uniform,
+        use_vertex_colour: vert_col_uniform,
+        use_second_uv: second_uv_uniform,
+        use_env_map: env_map_uniform,
     };
 
     //println!("Hello, World!");
     //println!("\x1b[29;16HPress Start to exit");
 
-    let mut cam_pos = Vec3::new(0.0, 0.0, 0.0);
-    let mut cam_rot = Vec3::new(0.0, 0.0, 0.0);
+    hid.scan_input();
+    let session = if hid.keys_held().contains(SKIP_SESSION_KEY)
+        || std::env::args().any(|a| a == "--no-session")
+    {
+        log_info!("not restoring last session");
+        None
+    } else {
+        Session::load()
+    };
+
+    let mut camera = Camera::new(ProjectionSettings::new(
+        VERTICAL_FOV.to_radians(),
+        NEAR_PLANE,
+        FAR_PLANE,
+    ));
+    camera.smoothing = CAMERA_SMOOTHING;
+    camera.collide = true;
+    let mut selected_model = SPIN_MODEL.to_owned();
+    let mut hud_page = DEFAULT_HUD_PAGE;
+    let mut scenes = vec![DEFAULT_SCENE.to_owned()];
+    let mut stereo = StereoSettings::default();
+    let mut spin_snap = DEFAULT_SPIN_SNAP;
+    let mut restored = false;
+    if let Some(session) = session {
+        if let Some(pos) = session.cam_pos {
+            camera.pos = pos;
+            camera.rot = session.cam_rot;
+            restored = true;
+        }
+        selected_model = session.selected_model.unwrap_or(selected_model);
+        hud_page = session.hud_page;
+        if !session.scenes.is_empty() {
+            scenes = session.scenes;
+        }
+        stereo.screen_depth = session.screen_depth.unwrap_or(stereo.screen_depth);
+        stereo.interocular_scale = session
+            .interocular_scale
+            .unwrap_or(stereo.interocular_scale);
+        // back within the limits, in case they've changed since
+        stereo.adjust(0.0, 0.0);
+        let (min, max) = SPIN_SNAP_LIMITS;
+        spin_snap = session.spin_snap.unwrap_or(spin_snap).clamp(min, max);
+    }
+    // the session is where the camera was last, the saved one is only for
+    // when there's no session or it's skipped, and going back to on demand
+    if !restored && camera.load(CAMERA_PATH) {
+        log_info!("restored the camera from {CAMERA_PATH}");
+        restored = true;
+    }
 
     /*let mut mdl = Model::new(
         Vec3::new(0.0, 0.0, -1.5),
         Vec3::new(0.0, 0.0, 0.0),
         vec![
             Shape::new(
-                Material::new(
-                    Some(Texture::new(64, 64, BOWSER.to_vec())),
-                    None,
-                    None,
-                    true,
-                ),
+                Material::builder()
+                    .texture(Rc::new(Texture::from_embedded(BOWSER).unwrap()))
+                    .vertex_colours(true)
+                    // seen from both sides, without a reversed copy of the quad
+                    .cull_mode(CullMode::None)
+                    .build(),
                 buffer::Primitive::TriangleFan,
                 &[
                     Vert {
@@ -356,76 +648,416 @@ fn main() {
                     },
                 ],
             ),
-            Shape::new(
-                Material::new(
-                    Some(Texture::new(
-                        64,
-                        64,
-                        repeat(0).take(64 * 64 * 4).collect::<Vec<_>>(),
-                    )),
-                    Some(Colour::new(0xFF, 0x00, 0xFF, 0xFF)),
-                    None,
-                    false,
-                ),
-                buffer::Primitive::TriangleFan,
-                &[
-                    Vert {
-                        pos: Vec3::new(0.5, 0.5, 0.0),
-                        tex: Vec2::new(0.0, 1.0),
-                    },
-                    Vert {
-                        pos: Vec3::new(0.5, -0.5, 0.0),
-                        tex: Vec2::new(0.0, 0.0),
-                    },
-                    Vert {
-                        pos: Vec3::new(-0.5, -0.5, 0.0),
-                        tex: Vec2::new(1.0, 0.0),
-                    },
-                    Vert {
-                        pos: Vec3::new(-0.5, 0.5, 0.0),
-                        tex: Vec2::new(1.0, 1.0),
-                    },
-                ],
-            ),
         ],
     );*/
-    let models = parse_obj("romfs:/textured-cornell-box.obj");
+    let mut models: Vec<Model<VertNorm>> = Vec::new();
+    scenes.retain(|scene| match load_scene(scene) {
+        Ok(scene_models) => {
+            models.extend(scene_models);
+            true
+        }
+        Err(e) => {
+            log_warn!("skipping scene {scene}: {e}");
+            false
+        }
+    });
+    if scenes.is_empty() {
+        match load_scene(DEFAULT_SCENE) {
+            Ok(scene_models) => {
+                models = scene_models;
+                scenes.push(DEFAULT_SCENE.to_owned());
+            }
+            // shown rather than panicking, so it's clear why nothing's drawn
+            Err(e) => log_error!("failed to load {DEFAULT_SCENE}: {e}"),
+        }
+    }
     for i in &models {
         println!("{:#?}", i);
     }
+    let stats = models.iter().map(Model::stats).sum::<GeometryStats>();
+    log_info!(
+        "scene has {} shapes, {} vertices, {} triangles using {} KiB of linear memory",
+        stats.shapes,
+        stats.verts,
+        stats.triangles,
+        stats.memory_bytes.div_ceil(1024)
+    );
+
+    // the models added below are placed around these, so aren't scaled
+    let scene_models = models.len();
+    let scene_aabb = models
+        .iter()
+        .map(|m| m.aabb().clone())
+        .reduce(|a, b| a.union(&b));
+    if !restored {
+        if let Some(aabb) = &scene_aabb {
+            camera.pos = frame_camera(aabb, camera.projection.fov());
+        }
+    }
+
+    if let Some(aabb) = &scene_aabb {
+        match window(aabb) {
+            Ok(window) => models.push(window),
+            Err(e) => log_warn!("not showing the window: {e}"),
+        }
+        match cutout(aabb) {
+            Ok(cutout) => models.push(cutout),
+            Err(e) => log_warn!("not showing the cutout: {e}"),
+        }
+        match chrome_sphere(aabb) {
+            Ok(sphere) => models.push(sphere),
+            Err(e) => log_warn!("not showing the chrome sphere: {e}"),
+        }
+        match sprite(aabb) {
+            Ok(sprite) => models.push(sprite),
+            Err(e) => log_warn!("not showing the sprite: {e}"),
+        }
+        match spinning_cube(aabb) {
+            Ok(cube) => models.push(cube),
+            Err(e) => log_warn!("not showing the spinning cube: {e}"),
+        }
+        match pointer_cone(aabb) {
+            Ok(cone) => models.push(cone),
+            Err(e) => log_warn!("not showing the pointer cone: {e}"),
+        }
+        match falling_cubes(aabb) {
+            Ok(cubes) => models.extend(cubes),
+            Err(e) => log_warn!("not dropping any cubes: {e}"),
+        }
+    }
+
+    // attached to the spinning model so it's carried round with it
+    if let Some(table) = find_by_name_mut(&mut models, SPIN_MODEL) {
+        match lamp(&table.aabb().clone()) {
+            Ok(lamp) => table.add_child(lamp),
+            Err(e) => log_warn!("not showing the lamp: {e}"),
+        }
+    }
+
+    let mut security_cam = RenderTexture::new(SECURITY_CAMERA_SIZE, SECURITY_CAMERA_SIZE)
+        .map_err(|e| log_warn!("not showing the security camera: {e}"))
+        .ok();
+    let security_screen = security_cam.as_ref().and_then(|cam| {
+        let wall = find_by_name(&models, SECURITY_SCREEN_WALL)?;
+        security_screen(wall.aabb(), scene_aabb.as_ref()?, cam.texture().clone())
+            .map_err(|e| log_warn!("not showing the security camera: {e}"))
+            .ok()
+    });
+
+    let mut scene = Scene::new(camera, models);
+    scene.light_colour = LIGHT_COLOUR;
+    if let Some(screen) = security_screen {
+        scene.add_model(screen);
+    }
+    if let Some(aabb) = &scene_aabb {
+        match pillars(aabb) {
+            Ok((pillar, transforms)) => scene.add_instanced(pillar, transforms),
+            Err(e) => log_warn!("not showing the pillars: {e}"),
+        }
+    }
+
+    let mut draw_state = DrawState::default();
+    let mut hud = Hud::new(hud_page);
+    let make_session = |camera: &Camera,
+                        hud: &Hud,
+                        selected_model: &str,
+                        stereo: &StereoSettings,
+                        spin_snap: f32| Session {
+        scenes: scenes.clone(),
+        cam_pos: Some(camera.pos.clone()),
+        cam_rot: camera.rot.clone(),
+        selected_model: Some(selected_model.to_owned()),
+        hud_page: hud.page(),
+        screen_depth: Some(stereo.screen_depth),
+        interocular_scale: Some(stereo.interocular_scale),
+        spin_snap: Some(spin_snap),
+    };
+    let mut last_session_save = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut selection_flash = Duration::ZERO;
+    let mut fog_density = 0.0_f32;
+    // index into every model's shapes one after another
+    let mut hidden_shape: Option<usize> = None;
+    // only while the HUD is on its scene page, see below
+    let mut bottom_target: Option<render::Target> = None;
+    // rebuilt only when what they're made from changes
+    let mut projections: Option<(ProjectionSettings, StereoSettings, f32, Projections)> = None;
+    // logged once the stereo key's let go, rather than every frame it moves
+    let mut stereo_adjusted = false;
+    // when it started, how many swaps so far, and the linear memory free
+    // before any of them
+    let mut stress_swap = std::env::args()
+        .any(|a| a == "--stress-swap")
+        .then(|| (Instant::now(), 0_usize, linear_free()));
 
     while apt.main_loop() {
         gfx.wait_for_vblank();
+        let dt = last_frame.elapsed();
+        last_frame = Instant::now();
+        scene.update(dt);
+        let eye = scene.camera.eye();
+        if let Some(cone) = scene.model_mut(POINTER_CONE) {
+            cone.look_at(eye, Vec3::new(0.0, 1.0, 0.0));
+        }
+        if let Some((started, swaps, free_before)) = &mut stress_swap {
+            if started.elapsed() < STRESS_SWAP_DURATION {
+                // each swap drops models the last frame drew, which may still
+                // be in flight
+                if let Some(path) = scenes.get(*swaps % scenes.len().max(1)) {
+                    match load_scene(path) {
+                        Ok(models) => scene.set_models(models),
+                        Err(e) => log_warn!("stress swap couldn't load {path}: {e}"),
+                    }
+                }
+                *swaps += 1;
+            } else {
+                // the last couple of frames' models are still queued, so
+                // expect it to be down by about that much and no more
+                log_info!(
+                    "swapped scenes {swaps} times, linear memory free went from {} to {} KiB",
+                    *free_before / 1024,
+                    linear_free() / 1024
+                );
+                stress_swap = None;
+            }
+        }
 
         hid.scan_input();
         if hid.keys_down().contains(KeyPad::START) {
             break;
         }
+        hud.update(&hid);
+
+        // the console and the bottom screen's render target both need the
+        // screen, so one's dropped before the other's made
+        let scene_on_bottom = hud.page() == HudPage::Scene;
+        if scene_on_bottom == console.is_some() {
+            if scene_on_bottom {
+                console = None;
+                let mut screen = gfx.bottom_screen.borrow_mut();
+                let RawFrameBuffer { width, height, .. } = screen.raw_framebuffer();
+                match render::Target::new(width, height, screen, Some(Depth16)) {
+                    Ok(target) => bottom_target = Some(target),
+                    Err(e) => {
+                        console = Some(Console::new(gfx.bottom_screen.borrow_mut()));
+                        log_warn!("not drawing the scene on the bottom screen: {e}");
+                        hud.set_page(HudPage::Scene.next());
+                    }
+                }
+            } else {
+                bottom_target = None;
+                console = Some(Console::new(gfx.bottom_screen.borrow_mut()));
+            }
+        }
+
+        let camera_keys = hid.keys_held().contains(CAMERA_KEY);
+        let map_view = camera_keys && hid.keys_held().contains(MAP_KEY);
 
         let (x, y) = hid.circlepad_position();
         let (x, y) = (x as f32, y as f32);
         //println!("{x}, {y}");
-        if x.abs() > CIRCLE_DEADZONE {
-            cam_pos.x -= x / 1000.0
+        let forward = if y.abs() > CIRCLE_DEADZONE {
+            y / 1000.0
+        } else {
+            0.0
+        };
+        let right = if x.abs() > CIRCLE_DEADZONE {
+            x / 1000.0
+        } else {
+            0.0
+        };
+        let up = if hid.keys_held().contains(KeyPad::X) {
+            0.01
+        } else if hid.keys_held().contains(KeyPad::Y) && !map_view {
+            -0.01
+        } else {
+            0.0
+        };
+        scene.camera.move_relative(forward, right, up);
+        scene.resolve_camera_collision();
+
+        let stereo_keys = hid.keys_held().contains(STEREO_KEY) && !camera_keys;
+        let held = |key| hid.keys_held().contains(key);
+        if stereo_keys {
+            let depth = match (held(KeyPad::DPAD_UP), held(KeyPad::DPAD_DOWN)) {
+                (true, false) => SCREEN_DEPTH_STEP,
+                (false, true) => -SCREEN_DEPTH_STEP,
+                _ => 0.0,
+            };
+            let interocular = match (held(KeyPad::DPAD_RIGHT), held(KeyPad::DPAD_LEFT)) {
+                (true, false) => INTEROCULAR_SCALE_STEP,
+                (false, true) => -INTEROCULAR_SCALE_STEP,
+                _ => 0.0,
+            };
+            stereo_adjusted |= stereo.adjust(depth, interocular);
+        } else if stereo_adjusted {
+            stereo_adjusted = false;
+            log_info!(
+                "screen depth {:.2}, interocular scale {:.2}",
+                stereo.screen_depth,
+                stereo.interocular_scale
+            );
+        }
+        // the D-pad does nothing else while it's adjusting something
+        let dpad_taken = camera_keys || stereo_keys;
+
+        // fog is off at zero density
+        let fog_change = if dpad_taken {
+            0.0
+        } else if hid.keys_held().contains(KeyPad::DPAD_UP) {
+            FOG_DENSITY_STEP
+        } else if hid.keys_held().contains(KeyPad::DPAD_DOWN) {
+            -FOG_DENSITY_STEP
+        } else {
+            0.0
+        };
+        if fog_change != 0.0 {
+            fog_density += fog_change;
+            // snapped so repeated steps down reach exactly zero
+            if fog_density < FOG_DENSITY_STEP / 2.0 {
+                fog_density = 0.0;
+            }
+            scene
+                .settings_mut()
+                .set_fog((fog_density > 0.0).then(|| Fog {
+                    colour: FOG_COLOUR,
+                    density: fog_density,
+                    end: FOG_END,
+                }));
         }
-        if y.abs() > CIRCLE_DEADZONE {
-            cam_pos.z += y / 1000.0
+
+        if camera_keys {
+            let change = if hid.keys_held().contains(KeyPad::DPAD_RIGHT) {
+                FOV_STEP
+            } else if hid.keys_held().contains(KeyPad::DPAD_LEFT) {
+                -FOV_STEP
+            } else {
+                0.0
+            };
+            if change != 0.0 {
+                let projection = &mut scene.camera.projection;
+                projection.set_fov(projection.fov() + change.to_radians());
+            }
         }
-        if hid.keys_held().contains(KeyPad::X) {
-            cam_pos.y -= 0.01;
+
+        if camera_keys {
+            if hid.keys_down().contains(KeyPad::DPAD_UP) {
+                match scene.camera.save(CAMERA_PATH) {
+                    Ok(()) => log_info!("saved the camera to {CAMERA_PATH}"),
+                    Err(e) => log_warn!("failed to save the camera: {e}"),
+                }
+            } else if hid.keys_down().contains(KeyPad::DPAD_DOWN) && !scene.camera.load(CAMERA_PATH)
+            {
+                log_info!("no camera saved at {CAMERA_PATH}");
+            }
         }
-        if hid.keys_held().contains(KeyPad::Y) {
-            cam_pos.y += 0.01;
+
+        if !dpad_taken && hid.keys_down().contains(WIREFRAME_KEY) {
+            draw_state.set_wireframe(!draw_state.wireframe());
         }
 
-        /*if hid.keys_down().contains(KeyPad::R) {
-            mdl.rot.z -= 0.25;
-            mdl.rot.z %= TAU;
+        let scale = if camera_keys {
+            1.0
+        } else if hid.keys_held().contains(SCENE_GROW_KEY) {
+            SCENE_SCALE_STEP
+        } else if hid.keys_held().contains(SCENE_SHRINK_KEY) {
+            1.0 / SCENE_SCALE_STEP
+        } else {
+            1.0
+        };
+        if scale != 1.0 {
+            for mdl in &mut scene.models_mut()[..scene_models] {
+                mdl.set_scale(mdl.transform().scale.scale(scale));
+                mdl.set_translation(mdl.transform().translation.scale(scale));
+            }
+        }
+
+        if !dpad_taken && hid.keys_down().contains(HIDE_SHAPE_KEY) {
+            let count = scene
+                .models()
+                .iter()
+                .map(|m| m.shapes().len())
+                .sum::<usize>();
+            hidden_shape = match hidden_shape {
+                None if count > 0 => Some(0),
+                Some(i) if i + 1 < count => Some(i + 1),
+                _ => None,
+            };
+            let mut i = 0;
+            for mdl in scene.models_mut() {
+                for idx in 0..mdl.shapes().len() {
+                    let hide = hidden_shape == Some(i);
+                    if hide {
+                        log_info!("hiding {}/{}", mdl.name, mdl.shapes()[idx].name());
+                    }
+                    mdl.set_shape_visible(idx, !hide);
+                    i += 1;
+                }
+            }
+        }
+
+        // touching a model selects it, through the center view the bottom
+        // screen shows on the HUD's scene page
+        if hid.keys_down().contains(KeyPad::TOUCH) {
+            let (x, y) = hid.touch_position();
+            let (width, height) = BOTTOM_SCREEN_SIZE;
+            let dir = scene.camera.ray(
+                x as f32 / width * 2.0 - 1.0,
+                1.0 - y as f32 / height * 2.0,
+                width / height,
+            );
+            match scene.pick(&scene.camera.eye(), &dir) {
+                Some((i, distance)) => {
+                    let name = scene.models()[i].name.clone();
+                    log_info!("touched {name}, {distance:.2} away");
+                    if name != selected_model {
+                        if let Some(mdl) = scene.model_mut(&selected_model) {
+                            mdl.set_tint(None);
+                        }
+                        selected_model = name;
+                    }
+                    selection_flash = SELECTION_FLASH_DURATION;
+                }
+                None => log_info!("touched nothing"),
+            }
+        }
+
+        if camera_keys {
+            let (min, max) = SPIN_SNAP_LIMITS;
+            let snap = if hid.keys_down().contains(SCENE_GROW_KEY) {
+                (spin_snap * 2.0).min(max)
+            } else if hid.keys_down().contains(SCENE_SHRINK_KEY) {
+                (spin_snap / 2.0).max(min)
+            } else {
+                spin_snap
+            };
+            if snap != spin_snap {
+                spin_snap = snap;
+                log_info!("spin snap {:.1} degrees", spin_snap.to_degrees());
+            }
+        }
+        if let Some(mdl) = scene.model_mut(&selected_model) {
+            let spin = if hid.keys_down().contains(KeyPad::R) {
+                -spin_snap
+            } else if hid.keys_down().contains(KeyPad::L) {
+                spin_snap
+            } else {
+                0.0
+            };
+            // flashes for a moment each time it's spun
+            let flashing = !selection_flash.is_zero();
+            selection_flash = selection_flash.saturating_sub(dt);
+            if flashing != mdl.is_tinted() {
+                mdl.set_tint(flashing.then_some(SELECTED_TINT));
+            }
+            if spin != 0.0 {
+                selection_flash = SELECTION_FLASH_DURATION;
+                mdl.set_rotation(
+                    &Rotation::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), spin)
+                        * &mdl.transform().rotation,
+                );
+            }
         }
-        if hid.keys_down().contains(KeyPad::L) {
-            mdl.rot.z += 0.25;
-            mdl.rot.z %= TAU;
-        }*/
 
         let (roll, pitch, yaw) = hid.gyroscope_rate().unwrap().into();
         let (roll, pitch, yaw) = (
@@ -435,20 +1067,12 @@ fn main() {
         );
 
         if hid.keys_held().contains(KeyPad::A) {
-            if roll.abs() > DEADZONE {
-                cam_rot.x += roll;
-                cam_rot.x %= TAU;
-            }
-
-            if pitch.abs() > DEADZONE {
-                cam_rot.y -= pitch;
-                cam_rot.y %= TAU;
-            }
-
-            if yaw.abs() > DEADZONE {
-                cam_rot.z -= yaw;
-                cam_rot.z %= TAU;
-            }
+            let outside_deadzone = |rate: f32| if rate.abs() > DEADZONE { rate } else { 0.0 };
+            scene.camera.rotate(
+                -outside_deadzone(roll),
+                outside_deadzone(pitch),
+                outside_deadzone(yaw),
+            );
         }
 
         /*cpp.scan_input();
@@ -460,50 +1084,571 @@ fn main() {
             );
             //println!("c: {x}, {y}");
             if x.abs() > CIRCLE_DEADZONE {
-                cam_rot.y += x / 1000.0
+                scene.camera.rotate(0.0, x / 1000.0, 0.0);
             }
             if y.abs() > CIRCLE_DEADZONE {
-                cam_rot.x -= y / 1000.0
+                scene.camera.rotate(-y / 1000.0, 0.0, 0.0);
             }
         }*/
 
-        gpu.render_frame_with(|inst| {
-            let mut camera_matrix = Matrix4::identity();
+        // the top screen's twice as wide with the 3D off, as the pixels for
+        // the right eye aren't needed. The old targets have to go before the
+        // new ones can borrow the screen
+        let slider = ctru::os::current_3d_slider_state();
+        let wide = slider == 0.0;
+        if wide != top_wide {
+            top_wide = wide;
+            top_targets = None;
+            top_targets = make_top_targets(&gfx, &top_screen, wide)
+                .or_else(|e| {
+                    log_warn!("failed to switch the top screen's wide mode: {e}");
+                    make_top_targets(&gfx, &top_screen, !wide)
+                })
+                .map_err(|e| log_error!("no render targets for the top screen: {e}"))
+                .ok();
+        }
 
-            camera_matrix.translate(cam_pos.x, cam_pos.y, cam_pos.z);
-            camera_matrix.rotate_x(cam_rot.x);
-            camera_matrix.rotate_y(cam_rot.y);
-            camera_matrix.rotate_z(cam_rot.z);
+        // only for this frame, the camera's put back after drawing
+        let normal_camera = match &scene_aabb {
+            Some(aabb) if map_view => {
+                let map = map_camera(aabb, &scene.camera);
+                Some(std::mem::replace(&mut scene.camera, map))
+            }
+            _ => None,
+        };
 
-            inst.bind_vertex_uniform(uniforms.camera_matrix, &camera_matrix);
+        let stale = projections
+            .as_ref()
+            .map_or(true, |(settings, last_stereo, last_slider, _)| {
+                *settings != scene.camera.projection
+                    || *last_stereo != stereo
+                    || *last_slider != slider
+            });
+        if stale {
+            let settings = scene.camera.projection.clone();
+            let built = calculate_projections(&settings, &stereo, slider);
+            projections = Some((settings, stereo.clone(), slider, built));
+        }
+        // UNWRAP: set just above if it wasn't already
+        let Projections {
+            left_eye,
+            right_eye,
+            center,
+        } = &projections.as_ref().unwrap().3;
 
-            let mut render_to = |target: &mut render::Target, projection| {
-                target.clear(ClearFlags::ALL, 0, 0);
+        gpu.render_frame_with(|inst| {
+            if let Some(cam) = &mut security_cam {
+                cam.clear(scene.clear_colour);
+                cam.select(inst);
+                // the screen isn't drawn into the camera's view, as it would
+                // be sampling the texture being rendered to
+                let set_screen_visible = |scene: &mut Scene<_>, visible| {
+                    if let Some(screen) = scene.model_mut(SECURITY_SCREEN) {
+                        screen.set_visible(visible);
+                    }
+                };
+                set_screen_visible(&mut scene, false);
+                scene.draw(inst, &uniforms, &mut draw_state, center);
+                set_screen_visible(&mut scene, true);
+            }
+            match &mut top_targets {
+                Some(TopTargets::Stereo(left, right)) => {
+                    // with the 3D off only the left eye is shown, so drawing
+                    // the right would be wasted
+                    let eyes = if slider > 0.0 { 2 } else { 1 };
+                    for (target, projection) in [(left, left_eye), (right, right_eye)]
+                        .into_iter()
+                        .take(eyes)
+                    {
+                        target.clear(ClearFlags::ALL, scene.clear_colour, 0);
+                        inst.select_render_target(target).unwrap();
+                        scene.draw(inst, &uniforms, &mut draw_state, projection);
+                    }
+                }
+                // the eyes are in the same place with the 3D off, so either
+                // projection will do
+                Some(TopTargets::Wide(target)) => {
+                    target.clear(ClearFlags::ALL, scene.clear_colour, 0);
+                    inst.select_render_target(target).unwrap();
+                    scene.draw(inst, &uniforms, &mut draw_state, left_eye);
+                }
+                None => {}
+            }
+            if let Some(target) = &mut bottom_target {
+                target.clear(ClearFlags::ALL, scene.clear_colour, 0);
                 inst.select_render_target(target).unwrap();
+                scene.draw(inst, &uniforms, &mut draw_state, center);
+            }
+        });
+        deferred::end_frame();
+        if let Some(camera) = normal_camera {
+            scene.camera = camera;
+        }
+        hud.set_draw_stats(draw_state.take_stats());
+
+        hud.draw(&hid, || {
+            scene
+                .models()
+                .iter()
+                .flat_map(|mdl| {
+                    mdl.shapes().iter().map(move |shape| {
+                        let mat = shape.material();
+                        format!(
+                            "{}/{}: tex {} col {:?}",
+                            mdl.name,
+                            shape.name(),
+                            mat.get_texture().is_some(),
+                            mat.colour()
+                        )
+                    })
+                })
+                .collect()
+        });
 
-                inst.bind_vertex_uniform(uniforms.projection_matrix, projection);
-                /*gpu.set_attr_info(&v_attrs);
-                gpu.draw_arrays(buffer::Primitive::TriangleFan, buf_vtos);*/
-                //mdl.draw(inst, &uniforms);
-                for mdl in &models {
-                    mdl.draw(inst, &uniforms);
-                }
-            };
+        if last_session_save.elapsed() >= SESSION_SAVE_INTERVAL {
+            make_session(&scene.camera, &hud, &selected_model, &stereo, spin_snap).save();
+            last_session_save = Instant::now();
+        }
+
+        //println!("{:?}", hid.gyroscope_rate().unwrap());
+    }
+
+    make_session(&scene.camera, &hud, &selected_model, &stereo, spin_snap).save();
+
+    // the loop has exited so nothing more will be submitted to the GPU
+    drop(scene);
+    deferred::flush();
+
+    logging::flush();
+}
+
+fn linear_free() -> u32 {
+    // SAFETY: only reads the allocator's bookkeeping
+    unsafe { ctru_sys::linearSpaceFree() }
+}
+
+/// Load the models in an OBJ file, going through the model cache.
+fn load_scene<T: ObjVertex>(path: &str) -> Result<Vec<Model<T>>, LoadError> {
+    let options = LoadOptions::default();
+    if let Some(models) = cache::load(path, &options) {
+        return Ok(models);
+    }
+    let mut progress = |stage: LoadStage, done: usize, total: usize| {
+        print!("\x1b[1;1HLoading {done}/{total} {stage}...\x1b[K");
+        // nothing else is drawing frames yet, so get the console on screen
+        unsafe {
+            ctru_sys::gfxFlushBuffers();
+            ctru_sys::gfxSwapBuffers();
+        }
+    };
+    let (mut models, sources) = parse_obj_with_sources(path, &options, Some(&mut progress))?;
+    print!("\x1b[1;1H\x1b[K");
+    let merged = models.iter_mut().map(Model::optimize).sum::<usize>();
+    if merged > 0 {
+        log_info!("merged away {merged} shapes in {path}");
+    }
+    cache::store(path, &options, &sources, &models);
+    Ok(models)
+}
 
-            let Projections {
-                left_eye,
-                right_eye,
-                ..
-            } = calculate_projections();
+/// Quad showing `texture` just in front of `wall`, on the side facing the
+/// middle of the scene.
+fn security_screen(
+    wall: &Aabb,
+    scene: &Aabb,
+    texture: Rc<Texture>,
+) -> Result<Model<VertNorm>, ShapeError> {
+    let center = wall.center();
+    let extents = wall.extents();
+    let facing = if scene.center().z > center.z {
+        1.0
+    } else {
+        -1.0
+    };
+    // half the size of the wall, and a little off it so they don't z-fight
+    let (w, h) = (extents.x / 2.0, extents.y / 2.0);
+    let z = center.z + facing * (extents.z + 0.001);
+
+    let mut corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    if facing < 0.0 {
+        // keep the winding counter-clockwise seen from the front
+        corners.reverse();
+    }
+    let verts = corners.map(|(x, y): (f32, f32)| VertNorm {
+        pos: Vec3::new(center.x + x * w, center.y + y * h, z),
+        tex: Vec2::new((x + 1.0) / 2.0, (y + 1.0) / 2.0),
+        norm: Vec3::new(0.0, 0.0, facing),
+    });
+
+    let mat = Material::builder()
+        .texture(texture)
+        // full emission so the screen isn't affected by lighting
+        .emission(Colour::WHITE)
+        .build();
+    Ok(Model::new(
+        SECURITY_SCREEN,
+        Transform::IDENTITY,
+        vec![Shape::new(mat, buffer::Primitive::TriangleFan, &verts)?],
+    ))
+}
 
-            render_to(&mut top_left_target, &left_eye);
-            render_to(&mut top_right_target, &right_eye);
+/// Translucent pane of glass across the front of the scene, over the
+/// middle half of it.
+fn window(scene: &Aabb) -> Result<Model<VertNorm>, ShapeError> {
+    let center = scene.center();
+    let extents = scene.extents();
+    let (w, h) = (extents.x / 2.0, extents.y / 2.0);
+    let verts =
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y): (f32, f32)| VertNorm {
+            pos: Vec3::new(center.x + x * w, center.y + y * h, scene.max.z),
+            tex: Vec2::new((x + 1.0) / 2.0, (y + 1.0) / 2.0),
+            norm: Vec3::new(0.0, 0.0, 1.0),
         });
 
-        //println!("{:?}", hid.gyroscope_rate().unwrap());
+    let mat = Material::builder()
+        .diffuse(WINDOW_COLOUR)
+        .blend_mode(BlendMode::AlphaBlend)
+        .cull_mode(CullMode::None)
+        .build();
+    Ok(Model::new(
+        "window",
+        Transform::IDENTITY,
+        vec![Shape::new(mat, buffer::Primitive::TriangleFan, &verts)?],
+    ))
+}
+
+/// Peach standing in the middle of the scene with a hole punched through
+/// her, drawn with an alpha test rather than blending.
+fn cutout(scene: &Aabb) -> Result<Model<VertNorm>, Box<dyn Error>> {
+    let texture = Texture::from_embedded(PEACH)?;
+    let (hole_w, hole_h) = (texture.width() / 4, texture.height() / 4);
+    texture.update_region(
+        texture.width() / 2 - hole_w / 2,
+        texture.height() / 2 - hole_h / 2,
+        hole_w,
+        hole_h,
+        &vec![0; texture.format().image_size(hole_w, hole_h)],
+    )?;
+
+    let center = scene.center();
+    let extents = scene.extents();
+    // stood on the floor, half as tall as the scene
+    let size = extents.y / 2.0;
+    let verts =
+        [(-1.0, 0.0), (1.0, 0.0), (1.0, 2.0), (-1.0, 2.0)].map(|(x, y): (f32, f32)| VertNorm {
+            pos: Vec3::new(
+                center.x + x * size / 2.0,
+                scene.min.y + y * size / 2.0,
+                center.z,
+            ),
+            tex: Vec2::new((x + 1.0) / 2.0, y / 2.0),
+            norm: Vec3::new(0.0, 0.0, 1.0),
+        });
+
+    let mat = Material::builder()
+        .texture(Rc::new(texture))
+        .alpha_test(CUTOUT_ALPHA_THRESHOLD)
+        .cull_mode(CullMode::None)
+        .build();
+    Ok(Model::new(
+        "cutout",
+        Transform::IDENTITY,
+        vec![Shape::new(mat, buffer::Primitive::TriangleFan, &verts)?],
+    ))
+}
+
+/// Shiny sphere sat on the floor of the scene, reflecting a studio through
+/// a sphere map.
+fn chrome_sphere(scene: &Aabb) -> Result<Model<VertNorm>, Box<dyn Error>> {
+    let env_map = Texture::from_embedded(STUDIO)?;
+
+    let extents = scene.extents();
+    let radius = extents.y / 6.0;
+    let center = Vec3::new(
+        scene.center().x + extents.x / 2.0,
+        scene.min.y + radius,
+        scene.center().z,
+    );
+
+    let mat = Material::builder()
+        .diffuse(Colour::new(0x30, 0x30, 0x30, 0xFF))
+        .env_map(Rc::new(env_map))
+        .build();
+    Ok(Model::new(
+        "chrome_sphere",
+        Transform::from_translation(center),
+        vec![
+            primitives::uv_sphere(radius, CHROME_SPHERE_SEGMENTS, CHROME_SPHERE_RINGS)
+                .into_shape(mat)?,
+        ],
+    ))
+}
+
+/// Bowser on a quad in the front of the scene, always turned to face the
+/// camera while staying upright.
+fn sprite(scene: &Aabb) -> Result<Model<VertNorm>, Box<dyn Error>> {
+    let texture = Texture::from_embedded(BOWSER)?;
+
+    let center = scene.center();
+    let extents = scene.extents();
+    let size = extents.y / 4.0;
+    // centered on the model's origin, which is what it turns around
+    let verts =
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y): (f32, f32)| VertNorm {
+            pos: Vec3::new(x * size / 2.0, y * size / 2.0, 0.0),
+            tex: Vec2::new((x + 1.0) / 2.0, (y + 1.0) / 2.0),
+            norm: Vec3::new(0.0, 0.0, 1.0),
+        });
+
+    let mat = Material::builder()
+        .texture(Rc::new(texture))
+        .alpha_test(CUTOUT_ALPHA_THRESHOLD)
+        .build();
+    let pos = Vec3::new(
+        center.x - extents.x / 4.0,
+        scene.min.y + size / 2.0,
+        center.z + extents.z / 4.0,
+    );
+    Ok(Model::new(
+        "sprite",
+        Transform::from_translation(pos),
+        vec![Shape::new(mat, buffer::Primitive::TriangleFan, &verts)?],
+    )
+    .with_billboard(BillboardMode::CylindricalY))
+}
+
+/// Cubes starting near the ceiling of the scene and falling to its floor,
+/// bouncing more or less depending on how far right they are.
+fn falling_cubes(scene: &Aabb) -> Result<Vec<Model<VertNorm>>, ShapeError> {
+    let extents = scene.extents();
+    let size = extents.y / 10.0;
+    // in scene units, so it takes the same time to fall however big it is
+    let gravity = extents.y * 2.0;
+    (0..FALLING_CUBES)
+        .map(|i| {
+            let along = (i as f32 + 1.0) / (FALLING_CUBES as f32 + 1.0);
+            let pos = Vec3::new(
+                scene.min.x + extents.x * along,
+                scene.max.y - size,
+                scene.center().z + extents.z / 4.0,
+            );
+            let mesh = primitives::cube(size);
+            let aabb = Aabb::from_points(mesh.verts.iter().map(|v| &v.pos));
+            let mat = Material::builder().diffuse(FALLING_CUBE_COLOUR).build();
+            Ok(Model::new(
+                format!("falling_cube_{i}"),
+                Transform::from_translation(pos),
+                vec![mesh.into_shape(mat)?],
+            )
+            .with_aabb(aabb)
+            .with_body(RigidBody::new(gravity, along, scene.min.y)))
+        })
+        .collect()
+}
+
+/// A pillar and a grid of places in front of the scene to draw it, as
+/// instances sharing the one copy of its vertices.
+fn pillars(scene: &Aabb) -> Result<(Model<VertNorm>, Vec<Transform>), ShapeError> {
+    let extents = scene.extents();
+    let height = extents.y;
+    let spacing = extents.x / 2.0;
+    let pillar = primitives::cylinder(spacing / 8.0, height, PILLAR_SEGMENTS)
+        .into_shape(Material::builder().diffuse(Colour::WHITE).build())?;
+
+    let start = Vec3::new(
+        scene.center().x - spacing * (PILLAR_GRID - 1) as f32 / 2.0,
+        scene.min.y + height / 2.0,
+        scene.max.z + spacing,
+    );
+    let transforms = (0..PILLAR_GRID * PILLAR_GRID)
+        .map(|i| {
+            let (col, row) = ((i % PILLAR_GRID) as f32, (i / PILLAR_GRID) as f32);
+            Transform::from_translation(start.add(&Vec3::new(col * spacing, 0.0, row * spacing)))
+        })
+        .collect();
+    Ok((
+        Model::new("pillar", Transform::IDENTITY, vec![pillar]),
+        transforms,
+    ))
+}
+
+/// Cube hovering over the floor of the scene, turning round and bobbing up
+/// and down through a looping keyframe animation.
+fn spinning_cube(scene: &Aabb) -> Result<Model<VertNorm>, ShapeError> {
+    let extents = scene.extents();
+    let size = extents.y / 8.0;
+    let low = Vec3::new(
+        scene.center().x - extents.x / 2.0 + size,
+        scene.min.y + size,
+        scene.center().z,
+    );
+    let high = low.add(&Vec3::new(0.0, size, 0.0));
+
+    // a keyframe every quarter turn, as interpolation takes the short way
+    // round so can't go more than half a turn between them
+    let keyframes = (0..=4)
+        .map(|i| {
+            let time = SPINNING_CUBE_PERIOD * i / 4;
+            let angle = TAU * i as f32 / 4.0;
+            let pos = if i % 2 == 0 { &low } else { &high };
+            Keyframe::new(
+                time,
+                Transform::new(
+                    pos.clone(),
+                    Rotation::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), angle),
+                    Vec3::splat(1.0),
+                ),
+            )
+        })
+        .collect();
+
+    let mat = Material::builder().diffuse(SPINNING_CUBE_COLOUR).build();
+    Ok(Model::new(
+        "spinning_cube",
+        Transform::from_translation(low),
+        vec![primitives::cube(size).into_shape(mat)?],
+    )
+    .with_animation(Animation::new(keyframes, true)))
+}
+
+/// Cone hovering over the scene opposite the spinning cube, which
+/// [`Model::look_at`] keeps pointed at the camera.
+fn pointer_cone(scene: &Aabb) -> Result<Model<VertNorm>, ShapeError> {
+    let extents = scene.extents();
+    let length = extents.y / 6.0;
+    let pos = Vec3::new(
+        scene.center().x + extents.x / 2.0 - length,
+        scene.center().y,
+        scene.center().z,
+    );
+
+    let mat = Material::builder().diffuse(POINTER_CONE_COLOUR).build();
+    let cone = primitives::cone(length / 3.0, length, POINTER_CONE_SEGMENTS).into_shape(mat)?;
+    // the mesh points up y, turn it so the tip is on -z which look_at aims
+    let tip_forward = Transform::new(
+        Vec3::splat(0.0),
+        Rotation::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), -FRAC_PI_2),
+        Vec3::splat(1.0),
+    );
+    let mut pointer = Model::new(POINTER_CONE, Transform::from_translation(pos), Vec::new());
+    pointer.add_child(Model::new("cone", tip_forward, vec![cone]));
+    Ok(pointer)
+}
+
+/// Small dark cube sat on top of `table` glowing through its emission colour
+/// alone, which only shows if the material uniforms reach the shader.
+fn lamp(table: &Aabb) -> Result<Model<VertNorm>, ShapeError> {
+    let extents = table.extents();
+    let size = extents.x.min(extents.z) / 3.0;
+    let center = Vec3::new(table.center().x, table.max.y + size / 2.0, table.center().z);
+
+    let mat = Material::builder()
+        .diffuse(Colour::new(0x10, 0x10, 0x10, 0xFF))
+        .emission(LAMP_EMISSION)
+        .build();
+    Ok(Model::new(
+        "lamp",
+        Transform::from_translation(center),
+        vec![primitives::cube(size).into_shape(mat)?],
+    ))
+}
+
+/// Where to put a camera with vertical field of view `fov` so it has the
+/// whole of `aabb` in view, looking down -z at its center.
+fn frame_camera(aabb: &Aabb, fov: f32) -> Vec3 {
+    let radius = aabb.extents().length();
+    let distance = radius / (fov / 2.0).tan();
+    aabb.center().add(&Vec3::new(0.0, 0.0, distance))
+}
+
+/// Render targets the top screen's drawn to.
+enum TopTargets<'screen> {
+    /// One for each eye
+    Stereo(render::Target<'screen>, render::Target<'screen>),
+    /// One across the whole 800 pixel wide framebuffer, with no 3D
+    Wide(render::Target<'screen>),
+}
+
+/// Put the top screen in or out of wide mode and make targets for it. Any
+/// targets already made for it have to be dropped first, as they borrow it
+fn make_top_targets<'screen>(
+    gfx: &'screen Gfx,
+    top_screen: &'screen TopScreen3D<'screen>,
+    wide: bool,
+) -> Result<TopTargets<'screen>, citro3d::Error> {
+    gfx.top_screen.borrow_mut().set_wide_mode(wide);
+    if wide {
+        // the pixels are half as wide rather than the screen twice as wide,
+        // so the projections' aspect ratio stays the same
+        let mut screen = gfx.top_screen.borrow_mut();
+        let RawFrameBuffer { width, height, .. } = screen.raw_framebuffer();
+        let target = render::Target::new(width, height, screen, Some(Depth16))?;
+        return Ok(TopTargets::Wide(target));
+    }
+
+    let (mut left, mut right) = top_screen.split_mut();
+    let RawFrameBuffer { width, height, .. } = left.raw_framebuffer();
+    let left = render::Target::new(width, height, left, Some(Depth16))?;
+    let RawFrameBuffer { width, height, .. } = right.raw_framebuffer();
+    let right = render::Target::new(width, height, right, Some(Depth16))?;
+    Ok(TopTargets::Stereo(left, right))
+}
+
+/// How the two eyes' views differ, on top of the 3D slider
+#[derive(Debug, Clone, PartialEq)]
+struct StereoSettings {
+    /// Distance at which things appear at the depth of the screen, closer
+    /// ones pop out of it
+    screen_depth: f32,
+    /// Distance between the eyes with the slider all the way up
+    interocular_scale: f32,
+}
+
+impl Default for StereoSettings {
+    fn default() -> Self {
+        Self {
+            screen_depth: 2.0,
+            interocular_scale: 0.5,
+        }
     }
 }
 
+impl StereoSettings {
+    /// Change both by the amounts given, kept within their limits. Returns
+    /// whether either changed
+    fn adjust(&mut self, screen_depth: f32, interocular_scale: f32) -> bool {
+        let old = self.clone();
+        let (min, max) = SCREEN_DEPTH_LIMITS;
+        self.screen_depth = (self.screen_depth + screen_depth).clamp(min, max);
+        let (min, max) = INTEROCULAR_SCALE_LIMITS;
+        self.interocular_scale = (self.interocular_scale + interocular_scale).clamp(min, max);
+        *self != old
+    }
+}
+
+/// Camera looking straight down on the whole of `aabb` through an
+/// orthographic projection, otherwise like `camera`
+fn map_camera(aabb: &Aabb, camera: &Camera) -> Camera {
+    let center = aabb.center();
+    let size = aabb.extents().scale(2.0 * (1.0 + MAP_MARGIN));
+    // looking down, the top of the screen is towards -z
+    let (width, height) = TOP_SCREEN_SIZE;
+    let aspect = width / height;
+    let height = size.z.max(size.x / aspect).max(f32::EPSILON);
+    let depth = size.y.max(f32::EPSILON);
+
+    let mut map = camera.clone();
+    map.pos = Vec3::new(center.x, aabb.max.y + depth, center.z);
+    map.rot = Vec3::new(-std::f32::consts::FRAC_PI_2, 0.0, 0.0);
+    map.snap();
+    map.projection.set_mode(ProjectionMode::Ortho {
+        width: height * aspect,
+        height,
+    });
+    map.projection.set_clip_planes(depth / 2.0, depth * 3.0);
+    map
+}
+
 #[derive(Debug)]
 struct Projections {
     left_eye: Matrix4,
@@ -511,20 +1656,37 @@ struct Projections {
     center: Matrix4,
 }
 
-fn calculate_projections() -> Projections {
-    // TODO: it would be cool to allow playing around with these parameters on
-    // the fly with D-pad, etc.
-    let slider_val = ctru::os::current_3d_slider_state();
-    let interocular_distance = slider_val / 2.0;
+/// Projections for `settings` and `stereo` with the 3D slider at `slider_val`
+fn calculate_projections(
+    settings: &ProjectionSettings,
+    stereo: &StereoSettings,
+    slider_val: f32,
+) -> Projections {
+    let interocular_distance = slider_val * stereo.interocular_scale;
 
-    let vertical_fov = 40.0_f32.to_radians();
-    let screen_depth = 2.0;
+    let vertical_fov = settings.fov();
+    let screen_depth = stereo.screen_depth;
 
     let clip_planes = ClipPlanes {
-        near: 0.01,
-        far: 100.0,
+        near: settings.near(),
+        far: settings.far(),
     };
 
+    if let ProjectionMode::Ortho { width, height } = settings.mode() {
+        // no depth to see, so both eyes get the same view
+        let ortho: Matrix4 = Projection::orthographic(
+            -width / 2.0..width / 2.0,
+            -height / 2.0..height / 2.0,
+            clip_planes,
+        )
+        .into();
+        return Projections {
+            left_eye: ortho.clone(),
+            right_eye: ortho.clone(),
+            center: ortho,
+        };
+    }
+
     let (left, right) = StereoDisplacement::new(interocular_distance, screen_depth);
 
     let (left_eye, right_eye) =
@@ -0,0 +1,826 @@
+//! Vector and transform types used on the CPU side.
+
+use std::ops::Mul;
+
+use citro3d::{attrib::Format, math::Matrix4};
+use vert_attr::VertAttrs;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[repr(C)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn splat(v: f32) -> Self {
+        Self::new(v, v, v)
+    }
+
+    pub fn add(&self, other: &Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+
+    pub fn sub(&self, other: &Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+
+    pub fn scale(&self, factor: f32) -> Vec3 {
+        Vec3::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+
+    /// Component-wise multiplication
+    pub fn mul(&self, other: &Vec3) -> Vec3 {
+        Vec3::new(self.x * other.x, self.y * other.y, self.z * other.z)
+    }
+
+    pub fn dot(&self, other: &Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    /// Point `t` of the way from `self` to `other`
+    pub fn lerp(&self, other: &Vec3, t: f32) -> Vec3 {
+        self.add(&other.sub(self).scale(t))
+    }
+
+    /// Returns a unit-length copy, or `None` if the vector is (close to) zero.
+    pub fn normalized(&self) -> Option<Vec3> {
+        let len = self.length();
+        if len <= f32::EPSILON {
+            None
+        } else {
+            Some(Vec3::new(self.x / len, self.y / len, self.z / len))
+        }
+    }
+}
+
+impl VertAttrs for Vec3 {
+    const FORMAT: Format = Format::Float;
+    const SIZE: u8 = 3;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+impl VertAttrs for Vec2 {
+    const FORMAT: Format = Format::Float;
+    const SIZE: u8 = 2;
+}
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box containing all the points, a degenerate box at the origin
+    /// if there aren't any.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Self::default();
+        };
+        points.fold(Self::new(first.clone(), first.clone()), |aabb, p| {
+            aabb.union(&Self::new(p.clone(), p.clone()))
+        })
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.min.add(&self.max).scale(0.5)
+    }
+
+    /// Half the size of the box along each axis
+    pub fn extents(&self) -> Vec3 {
+        self.max.sub(&self.min).scale(0.5)
+    }
+
+    /// How far along the ray from `origin` in `dir` it first enters the box,
+    /// in lengths of `dir`. Zero if it starts inside, `None` if it misses.
+    pub fn ray_hit(&self, origin: &Vec3, dir: &Vec3) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+        for (o, d, min, max) in [
+            (origin.x, dir.x, self.min.x, self.max.x),
+            (origin.y, dir.y, self.min.y, self.max.y),
+            (origin.z, dir.z, self.min.z, self.max.z),
+        ] {
+            // parallel to this pair of sides, so either always between them
+            // or never
+            if d.abs() <= f32::EPSILON {
+                if o < min || o > max {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((min - o) / d, (max - o) / d);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    /// Where a sphere at `center` has to move to so it only touches the box,
+    /// taking the shortest way out. `None` if they don't overlap.
+    pub fn push_out(&self, center: &Vec3, radius: f32) -> Option<Vec3> {
+        let closest = Vec3::new(
+            center.x.clamp(self.min.x, self.max.x),
+            center.y.clamp(self.min.y, self.max.y),
+            center.z.clamp(self.min.z, self.max.z),
+        );
+        let offset = center.sub(&closest);
+        let distance = offset.length();
+        if distance >= radius {
+            return None;
+        }
+        if distance > f32::EPSILON {
+            // only moved away from the box, so anything sliding along a side
+            // keeps going
+            return Some(closest.add(&offset.scale(radius / distance)));
+        }
+
+        // the center's inside, so out through whichever side is nearest
+        let mut out = center.clone();
+        let sides = [
+            (
+                center.x - self.min.x,
+                Vec3::new(self.min.x - radius, center.y, center.z),
+            ),
+            (
+                self.max.x - center.x,
+                Vec3::new(self.max.x + radius, center.y, center.z),
+            ),
+            (
+                center.y - self.min.y,
+                Vec3::new(center.x, self.min.y - radius, center.z),
+            ),
+            (
+                self.max.y - center.y,
+                Vec3::new(center.x, self.max.y + radius, center.z),
+            ),
+            (
+                center.z - self.min.z,
+                Vec3::new(center.x, center.y, self.min.z - radius),
+            ),
+            (
+                self.max.z - center.z,
+                Vec3::new(center.x, center.y, self.max.z + radius),
+            ),
+        ];
+        let mut nearest = f32::INFINITY;
+        for (depth, moved) in sides {
+            if depth < nearest {
+                nearest = depth;
+                out = moved;
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Bounding sphere.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    /// Sphere around the bounding box of the points, a point at the origin if
+    /// there aren't any.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3> + Clone) -> Self {
+        let center = Aabb::from_points(points.clone()).center();
+        let radius = points
+            .into_iter()
+            .map(|p| p.sub(&center).length())
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// Sphere containing all of `spheres`, `None` if there aren't any.
+    pub fn enclosing<'a>(spheres: impl IntoIterator<Item = &'a Sphere> + Clone) -> Option<Self> {
+        let center = spheres
+            .clone()
+            .into_iter()
+            .map(|s| {
+                let r = Vec3::splat(s.radius);
+                Aabb::new(s.center.sub(&r), s.center.add(&r))
+            })
+            .reduce(|a, b| a.union(&b))?
+            .center();
+        let radius = spheres
+            .into_iter()
+            .map(|s| s.center.sub(&center).length() + s.radius)
+            .fold(0.0, f32::max);
+        Some(Self { center, radius })
+    }
+
+    /// Like [`Aabb::ray_hit`], for the sphere
+    pub fn ray_hit(&self, origin: &Vec3, dir: &Vec3) -> Option<f32> {
+        let offset = origin.sub(&self.center);
+        let a = dir.dot(dir);
+        let b = 2.0 * dir.dot(&offset);
+        let c = offset.dot(&offset) - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if a <= f32::EPSILON || discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (near, far) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+        (far >= 0.0).then_some(near.max(0.0))
+    }
+
+    /// Sphere containing this one after `transform`
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        let scale = transform.scale;
+        let max_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        Self {
+            center: transform.apply(&self.center),
+            radius: self.radius * max_scale,
+        }
+    }
+}
+
+/// The sides of the volume a projection can see, for culling things outside
+/// it. The near and far planes aren't tested, the sides already meet behind
+/// the camera.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frustum {
+    /// Each plane as a unit normal pointing inwards and distance, so a point
+    /// `p` is inside when `normal.dot(p) + distance >= 0`
+    planes: [(Vec3, f32); 4],
+}
+
+impl Frustum {
+    /// Extract the planes from a projection * camera matrix
+    pub fn from_matrix(matrix: &Matrix4) -> Self {
+        let m = matrix_rows(matrix);
+        let plane = |sign: f32, row: usize| {
+            let normal = Vec3::new(
+                m[3][0] + sign * m[row][0],
+                m[3][1] + sign * m[row][1],
+                m[3][2] + sign * m[row][2],
+            );
+            let distance = m[3][3] + sign * m[row][3];
+            let len = normal.length().max(f32::EPSILON);
+            (normal.scale(1.0 / len), distance / len)
+        };
+        Self {
+            planes: [plane(1.0, 0), plane(-1.0, 0), plane(1.0, 1), plane(-1.0, 1)],
+        }
+    }
+
+    /// Whether any of `sphere` might be inside
+    pub fn intersects(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|(normal, distance)| normal.dot(&sphere.center) + distance >= -sphere.radius)
+    }
+}
+
+/// Row-major copy of a matrix
+fn matrix_rows(matrix: &Matrix4) -> [[f32; 4]; 4] {
+    let raw = unsafe { matrix.as_raw().m };
+    let mut rows = [[0.0; 4]; 4];
+    for (r, row) in rows.iter_mut().enumerate() {
+        for (c, cell) in row.iter_mut().enumerate() {
+            // same reversed layout as Transform::to_matrix
+            *cell = raw[r * 4 + (3 - c)];
+        }
+    }
+    rows
+}
+
+/// A rotation, stored as a unit quaternion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rotation {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Rotation {
+    pub const IDENTITY: Rotation = Rotation {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    /// Rotation of `angle` radians counter-clockwise around `axis`
+    pub fn from_axis_angle(axis: &Vec3, angle: f32) -> Self {
+        let Some(axis) = axis.normalized() else {
+            return Self::IDENTITY;
+        };
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Self {
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+            w: cos,
+        }
+    }
+
+    /// Equivalent of rotating around x, then y, then z in the style of
+    /// `Matrix4::rotate_x` etc. i.e. the matrix `Rx * Ry * Rz`.
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        Self::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), x)
+            * Self::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), y)
+            * Self::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), z)
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: self.w,
+        }
+    }
+
+    /// Scaled back to unit length, undoing drift from composing many
+    /// rotations. The identity if it's too close to zero to tell a direction
+    pub fn normalized(&self) -> Self {
+        let len = (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt();
+        if len <= f32::EPSILON {
+            return Self::IDENTITY;
+        }
+        Self {
+            x: self.x / len,
+            y: self.y / len,
+            z: self.z / len,
+            w: self.w / len,
+        }
+    }
+
+    /// Rotation `t` of the way from `self` to `other`, turning at a steady
+    /// rate the shortest way round
+    pub fn slerp(&self, other: &Rotation, t: f32) -> Self {
+        let mut dot = self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w;
+        // q and -q are the same rotation, but only one is the short way
+        let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+        dot *= sign;
+        let (a, b) = if dot > 0.9995 {
+            // too close for the angle to be accurate, a straight line is
+            (1.0 - t, t)
+        } else {
+            let angle = dot.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        let b = b * sign;
+        Self {
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+            w: a * self.w + b * other.w,
+        }
+        .normalized()
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        // v' = v + 2w(q x v) + 2(q x (q x v))
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = q.cross(v).scale(2.0);
+        v.add(&t.scale(self.w)).add(&q.cross(&t))
+    }
+
+    /// Row-major 3x3 rotation matrix
+    pub fn to_rows(&self) -> [[f32; 3]; 3] {
+        let Self { x, y, z, w } = *self;
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
+    /// Matrix rotating by this and nothing else
+    pub fn to_matrix(&self) -> Matrix4 {
+        Transform {
+            rotation: self.clone(),
+            ..Transform::IDENTITY
+        }
+        .to_matrix()
+    }
+
+    /// Rotation from a row-major 3x3 matrix, which must be orthonormal with
+    /// a determinant of 1.
+    pub fn from_rows(m: [[f32; 3]; 3]) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self {
+                w: 0.25 * s,
+                x: (m[2][1] - m[1][2]) / s,
+                y: (m[0][2] - m[2][0]) / s,
+                z: (m[1][0] - m[0][1]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Self {
+                w: (m[2][1] - m[1][2]) / s,
+                x: 0.25 * s,
+                y: (m[0][1] + m[1][0]) / s,
+                z: (m[0][2] + m[2][0]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Self {
+                w: (m[0][2] - m[2][0]) / s,
+                x: (m[0][1] + m[1][0]) / s,
+                y: 0.25 * s,
+                z: (m[1][2] + m[2][1]) / s,
+            }
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Self {
+                w: (m[1][0] - m[0][1]) / s,
+                x: (m[0][2] + m[2][0]) / s,
+                y: (m[1][2] + m[2][1]) / s,
+                z: 0.25 * s,
+            }
+        };
+        q.normalized()
+    }
+}
+
+impl Mul for &Rotation {
+    type Output = Rotation;
+
+    /// Rotation which applies `rhs` first, then `self`
+    fn mul(self, rhs: &Rotation) -> Rotation {
+        Rotation {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+        .normalized()
+    }
+}
+
+impl Mul for Rotation {
+    type Output = Rotation;
+
+    fn mul(self, rhs: Rotation) -> Rotation {
+        &self * &rhs
+    }
+}
+
+/// Translation, rotation, and scale, applied to a point in the order scale,
+/// rotate, translate (i.e. the matrix `T * R * S`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Rotation,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::splat(0.0),
+        rotation: Rotation::IDENTITY,
+        scale: Vec3::splat(1.0),
+    };
+
+    pub fn new(translation: Vec3, rotation: Rotation, scale: Vec3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn apply(&self, point: &Vec3) -> Vec3 {
+        self.rotation
+            .rotate(&point.mul(&self.scale))
+            .add(&self.translation)
+    }
+
+    /// Row-major 4x4 matrix for this transform
+    pub fn to_rows(&self) -> [[f32; 4]; 4] {
+        let r = self.rotation.to_rows();
+        let s = [self.scale.x, self.scale.y, self.scale.z];
+        let t = [self.translation.x, self.translation.y, self.translation.z];
+        let mut m = [[0.0; 4]; 4];
+        for row in 0..3 {
+            for col in 0..3 {
+                m[row][col] = r[row][col] * s[col];
+            }
+            m[row][3] = t[row];
+        }
+        m[3][3] = 1.0;
+        m
+    }
+
+    /// Decompose a row-major affine matrix. Any shear in the upper 3x3 can't
+    /// be represented, it's discarded by orthonormalising the rotation. A
+    /// mirroring matrix comes out as a negative x scale.
+    pub fn from_rows(m: [[f32; 4]; 4]) -> Self {
+        let column = |c: usize| Vec3::new(m[0][c], m[1][c], m[2][c]);
+        let (x_axis, y_axis, z_axis) = (column(0), column(1), column(2));
+
+        let mut scale = Vec3::new(x_axis.length(), y_axis.length(), z_axis.length());
+        if x_axis.cross(&y_axis).dot(&z_axis) < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        // Gram-Schmidt, so shear ends up thrown away rather than skewing the
+        // rotation
+        let x = x_axis
+            .scale(scale.x.signum())
+            .normalized()
+            .unwrap_or(Vec3::new(1.0, 0.0, 0.0));
+        let y = y_axis
+            .sub(&x.scale(x.dot(&y_axis)))
+            .normalized()
+            .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+        let z = x.cross(&y);
+
+        Self {
+            translation: column(3),
+            rotation: Rotation::from_rows([[x.x, y.x, z.x], [x.y, y.y, z.y], [x.z, y.z, z.z]]),
+            scale,
+        }
+    }
+
+    pub fn to_matrix(&self) -> Matrix4 {
+        let rows = self.to_rows();
+        let mut raw = citro3d_sys::C3D_Mtx { m: [0.0; 16] };
+        // citro3d stores each row in reverse (wzyx) order
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                unsafe { raw.m[r * 4 + (3 - c)] = *cell };
+            }
+        }
+        Matrix4::from_raw(raw)
+    }
+
+    pub fn from_matrix(matrix: &Matrix4) -> Self {
+        Self::from_rows(matrix_rows(matrix))
+    }
+
+    /// Transform `t` of the way from `self` to `other`, interpolating each
+    /// part separately
+    pub fn interpolate(&self, other: &Transform, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+
+    /// Transform which undoes this one. Exact when the scale is uniform, with
+    /// non-uniform scale and a rotation the true inverse has shear, which
+    /// can't be represented.
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        let scale = Vec3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        let translation = rotation.rotate(&self.translation).mul(&scale).scale(-1.0);
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+}
+
+impl Mul for &Transform {
+    type Output = Transform;
+
+    /// Transform which applies `rhs` first, then `self`, like multiplying
+    /// their matrices. Exact unless `self` has non-uniform scale and `rhs`
+    /// has a rotation, in which case the shear is dropped.
+    fn mul(self, rhs: &Transform) -> Transform {
+        Transform {
+            translation: self.apply(&rhs.translation),
+            rotation: &self.rotation * &rhs.rotation,
+            scale: self.scale.mul(&rhs.scale),
+        }
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        &self * &rhs
+    }
+}
+
+/// Which way is up in a model's source data. Everything here is y up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AxisConvention {
+    /// Already y up, left as it is
+    #[default]
+    YUp,
+    /// Z up with y going into the screen, as Blender and most CAD tools use,
+    /// turned to y up
+    ZUpToYUp,
+}
+
+impl AxisConvention {
+    /// Rotation turning something in this convention to y up
+    pub fn rotation(&self) -> Rotation {
+        match self {
+            AxisConvention::YUp => Rotation::IDENTITY,
+            AxisConvention::ZUpToYUp => {
+                Rotation::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), -std::f32::consts::FRAC_PI_2)
+            }
+        }
+    }
+
+    /// `v` (a position or normal) turned to y up. Only swaps components, so
+    /// is exact unlike going through [`AxisConvention::rotation`]
+    pub fn convert(&self, v: &Vec3) -> Vec3 {
+        match self {
+            AxisConvention::YUp => v.clone(),
+            AxisConvention::ZUpToYUp => Vec3::new(v.x, v.z, -v.y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rows_eq(actual: [[f32; 4]; 4], expected: [[f32; 4]; 4]) {
+        for (a, e) in actual.iter().flatten().zip(expected.iter().flatten()) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn product(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+        let mut out = [[0.0; 4]; 4];
+        for (r, row) in out.iter_mut().enumerate() {
+            for (c, cell) in row.iter_mut().enumerate() {
+                *cell = (0..4).map(|i| a[r][i] * b[i][c]).sum();
+            }
+        }
+        out
+    }
+
+    fn sample() -> Transform {
+        Transform::new(
+            Vec3::new(1.0, -2.0, 3.0),
+            Rotation::from_euler(0.3, -1.1, 2.0),
+            Vec3::new(2.0, 0.5, 1.5),
+        )
+    }
+
+    #[test]
+    fn transform_matches_matrix_ops() {
+        let mut expected = Matrix4::identity();
+        expected.translate(1.0, -2.0, 3.0);
+        expected.rotate_x(0.3);
+        expected.rotate_y(-1.1);
+        expected.rotate_z(2.0);
+        expected.scale(2.0, 0.5, 1.5);
+        assert_rows_eq(matrix_rows(&sample().to_matrix()), matrix_rows(&expected));
+    }
+
+    #[test]
+    fn rotation_matches_its_matrix() {
+        let rotation = Rotation::from_axis_angle(&Vec3::new(1.0, 2.0, -0.5), 0.8);
+        let v = Vec3::new(0.2, -3.0, 1.0);
+        let rows = matrix_rows(&rotation.to_matrix());
+        let rotated = rotation.rotate(&v);
+        for (row, actual) in rows.iter().zip([rotated.x, rotated.y, rotated.z]) {
+            let expected = row[0] * v.x + row[1] * v.y + row[2] * v.z;
+            assert!((actual - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn composition_is_the_matrix_product() {
+        // uniform scale on the left so there's no shear to drop
+        let parent = Transform::new(
+            Vec3::new(-4.0, 0.5, 2.0),
+            Rotation::from_euler(1.2, 0.4, -0.7),
+            Vec3::splat(3.0),
+        );
+        let child = sample();
+        assert_rows_eq(
+            (&parent * &child).to_rows(),
+            product(parent.to_rows(), child.to_rows()),
+        );
+        let rows = |r: &Rotation| matrix_rows(&r.to_matrix());
+        assert_rows_eq(
+            rows(&(&parent.rotation * &child.rotation)),
+            product(rows(&parent.rotation), rows(&child.rotation)),
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_uniform_scale() {
+        let t = Transform::new(
+            Vec3::new(1.0, -2.0, 3.0),
+            Rotation::from_euler(0.3, -1.1, 2.0),
+            Vec3::splat(2.5),
+        );
+        assert_rows_eq(
+            product(t.inverse().to_rows(), t.to_rows()),
+            Transform::IDENTITY.to_rows(),
+        );
+    }
+
+    #[test]
+    fn decompose_round_trips() {
+        let t = sample();
+        assert_rows_eq(Transform::from_rows(t.to_rows()).to_rows(), t.to_rows());
+
+        // mirrored, so the decomposition has to find a negative scale
+        let mirrored = Transform::new(
+            t.translation.clone(),
+            t.rotation.clone(),
+            Vec3::new(-2.0, 0.5, 1.5),
+        );
+        assert_rows_eq(
+            Transform::from_rows(mirrored.to_rows()).to_rows(),
+            mirrored.to_rows(),
+        );
+    }
+}
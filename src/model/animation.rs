@@ -0,0 +1,84 @@
+//! Keyframed movement of whole models.
+
+use std::time::Duration;
+
+use crate::math::Transform;
+
+/// Where a model should be at `time` into the animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub time: Duration,
+    pub transform: Transform,
+}
+
+impl Keyframe {
+    pub fn new(time: Duration, transform: Transform) -> Self {
+        Self { time, transform }
+    }
+}
+
+/// Keyframes a model's transform is moved through, interpolating linearly
+/// between them. See [`Model::set_animation`](super::Model::set_animation).
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// Sorted by time
+    keyframes: Vec<Keyframe>,
+    looping: bool,
+    elapsed: Duration,
+}
+
+impl Animation {
+    /// Animation through `keyframes`, which don't need to be in order. With
+    /// `looping` the last keyframe should match the first to avoid a jump.
+    pub fn new(mut keyframes: Vec<Keyframe>, looping: bool) -> Self {
+        keyframes.sort_by_key(|k| k.time);
+        Self {
+            keyframes,
+            looping,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> Duration {
+        self.keyframes.last().map_or(Duration::ZERO, |k| k.time)
+    }
+
+    /// Whether a non-looping animation has reached its last keyframe
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.elapsed >= self.duration()
+    }
+
+    /// Go back to the start
+    pub fn restart(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Move on by `dt`, returning the transform at the new time. `None` if
+    /// there are no keyframes.
+    pub fn advance(&mut self, dt: Duration) -> Option<Transform> {
+        let duration = self.duration();
+        self.elapsed += dt;
+        if self.looping && !duration.is_zero() {
+            while self.elapsed >= duration {
+                self.elapsed -= duration;
+            }
+        } else {
+            self.elapsed = self.elapsed.min(duration);
+        }
+        self.sample(self.elapsed)
+    }
+
+    /// Transform at `time`, held at the first and last keyframes outside
+    /// of them
+    pub fn sample(&self, time: Duration) -> Option<Transform> {
+        let next = self.keyframes.iter().position(|k| k.time > time);
+        let (from, to) = match next {
+            Some(0) => return self.keyframes.first().map(|k| k.transform.clone()),
+            Some(i) => (&self.keyframes[i - 1], &self.keyframes[i]),
+            None => return self.keyframes.last().map(|k| k.transform.clone()),
+        };
+        let t = (time - from.time).as_secs_f32() / (to.time - from.time).as_secs_f32();
+        Some(from.transform.interpolate(&to.transform, t))
+    }
+}
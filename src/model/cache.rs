@@ -0,0 +1,725 @@
+//! Parsed models cached on the SD card, keyed by the OBJ, the load options
+//! and every material library and texture read while parsing it.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    mem::{size_of, size_of_val},
+    path::PathBuf,
+    ptr,
+    rc::Rc,
+    time::UNIX_EPOCH,
+};
+
+use citro3d::buffer::Primitive;
+
+use crate::{
+    logging::{log_info, log_warn},
+    math::{Aabb, Transform, Vec2, Vec3},
+    obj::LoadOptions,
+    Vertex,
+};
+
+use super::{
+    colour::Colour,
+    material::{BlendMode, CullMode, Material},
+    shape::Shape,
+    texture::{
+        sampling::{Filter, TextureSampling, Wrap},
+        Texture, TextureFormat,
+    },
+    Model,
+};
+
+const CACHE_DIR: &str = "sdmc:/draw-trongle/cache";
+const MAGIC: &[u8; 4] = b"TRGC";
+/// Bumped whenever the layout below changes
+const VERSION: u32 = 6;
+
+/// Load the cached models for the file at `path` parsed with `options`, if
+/// there is a valid entry.
+pub fn load<T: Vertex>(path: &str, options: &LoadOptions) -> Option<Vec<Model<T>>> {
+    let bytes = fs::read(cache_path(path)).ok()?;
+    let models = read_models(&mut Reader(&bytes), &source_key(path, options)?);
+    match &models {
+        Some(_) => log_info!("loaded {path} from the model cache"),
+        None => log_info!("model cache for {path} is stale, reparsing"),
+    }
+    models
+}
+
+/// Write `models` loaded from the file at `path` with `options` to the cache,
+/// with `sources` being the other files read while loading them. Nothing is
+/// written if they use anything the cache can't store.
+pub fn store<T: Vertex>(
+    path: &str,
+    options: &LoadOptions,
+    sources: &[PathBuf],
+    models: &[Model<T>],
+) {
+    if !is_cacheable(models) {
+        log_info!("not caching {path}, it has animated textures, custom texenvs or child models");
+        return;
+    }
+    let Some(key) = source_key(path, options) else {
+        return;
+    };
+    let sources = sources
+        .iter()
+        .map(|source| {
+            let source = source.to_string_lossy().into_owned();
+            let stamp = file_stamp(&source);
+            (source, stamp)
+        })
+        .collect::<Vec<_>>();
+    let mut w = Writer::default();
+    write_models(&mut w, &key, &sources, models);
+    let result = fs::create_dir_all(CACHE_DIR).and_then(|_| fs::write(cache_path(path), &w.0));
+    if let Err(e) = result {
+        log_warn!("failed to write model cache for {path}: {e}");
+    }
+}
+
+fn cache_path(path: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    format!("{CACHE_DIR}/{:016x}.bin", hasher.finish())
+}
+
+/// Whether everything about `models` can be written to the cache, which
+/// doesn't store texture animations, custom texenvs or child models
+fn is_cacheable<T: Vertex>(models: &[Model<T>]) -> bool {
+    models.iter().all(|model| {
+        model.children().is_empty()
+            && model
+                .shapes()
+                .iter()
+                .map(Shape::material)
+                .all(|mat| !mat.is_animated() && !mat.has_custom_texenv())
+    })
+}
+
+/// Identifies the version of the source file an entry was built from, and
+/// how it was parsed.
+#[derive(PartialEq)]
+struct SourceKey {
+    path: String,
+    mtime: u64,
+    size: u64,
+    options: u64,
+}
+
+fn source_key(path: &str, options: &LoadOptions) -> Option<SourceKey> {
+    let (mtime, size) = file_stamp(path)?;
+    Some(SourceKey {
+        path: path.to_owned(),
+        mtime,
+        size,
+        options: options_hash(options),
+    })
+}
+
+/// Modification time and size of the file at `path`, `None` if it doesn't
+/// exist
+type Stamp = Option<(u64, u64)>;
+
+fn file_stamp(path: &str) -> Stamp {
+    let meta = fs::metadata(path).ok()?;
+    // not every filesystem has modification times, the size still catches
+    // most edits
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    Some((mtime, meta.len()))
+}
+
+fn options_hash(options: &LoadOptions) -> u64 {
+    // destructured so a new option can't be left out of the key
+    let LoadOptions {
+        prefer_ktx,
+        mipmaps,
+        prefer_etc1,
+        texture_location,
+        atlas_size,
+        fix_winding,
+        normalize,
+        target_size,
+        recenter,
+        merge_by_material,
+        flip_winding,
+        flip_uv_v,
+        cull_mode,
+        lod_distance,
+        stripify,
+        weld_tolerance,
+        scale,
+        axis_convention,
+    } = options;
+    let mut hasher = DefaultHasher::new();
+    let flags = [
+        prefer_ktx,
+        mipmaps,
+        prefer_etc1,
+        fix_winding,
+        normalize,
+        recenter,
+        merge_by_material,
+        flip_winding,
+        flip_uv_v,
+        stripify,
+    ];
+    flags.hash(&mut hasher);
+    (texture_location, atlas_size, cull_mode, axis_convention).hash(&mut hasher);
+    let sizes = [
+        Some(*target_size),
+        *lod_distance,
+        *weld_tolerance,
+        Some(*scale),
+    ];
+    sizes.map(|size| size.map(f32::to_bits)).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn write_models<T: Vertex>(
+    w: &mut Writer,
+    key: &SourceKey,
+    sources: &[(String, Stamp)],
+    models: &[Model<T>],
+) {
+    w.bytes(MAGIC);
+    w.u32(VERSION);
+    w.u32(size_of::<T>() as u32);
+    w.u8(T::HAS_COLOUR as u8);
+    w.str(&key.path);
+    w.u64(key.mtime);
+    w.u64(key.size);
+    w.u64(key.options);
+    w.u32(sources.len() as u32);
+    for (path, stamp) in sources {
+        w.str(path);
+        match stamp {
+            Some((mtime, size)) => {
+                w.u8(1);
+                w.u64(*mtime);
+                w.u64(*size);
+            }
+            None => w.u8(0),
+        }
+    }
+
+    // textures are shared between materials, so they're written once up front
+    // and referred to by index
+    let mut textures: Vec<&Rc<Texture>> = Vec::new();
+    for mat in models.iter().flat_map(|m| m.shapes()).map(|s| s.material()) {
+        for tex in [&mat.texture, &mat.ambient_texture, &mat.env_map]
+            .into_iter()
+            .flatten()
+        {
+            if !textures.iter().any(|t| Rc::ptr_eq(t, tex)) {
+                textures.push(tex);
+            }
+        }
+    }
+    w.u32(textures.len() as u32);
+    for tex in &textures {
+        w.u16(tex.width);
+        w.u16(tex.height);
+        w.u16(tex.image_width);
+        w.u16(tex.image_height);
+        w.u8(match tex.format() {
+            TextureFormat::Rgba8 => 0,
+            TextureFormat::Rgb565 => 1,
+            TextureFormat::Etc1 => 2,
+            TextureFormat::Rgba4 => 3,
+            TextureFormat::A8 => 4,
+            TextureFormat::Etc1A4 => 5,
+        });
+        w.u8(tex.levels);
+        w.blob(&tex.data.borrow());
+    }
+    let texture_index = |tex: &Option<Rc<Texture>>| match tex {
+        // UNWRAP: every texture was added to the table above
+        Some(tex) => textures.iter().position(|t| Rc::ptr_eq(t, tex)).unwrap() as u32,
+        None => u32::MAX,
+    };
+
+    w.u32(models.len() as u32);
+    for model in models {
+        w.str(&model.name);
+        for row in model.transform().to_rows() {
+            row.iter().for_each(|&v| w.f32(v));
+        }
+        w.vec3(&model.aabb().min);
+        w.vec3(&model.aabb().max);
+
+        w.u32(model.shapes().len() as u32);
+        for shape in model.shapes() {
+            let mat = shape.material();
+            w.str(shape.name());
+            w.u32(texture_index(&mat.texture));
+            w.u32(texture_index(&mat.ambient_texture));
+            w.u32(texture_index(&mat.env_map));
+            for colour in [
+                mat.colour(),
+                mat.emission(),
+                mat.ambient.as_ref(),
+                mat.specular.as_ref(),
+                mat.tint(),
+            ] {
+                w.colour(colour);
+            }
+            w.f32(mat.shininess);
+            w.u8(mat.use_vertex_colours() as u8);
+            w.u8(match mat.blend_mode() {
+                BlendMode::Opaque => 0,
+                BlendMode::AlphaBlend => 1,
+                BlendMode::Additive => 2,
+            });
+            w.u8(mat.alpha_test().is_some() as u8);
+            w.u8(mat.alpha_test().unwrap_or(0));
+            w.u8(match mat.cull_mode() {
+                CullMode::None => 0,
+                CullMode::Back => 1,
+                CullMode::Front => 2,
+            });
+            let sampling = mat.sampling();
+            w.u8(sampling.min_filter as u8);
+            w.u8(sampling.mag_filter as u8);
+            w.u8(sampling.wrap_u as u8);
+            w.u8(sampling.wrap_v as u8);
+            w.vec2(mat.uv_offset());
+            w.vec2(mat.uv_scale());
+            w.vec2(mat.uv_scroll());
+
+            w.u8(match shape.prim_type {
+                Primitive::Triangles => 0,
+                Primitive::TriangleStrip => 1,
+                Primitive::TriangleFan => 2,
+                Primitive::GeometryPrim => 3,
+            });
+            w.blob(vert_bytes(&shape.verts));
+            match &shape.indices {
+                Some(indices) => {
+                    w.u8(1);
+                    w.u32(indices.len() as u32);
+                    indices.iter().for_each(|&i| w.u16(i));
+                }
+                None => w.u8(0),
+            }
+            w.f32(shape.max_distance());
+            w.u32(shape.lods().count() as u32);
+            for (max_distance, verts) in shape.lods() {
+                w.f32(max_distance);
+                w.blob(vert_bytes(verts));
+            }
+        }
+    }
+}
+
+fn read_models<T: Vertex>(r: &mut Reader, key: &SourceKey) -> Option<Vec<Model<T>>> {
+    if r.bytes(4)? != MAGIC
+        || r.u32()? != VERSION
+        || r.u32()? != size_of::<T>() as u32
+        || r.u8()? != T::HAS_COLOUR as u8
+    {
+        return None;
+    }
+    let file_key = SourceKey {
+        path: r.str()?,
+        mtime: r.u64()?,
+        size: r.u64()?,
+        options: r.u64()?,
+    };
+    if file_key != *key {
+        return None;
+    }
+    for _ in 0..r.u32()? {
+        let path = r.str()?;
+        let stamp = match r.u8()? {
+            0 => None,
+            _ => Some((r.u64()?, r.u64()?)),
+        };
+        if file_stamp(&path) != stamp {
+            return None;
+        }
+    }
+
+    let mut textures = Vec::new();
+    for _ in 0..r.u32()? {
+        let (width, height, image_width, image_height) = (r.u16()?, r.u16()?, r.u16()?, r.u16()?);
+        let format = match r.u8()? {
+            0 => TextureFormat::Rgba8,
+            1 => TextureFormat::Rgb565,
+            2 => TextureFormat::Etc1,
+            3 => TextureFormat::Rgba4,
+            4 => TextureFormat::A8,
+            5 => TextureFormat::Etc1A4,
+            _ => return None,
+        };
+        let levels = r.u8()?;
+        let data = r.blob()?.to_vec();
+        let tex = Texture::with_format(width, height, format, levels, data).ok()?;
+        textures.push(Rc::new(tex.with_image_size(image_width, image_height)));
+    }
+    let texture = |idx: u32| match idx {
+        u32::MAX => Some(None),
+        idx => textures.get(idx as usize).cloned().map(Some),
+    };
+
+    let mut models = Vec::new();
+    for _ in 0..r.u32()? {
+        let name = r.str()?;
+        let mut rows = [[0.0; 4]; 4];
+        for v in rows.iter_mut().flatten() {
+            *v = r.f32()?;
+        }
+        let aabb = Aabb::new(r.vec3()?, r.vec3()?);
+
+        let mut shapes = Vec::new();
+        for _ in 0..r.u32()? {
+            let shape_name = r.str()?;
+            let (tex, ambient_tex, env_map) =
+                (texture(r.u32()?)?, texture(r.u32()?)?, texture(r.u32()?)?);
+            let (colour, emission, ambient, specular) =
+                (r.colour()?, r.colour()?, r.colour()?, r.colour()?);
+            let tint = r.colour()?;
+            let shininess = r.f32()?;
+            let vertex_colours = r.u8()? != 0;
+            let blend_mode = match r.u8()? {
+                0 => BlendMode::Opaque,
+                1 => BlendMode::AlphaBlend,
+                2 => BlendMode::Additive,
+                _ => return None,
+            };
+            let alpha_test = match (r.u8()?, r.u8()?) {
+                (0, _) => None,
+                (_, threshold) => Some(threshold),
+            };
+            let cull_mode = match r.u8()? {
+                0 => CullMode::None,
+                1 => CullMode::Back,
+                2 => CullMode::Front,
+                _ => return None,
+            };
+            let sampling = TextureSampling {
+                min_filter: read_filter(r.u8()?)?,
+                mag_filter: read_filter(r.u8()?)?,
+                wrap_u: read_wrap(r.u8()?)?,
+                wrap_v: read_wrap(r.u8()?)?,
+            };
+            let (uv_offset, uv_scale, uv_scroll) = (r.vec2()?, r.vec2()?, r.vec2()?);
+
+            let mut mat = Material::new(tex, colour, ambient, specular, shininess, vertex_colours);
+            mat.set_emission(emission);
+            mat.set_blend_mode(blend_mode);
+            mat.set_alpha_test(alpha_test);
+            mat.set_cull_mode(cull_mode);
+            mat.set_ambient_texture(ambient_tex);
+            mat.set_sampling(sampling);
+            mat.set_env_map(env_map);
+            mat.set_tint(tint);
+            mat.set_uv_offset(uv_offset);
+            mat.set_uv_scale(uv_scale);
+            mat.set_uv_scroll(uv_scroll);
+
+            let prim = match r.u8()? {
+                0 => Primitive::Triangles,
+                1 => Primitive::TriangleStrip,
+                2 => Primitive::TriangleFan,
+                3 => Primitive::GeometryPrim,
+                _ => return None,
+            };
+            let verts = read_verts::<T>(r.blob()?)?;
+            // out of memory falls back to parsing the obj, which reports it
+            let shape = match r.u8()? {
+                0 => Shape::new(mat, prim, &verts).ok()?,
+                _ => {
+                    let indices = (0..r.u32()?).map(|_| r.u16()).collect::<Option<Vec<_>>>()?;
+                    Shape::new_indexed(mat, prim, &verts, &indices).ok()?
+                }
+            };
+            let mut shape = shape.with_name(shape_name).with_max_distance(r.f32()?);
+            for _ in 0..r.u32()? {
+                let max_distance = r.f32()?;
+                let verts = read_verts::<T>(r.blob()?)?;
+                shape.add_lod(max_distance, &verts).ok()?;
+            }
+            shapes.push(shape);
+        }
+        models.push(Model::new(name, Transform::from_rows(rows), shapes).with_aabb(aabb));
+    }
+    Some(models)
+}
+
+fn read_filter(byte: u8) -> Option<Filter> {
+    [Filter::Nearest, Filter::Linear]
+        .get(byte as usize)
+        .copied()
+}
+
+fn read_wrap(byte: u8) -> Option<Wrap> {
+    [Wrap::Clamp, Wrap::Repeat, Wrap::Mirror]
+        .get(byte as usize)
+        .copied()
+}
+
+fn vert_bytes<T: Vertex>(verts: &[T]) -> &[u8] {
+    // SAFETY: vertex types are plain `repr(C)` floats and bytes
+    unsafe { std::slice::from_raw_parts(verts.as_ptr() as *const u8, size_of_val(verts)) }
+}
+
+fn read_verts<T: Vertex>(bytes: &[u8]) -> Option<Vec<T>> {
+    if bytes.len() % size_of::<T>() != 0 {
+        return None;
+    }
+    let count = bytes.len() / size_of::<T>();
+    let mut verts = Vec::<T>::with_capacity(count);
+    // SAFETY: vertex types are plain `repr(C)` floats and bytes, any bit
+    // pattern is a valid vertex
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), verts.as_mut_ptr() as *mut u8, bytes.len());
+        verts.set_len(count);
+    }
+    Some(verts)
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn bytes(&mut self, b: &[u8]) {
+        self.0.extend_from_slice(b);
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn vec2(&mut self, v: &Vec2) {
+        self.f32(v.x);
+        self.f32(v.y);
+    }
+
+    fn vec3(&mut self, v: &Vec3) {
+        [v.x, v.y, v.z].into_iter().for_each(|c| self.f32(c));
+    }
+
+    /// Length prefixed bytes
+    fn blob(&mut self, b: &[u8]) {
+        self.u32(b.len() as u32);
+        self.bytes(b);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.blob(s.as_bytes());
+    }
+
+    fn colour(&mut self, c: Option<&Colour>) {
+        match c {
+            Some(c) => self.bytes(&[1, c.r(), c.g(), c.b(), c.a()]),
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        // UNWRAP: `bytes` returns exactly N bytes
+        self.bytes(N).map(|b| b.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.array::<1>().map(|[b]| b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    fn vec2(&mut self) -> Option<Vec2> {
+        Some(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn vec3(&mut self) -> Option<Vec3> {
+        Some(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn blob(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn str(&mut self) -> Option<String> {
+        String::from_utf8(self.blob()?.to_vec()).ok()
+    }
+
+    fn colour(&mut self) -> Option<Option<Colour>> {
+        match self.u8()? {
+            0 => Some(None),
+            _ => {
+                let [r, g, b, a] = self.array()?;
+                Some(Some(Colour::new(r, g, b, a)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vert;
+
+    #[test]
+    fn models_round_trip() {
+        let vert = |x| Vert {
+            pos: Vec3::new(x, 0.0, 1.0),
+            tex: Vec2::new(x, 0.5),
+        };
+        let verts = [vert(0.0), vert(1.0), vert(2.0), vert(3.0)];
+        let tex = Rc::new(Texture::new(8, 8, vec![0x7F; 8 * 8 * 4]));
+        let mut mat = Material::new(
+            Some(tex.clone()),
+            Some(Colour::new(1, 2, 3, 4)),
+            None,
+            None,
+            8.0,
+            false,
+        );
+        mat.set_env_map(Some(tex));
+        mat.set_tint(Some(Colour::WHITE));
+        mat.set_uv_scale(Vec2::new(2.0, 3.0));
+        mat.set_uv_scroll(Vec2::new(0.0, 0.25));
+        let mut shape = Shape::new_indexed(mat, Primitive::Triangles, &verts, &[0, 1, 2, 2, 1, 3])
+            .unwrap()
+            .with_name("quad")
+            .with_max_distance(5.0);
+        shape.add_lod(10.0, &verts[..3]).unwrap();
+        let models = [Model::new("thing", Transform::default(), vec![shape])];
+        let key = SourceKey {
+            path: "thing.obj".to_owned(),
+            mtime: 1,
+            size: 2,
+            options: 3,
+        };
+        let missing = "sdmc:/draw-trongle/missing.mtl".to_owned();
+
+        let mut w = Writer::default();
+        write_models(&mut w, &key, &[(missing.clone(), None)], &models);
+        let read = read_models::<Vert>(&mut Reader(&w.0), &key).unwrap();
+
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].name, "thing");
+        assert_eq!(read[0].transform(), models[0].transform());
+        assert_eq!(read[0].aabb(), models[0].aabb());
+        let (shape, expected) = (&read[0].shapes()[0], &models[0].shapes()[0]);
+        assert_eq!(shape.name(), "quad");
+        let positions = |verts: &[Vert]| verts.iter().map(|v| v.pos.clone()).collect::<Vec<_>>();
+        assert_eq!(positions(&shape.verts), positions(&expected.verts));
+        assert_eq!(shape.indices.as_deref(), expected.indices.as_deref());
+        assert_eq!(shape.max_distance(), 5.0);
+        let lods = shape
+            .lods()
+            .map(|(d, v)| (d, positions(v)))
+            .collect::<Vec<_>>();
+        assert_eq!(lods, [(10.0, positions(&verts[..3]))]);
+
+        let (mat, expected) = (shape.material(), expected.material());
+        let rgba = |c: Option<&Colour>| c.map(|c| [c.r(), c.g(), c.b(), c.a()]);
+        assert_eq!(rgba(mat.colour()), rgba(expected.colour()));
+        assert_eq!(rgba(mat.tint()), rgba(expected.tint()));
+        assert_eq!(mat.shininess, expected.shininess);
+        assert_eq!(mat.uv_offset(), expected.uv_offset());
+        assert_eq!(mat.uv_scale(), expected.uv_scale());
+        assert_eq!(mat.uv_scroll(), expected.uv_scroll());
+        // both textures come from the same table entry
+        let (tex, env_map) = (mat.texture.as_ref().unwrap(), mat.env_map.as_ref().unwrap());
+        assert!(Rc::ptr_eq(tex, env_map));
+        assert_eq!(
+            *tex.data.borrow(),
+            *expected.texture.as_ref().unwrap().data.borrow()
+        );
+
+        // a source which has appeared since makes the entry stale
+        let mut w = Writer::default();
+        write_models(&mut w, &key, &[(missing, Some((1, 2)))], &models);
+        assert!(read_models::<Vert>(&mut Reader(&w.0), &key).is_none());
+    }
+
+    #[test]
+    fn options_change_the_key() {
+        let default = LoadOptions::default();
+        let changed = [
+            LoadOptions {
+                flip_winding: true,
+                ..default.clone()
+            },
+            LoadOptions {
+                flip_uv_v: false,
+                ..default.clone()
+            },
+            LoadOptions {
+                atlas_size: Some(512),
+                ..default.clone()
+            },
+            LoadOptions {
+                stripify: true,
+                ..default.clone()
+            },
+            LoadOptions {
+                weld_tolerance: Some(0.001),
+                ..default.clone()
+            },
+            LoadOptions {
+                lod_distance: Some(4.0),
+                ..default.clone()
+            },
+        ];
+        let key = options_hash(&default);
+        assert_eq!(options_hash(&default.clone()), key);
+        for options in &changed {
+            assert_ne!(options_hash(options), key, "{options:?}");
+        }
+    }
+}
@@ -1,10 +1,14 @@
-use citro3d::math::FVec4;
+use citro3d::{attrib::Format, math::FVec4};
+use vert_attr::VertAttrs;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[repr(C)]
 pub struct Colour([u8; 4]);
 
 impl Colour {
-    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+    pub const WHITE: Colour = Colour::new(0xFF, 0xFF, 0xFF, 0xFF);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
         Self([r, g, b, a])
     }
 
@@ -37,3 +41,8 @@ impl Into<FVec4> for &Colour {
         FVec4::new(r, g, b, a)
     }
 }
+
+impl VertAttrs for Colour {
+    const FORMAT: Format = Format::UnsignedByte;
+    const SIZE: u8 = 4;
+}
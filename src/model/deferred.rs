@@ -0,0 +1,50 @@
+//! GPU resources parked until the frames which may still use them are done.
+
+use std::{any::Any, cell::RefCell};
+
+/// Number of frames a resource is kept alive for after it's released
+const FRAMES_IN_FLIGHT: u64 = 2;
+
+#[derive(Default)]
+struct Queue {
+    frame: u64,
+    pending: Vec<(u64, Box<dyn Any>)>,
+}
+
+thread_local! {
+    static QUEUE: RefCell<Queue> = RefCell::new(Queue::default());
+}
+
+/// Queue something owning GPU memory (a texture, a linear buffer, a render
+/// target) to be dropped once the GPU can no longer be using it.
+pub fn release<R: 'static>(resource: R) {
+    QUEUE.with_borrow_mut(|q| {
+        let frame = q.frame;
+        q.pending.push((frame, Box::new(resource)))
+    });
+}
+
+/// Mark the end of a frame, must be called after each `render_frame_with`
+/// returns. Destroys resources released at least [`FRAMES_IN_FLIGHT`] frames
+/// ago.
+pub fn end_frame() {
+    let expired = QUEUE.with_borrow_mut(|q| {
+        q.frame += 1;
+        let frame = q.frame;
+        let (expired, pending) = q
+            .pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|(released, _)| frame - released >= FRAMES_IN_FLIGHT);
+        q.pending = pending;
+        expired
+    });
+    // dropped outside the borrow in case a destructor releases something else
+    drop(expired);
+}
+
+/// Destroy everything still queued, only safe once the GPU is idle (e.g. on
+/// exit).
+pub fn flush() {
+    let pending = QUEUE.with_borrow_mut(|q| std::mem::take(&mut q.pending));
+    drop(pending);
+}
@@ -0,0 +1,26 @@
+//! `.etc1` sidecar textures: `ETC1`, u16 LE width and height, then tiled ETC1
+//! blocks (ETC1A4 when there are twice as many bytes).
+
+use super::texture::{Texture, TextureError, TextureFormat};
+
+const MAGIC: &[u8; 4] = b"ETC1";
+const HEADER_SIZE: usize = 8;
+
+pub fn parse_etc1(bytes: &[u8]) -> Result<Texture, TextureError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(TextureError::Malformed("missing ETC1 magic"));
+    }
+    let Some(header) = bytes.get(..HEADER_SIZE) else {
+        return Err(TextureError::Malformed("truncated ETC1 header"));
+    };
+    let width = u16::from_le_bytes([header[4], header[5]]);
+    let height = u16::from_le_bytes([header[6], header[7]]);
+
+    let data = &bytes[HEADER_SIZE..];
+    let format = if data.len() == TextureFormat::Etc1A4.image_size(width, height) {
+        TextureFormat::Etc1A4
+    } else {
+        TextureFormat::Etc1
+    };
+    Texture::with_format(width, height, format, 1, data.to_vec())
+}
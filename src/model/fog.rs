@@ -0,0 +1,104 @@
+//! Distance fog from the GPU's fog unit.
+
+use super::colour::Colour;
+
+/// Entries in the GPU's fog lookup table, indexed by depth
+const LUT_SIZE: usize = 128;
+
+/// Exponential fog which reaches `colour` completely at `end`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fog {
+    pub colour: Colour,
+    /// How quickly things fade out with distance
+    pub density: f32,
+    /// Distance from the camera everything past is fully fogged
+    pub end: f32,
+}
+
+impl Fog {
+    /// Lookup table for the fog unit with a projection from `near` to `far`.
+    fn lut(&self, near: f32, far: f32) -> citro3d_sys::C3D_FogLut {
+        // the table holds values at each step followed by the difference to
+        // the next one, the layout FogLut_FromArray takes
+        let mut data = [0.0; LUT_SIZE * 2];
+        let mut prev = 0.0;
+        for i in 0..=LUT_SIZE {
+            // same depth to distance mapping as citro3d's FogLut_Exp
+            let depth = i as f32 / LUT_SIZE as f32;
+            let distance = far * near / (depth * (far - near) + near);
+            let value = if distance >= self.end {
+                0.0
+            } else {
+                (-self.density * distance).exp()
+            };
+            if i < LUT_SIZE {
+                data[i] = value;
+            }
+            if i > 0 {
+                data[i + LUT_SIZE - 1] = value - prev;
+            }
+            prev = value;
+        }
+
+        let mut lut = citro3d_sys::C3D_FogLut::default();
+        // SAFETY: data is the size FogLut_FromArray reads
+        unsafe {
+            citro3d_sys::FogLut_FromArray(&mut lut, data.as_ptr());
+        }
+        lut
+    }
+}
+
+/// Settings for the whole scene, applied once per frame before anything is
+/// drawn.
+#[derive(Debug, Default)]
+pub struct SceneSettings {
+    fog: Option<Fog>,
+    /// Kept here as the GPU reads it through a pointer, with the near and far
+    /// it was built for
+    lut: Option<(f32, f32, Box<citro3d_sys::C3D_FogLut>)>,
+}
+
+impl SceneSettings {
+    pub fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+
+    pub fn set_fog(&mut self, fog: Option<Fog>) {
+        if self.fog != fog {
+            self.fog = fog;
+            self.lut = None;
+        }
+    }
+
+    /// Set up the GPU for the scene, with a projection from `near` to `far`
+    pub fn apply(&mut self, near: f32, far: f32) {
+        let Some(fog) = &self.fog else {
+            // SAFETY: only changes GPU state for following draws
+            unsafe {
+                citro3d_sys::C3D_FogGasMode(
+                    ctru_sys::GPU_NO_FOG,
+                    ctru_sys::GPU_PLAIN_DENSITY,
+                    false,
+                );
+            }
+            return;
+        };
+        // the depth to distance mapping changes with the clip planes
+        if matches!(&self.lut, Some((n, f, _)) if (*n, *f) != (near, far)) {
+            self.lut = None;
+        }
+        let (_, _, lut) = self
+            .lut
+            .get_or_insert_with(|| (near, far, Box::new(fog.lut(near, far))));
+        let colour = u32::from_le_bytes([fog.colour.r(), fog.colour.g(), fog.colour.b(), 0]);
+        // SAFETY: the table is boxed and kept alive by self until the fog or
+        // clip planes change, after which it's rebound before anything else
+        // is drawn
+        unsafe {
+            citro3d_sys::C3D_FogGasMode(ctru_sys::GPU_FOG, ctru_sys::GPU_PLAIN_DENSITY, false);
+            citro3d_sys::C3D_FogColor(colour);
+            citro3d_sys::C3D_FogLutBind(&mut **lut);
+        }
+    }
+}
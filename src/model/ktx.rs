@@ -0,0 +1,148 @@
+//! KTX (version 1) textures, already in the 3DS's tiled layout.
+
+use super::texture::{Texture, TextureError, TextureFormat};
+
+const IDENTIFIER: &[u8; 12] = b"\xABKTX 11\xBB\r\n\x1A\n";
+const ENDIANNESS: u32 = 0x04030201;
+const HEADER_SIZE: usize = 64;
+
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+const GL_UNSIGNED_SHORT_5_6_5: u32 = 0x8363;
+const GL_RGB: u32 = 0x1907;
+const GL_RGBA: u32 = 0x1908;
+const GL_RGBA8: u32 = 0x8058;
+const GL_RGB565: u32 = 0x8D62;
+const GL_ETC1_RGB8_OES: u32 = 0x8D64;
+
+pub fn is_ktx(bytes: &[u8]) -> bool {
+    bytes.starts_with(IDENTIFIER)
+}
+
+pub fn parse_ktx(bytes: &[u8]) -> Result<Texture, TextureError> {
+    if !is_ktx(bytes) {
+        return Err(TextureError::Malformed("missing KTX identifier"));
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(TextureError::Malformed("truncated KTX header"));
+    }
+
+    let field = |idx: usize| {
+        let start = IDENTIFIER.len() + idx * 4;
+        // UNWRAP: the slice is always 4 bytes long
+        u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap())
+    };
+
+    if field(0) != ENDIANNESS {
+        return Err(TextureError::Malformed(
+            "big-endian KTX files aren't supported",
+        ));
+    }
+
+    let gl_type = field(1);
+    let gl_internal_format = field(4);
+    let format = match (gl_internal_format, gl_type) {
+        (GL_ETC1_RGB8_OES, _) => TextureFormat::Etc1,
+        (GL_RGB565, _) | (GL_RGB, GL_UNSIGNED_SHORT_5_6_5) => TextureFormat::Rgb565,
+        (GL_RGBA8, _) | (GL_RGBA, GL_UNSIGNED_BYTE) => TextureFormat::Rgba8,
+        _ => {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "glInternalFormat 0x{gl_internal_format:04X} (glType 0x{gl_type:04X})"
+            )))
+        }
+    };
+
+    let width = field(6);
+    let height = field(7);
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(TextureError::Malformed("texture dimensions are too large"));
+    };
+    if field(8) > 1 || field(9) > 1 || field(10) > 1 {
+        return Err(TextureError::Malformed(
+            "only single 2D textures are supported, not 3D, arrays, or cubemaps",
+        ));
+    }
+    let levels = field(11).max(1);
+    let Ok(levels) = u8::try_from(levels) else {
+        return Err(TextureError::Malformed("too many mip levels"));
+    };
+    // checked before the levels are read, as their sizes shift by the level
+    Texture::check_size(width, height, levels)?;
+    let key_value_size = field(12) as usize;
+
+    // sizes come from the file, so every offset is checked rather than
+    // trusted not to overflow
+    let slice = |start: usize, len: usize| {
+        start
+            .checked_add(len)
+            .and_then(|end| bytes.get(start..end))
+            .ok_or(TextureError::Malformed("truncated mip level"))
+    };
+    let mut offset = HEADER_SIZE
+        .checked_add(key_value_size)
+        .ok_or(TextureError::Malformed("key/value data is too large"))?;
+    let mut data = Vec::new();
+    for level in 0..levels {
+        // UNWRAP: the slice is always 4 bytes long
+        let size = u32::from_le_bytes(slice(offset, 4)?.try_into().unwrap()) as usize;
+        offset += 4;
+
+        let expected = format.image_size((width >> level).max(8), (height >> level).max(8));
+        if size != expected {
+            return Err(TextureError::SizeMismatch {
+                expected,
+                actual: size,
+            });
+        }
+
+        data.extend_from_slice(slice(offset, size)?);
+        // mip levels are padded to 4 bytes, and the size matched the
+        // texture's so can't overflow
+        offset += size.next_multiple_of(4);
+    }
+
+    Texture::with_format(width, height, format, levels, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(levels: u32, key_value_size: u32) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        let fields = [
+            ENDIANNESS,
+            GL_UNSIGNED_BYTE,
+            1,
+            GL_RGBA,
+            GL_RGBA8,
+            GL_RGBA,
+            64,
+            64,
+            0,
+            0,
+            1,
+            levels,
+            key_value_size,
+        ];
+        for field in fields {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn too_many_levels_is_an_error() {
+        assert!(matches!(
+            parse_ktx(&header(17, 0)),
+            Err(TextureError::BadLevelCount { levels: 17, max: 4 })
+        ));
+    }
+
+    #[test]
+    fn huge_key_value_size_is_an_error() {
+        assert!(matches!(
+            parse_ktx(&header(1, u32::MAX)),
+            Err(TextureError::Malformed(_))
+        ));
+    }
+}
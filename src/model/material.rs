@@ -1,94 +1,629 @@
-use std::{fmt::Debug, mem::MaybeUninit};
+use std::{fmt::Debug, mem::MaybeUninit, rc::Rc, time::Duration};
 
 use citro3d::{
     math::{FVec3, FVec4},
-    texture::{Tex, TexParams},
+    texenv::{CombineFunc, Source},
+    texture::Tex,
     Instance,
 };
 use ctru::linear::LinearAllocator;
 
-use crate::Uniforms;
+use crate::{logging::log_warn, math::Vec2, Uniforms};
 
-use super::{colour::Colour, texture::Texture};
+use super::{
+    colour::Colour,
+    texenv::{TexEnvConfig, TexEnvStage},
+    texture::{sampling::TextureSampling, Texture, TextureError},
+    DrawState,
+};
+
+/// Frames a material's texture cycles through, see [`Material::with_animation`]
+#[derive(Debug)]
+struct Animation {
+    frames: Vec<Rc<Texture>>,
+    frame_duration: Duration,
+    /// Time spent on the current frame so far
+    elapsed: Duration,
+    current: usize,
+}
+
+/// How a material's colour is combined with what's already been drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Replaces whatever is behind it
+    #[default]
+    Opaque,
+    /// Blended over what's behind it by its alpha, for glass and the like
+    AlphaBlend,
+    /// Added to what's behind it, scaled by its alpha, for glows
+    Additive,
+}
+
+impl BlendMode {
+    /// Whether what's behind shows through, so it has to be drawn after
+    /// everything opaque and without writing depth
+    pub fn is_translucent(self) -> bool {
+        self != BlendMode::Opaque
+    }
+}
+
+/// Which side of triangles isn't drawn, the front being the side they wind
+/// counter-clockwise on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CullMode {
+    /// Draw both sides, for thin things like quads seen from either side
+    None,
+    #[default]
+    Back,
+    Front,
+}
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Material {
-    texture: Option<Texture>,
+    pub(super) texture: Option<Rc<Texture>>,
+    /// Ambient occlusion map or lightmap, on the second UVs if there are any
+    pub(super) ambient_texture: Option<Rc<Texture>>,
+    pub(super) env_map: Option<Rc<Texture>>,
+    /// Diffuse colour
     colour: Option<Colour>,
-    ambient: Option<Colour>,
+    emission: Option<Colour>,
+    pub(super) ambient: Option<Colour>,
+    pub(super) specular: Option<Colour>,
+    pub(super) shininess: f32,
     vertex_colours: bool,
-    citro_tex: Option<Tex>,
+    blend_mode: BlendMode,
+    alpha_test: Option<u8>,
+    cull_mode: CullMode,
+    sampling: TextureSampling,
+    animation: Option<Animation>,
+    custom_texenv: Option<TexEnvConfig>,
+    tint: Option<Colour>,
+    uv_offset: Vec2,
+    uv_scale: Vec2,
+    uv_scroll: Vec2,
+    /// Kept within 0..1, separate so `uv_offset` isn't lost to wrapping
+    uv_scrolled: Vec2,
+    texenv: TexEnvConfig,
 }
 
 impl Material {
+    /// Start building a material, see [`MaterialBuilder`] for the defaults
+    pub fn builder() -> MaterialBuilder {
+        MaterialBuilder::default()
+    }
+
     pub fn new(
-        texture: Option<Texture>,
+        texture: Option<Rc<Texture>>,
         colour: Option<Colour>,
         ambient: Option<Colour>,
+        specular: Option<Colour>,
+        shininess: f32,
         vertex_colours: bool,
     ) -> Self {
-        let citro_tex = Self::make_texture(&texture);
-        Self {
+        let mut mat = Self {
             texture,
+            ambient_texture: None,
+            env_map: None,
             colour,
+            emission: None,
             ambient,
+            specular,
+            shininess,
             vertex_colours,
-            citro_tex,
+            blend_mode: BlendMode::Opaque,
+            alpha_test: None,
+            cull_mode: CullMode::Back,
+            sampling: TextureSampling::default(),
+            animation: None,
+            custom_texenv: None,
+            tint: None,
+            uv_offset: Vec2::new(0.0, 0.0),
+            uv_scale: Vec2::new(1.0, 1.0),
+            uv_scroll: Vec2::new(0.0, 0.0),
+            uv_scrolled: Vec2::new(0.0, 0.0),
+            texenv: TexEnvConfig::default(),
+        };
+        mat.update_texenv();
+        mat
+    }
+
+    /// Cycle the texture through `frames`, showing each for `frame_duration`
+    pub fn with_animation(mut self, frames: Vec<Texture>, frame_duration: Duration) -> Self {
+        let frames = frames.into_iter().map(Rc::new).collect::<Vec<_>>();
+        for frame in &frames {
+            frame.citro_tex();
+        }
+        self.texture = frames.first().cloned();
+        self.animation = Some(Animation {
+            frames,
+            frame_duration,
+            elapsed: Duration::ZERO,
+            current: 0,
+        });
+        self.update_texenv();
+        self
+    }
+
+    /// Whether the texture cycles through frames, see
+    /// [`Material::with_animation`]
+    pub fn is_animated(&self) -> bool {
+        self.animation.is_some()
+    }
+
+    /// Advance the animation and UV scrolling, if there are any, by `dt`.
+    pub fn update(&mut self, dt: Duration) {
+        let secs = dt.as_secs_f32();
+        self.uv_scrolled = Vec2::new(
+            (self.uv_scrolled.x + self.uv_scroll.x * secs).rem_euclid(1.0),
+            (self.uv_scrolled.y + self.uv_scroll.y * secs).rem_euclid(1.0),
+        );
+
+        let Some(anim) = &mut self.animation else {
+            return;
+        };
+        if anim.frames.is_empty() || anim.frame_duration.is_zero() {
+            return;
         }
+        anim.elapsed += dt;
+        while anim.elapsed >= anim.frame_duration {
+            anim.elapsed -= anim.frame_duration;
+            anim.current = (anim.current + 1) % anim.frames.len();
+        }
+        self.texture = Some(anim.frames[anim.current].clone());
     }
 
     pub fn use_vertex_colours(&self) -> bool {
         self.vertex_colours
     }
 
-    fn make_texture(texture: &Option<Texture>) -> Option<Tex> {
-        if let Some(tex) = texture {
-            let t = Tex::new(TexParams::new_2d(tex.width, tex.height)).ok()?;
-            t.upload(&tex.data);
-            Some(t)
-        } else {
-            None
+    pub fn set_ambient_texture(&mut self, texture: Option<Rc<Texture>>) {
+        self.ambient_texture = texture;
+        self.update_texenv();
+    }
+
+    /// Sphere map for fake reflections, needs vertices with normals
+    pub fn set_env_map(&mut self, texture: Option<Rc<Texture>>) {
+        self.env_map = texture;
+        self.update_texenv();
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+        self.update_texenv();
+    }
+
+    pub fn alpha_test(&self) -> Option<u8> {
+        self.alpha_test
+    }
+
+    /// Discard pixels with an alpha below `threshold`, for cutouts
+    pub fn set_alpha_test(&mut self, threshold: Option<u8>) {
+        self.alpha_test = threshold;
+    }
+
+    pub fn cull_mode(&self) -> CullMode {
+        self.cull_mode
+    }
+
+    pub fn set_cull_mode(&mut self, cull_mode: CullMode) {
+        self.cull_mode = cull_mode;
+    }
+
+    pub fn sampling(&self) -> &TextureSampling {
+        &self.sampling
+    }
+
+    pub fn set_sampling(&mut self, sampling: TextureSampling) {
+        self.sampling = sampling;
+    }
+
+    pub fn colour(&self) -> Option<&Colour> {
+        self.colour.as_ref()
+    }
+
+    pub fn emission(&self) -> Option<&Colour> {
+        self.emission.as_ref()
+    }
+
+    pub fn set_emission(&mut self, emission: Option<Colour>) {
+        self.emission = emission;
+    }
+
+    pub fn texenv(&self) -> &TexEnvConfig {
+        &self.texenv
+    }
+
+    /// Whether [`Material::set_texenv`] replaced the preset
+    pub fn has_custom_texenv(&self) -> bool {
+        self.custom_texenv.is_some()
+    }
+
+    /// Use `config` instead of the preset, the ambient map and tint still
+    /// go after it
+    pub fn set_texenv(&mut self, config: Option<TexEnvConfig>) {
+        self.custom_texenv = config;
+        self.update_texenv();
+    }
+
+    pub fn tint(&self) -> Option<&Colour> {
+        self.tint.as_ref()
+    }
+
+    pub fn set_tint(&mut self, tint: Option<Colour>) {
+        self.tint = tint;
+        self.update_texenv();
+    }
+
+    pub fn uv_offset(&self) -> &Vec2 {
+        &self.uv_offset
+    }
+
+    /// Added to the main UVs after scaling them
+    pub fn set_uv_offset(&mut self, offset: Vec2) {
+        self.uv_offset = offset;
+    }
+
+    pub fn uv_scale(&self) -> &Vec2 {
+        &self.uv_scale
+    }
+
+    pub fn set_uv_scale(&mut self, scale: Vec2) {
+        self.uv_scale = scale;
+    }
+
+    pub fn uv_scroll(&self) -> &Vec2 {
+        &self.uv_scroll
+    }
+
+    /// Move the UVs by `scroll` every second
+    pub fn set_uv_scroll(&mut self, scroll: Vec2) {
+        self.uv_scroll = scroll;
+    }
+
+    /// Sorts by blend mode, then texenv, then texture, so shapes sharing GPU
+    /// state are drawn together
+    pub fn sort_key(&self) -> u64 {
+        let blend = match self.blend_mode {
+            BlendMode::Opaque => 0,
+            BlendMode::AlphaBlend => 1,
+            BlendMode::Additive => 2,
+        };
+        // pointers are 32 bits on the 3DS, so this is exact
+        let texture = self
+            .texture
+            .as_ref()
+            .map_or(0, |t| Rc::as_ptr(t) as usize as u32);
+        (blend << 62) | ((self.texenv.key() >> 34) << 32) | texture as u64
+    }
+
+    fn update_texenv(&mut self) {
+        let translucent = self.blend_mode.is_translucent();
+        self.texenv = match &self.custom_texenv {
+            Some(config) => config.clone(),
+            None => TexEnvConfig::base(
+                self.get_texture().is_some(),
+                self.vertex_colours,
+                translucent,
+            ),
+        };
+
+        if self.get_ambient_texture().is_some() {
+            let ambient = TexEnvStage::new(
+                (Source::Previous, Some(Source::Texture1), None),
+                CombineFunc::Modulate,
+            );
+            self.texenv = self
+                .texenv
+                .clone()
+                .with_stage(ambient)
+                .unwrap_or_else(|config| {
+                    log_warn!("no texenv stage left for the ambient map");
+                    config
+                });
+        }
+        if self.get_env_map().is_some() {
+            let env_map = TexEnvStage::new(
+                (Source::Previous, Some(Source::Texture2), None),
+                CombineFunc::Add,
+            )
+            // the reflection doesn't change how see-through it is
+            .with_alpha_func(CombineFunc::Replace);
+            self.texenv = self
+                .texenv
+                .clone()
+                .with_stage(env_map)
+                .unwrap_or_else(|config| {
+                    log_warn!("no texenv stage left for the environment map");
+                    config
+                });
+        }
+        if let Some(tint) = &self.tint {
+            self.texenv = self
+                .texenv
+                .clone()
+                .with_tint(tint)
+                .unwrap_or_else(|config| {
+                    log_warn!("no texenv stage left for the tint");
+                    config
+                });
         }
     }
 
     pub fn get_texture(&self) -> Option<&Tex> {
-        if let Some(tex) = &self.citro_tex {
-            Some(tex)
-        } else {
-            None
+        self.texture.as_ref().and_then(|t| t.citro_tex())
+    }
+
+    pub fn update_texture_region(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        match &self.texture {
+            Some(t) => t.update_region(x, y, width, height, pixels),
+            None => Err(TextureError::NoTexture),
         }
     }
 
-    pub fn set_uniforms(&self, _gpu: &mut Instance, uniforms: &Uniforms) {
+    /// Push pending texture changes to the GPU
+    pub fn sync_textures(&self) {
+        for tex in [&self.texture, &self.ambient_texture, &self.env_map]
+            .into_iter()
+            .flatten()
+        {
+            tex.sync();
+        }
+    }
+
+    pub fn get_ambient_texture(&self) -> Option<&Tex> {
+        self.ambient_texture.as_ref().and_then(|t| t.citro_tex())
+    }
+
+    pub fn get_env_map(&self) -> Option<&Tex> {
+        self.env_map.as_ref().and_then(|t| t.citro_tex())
+    }
+
+    /// Whether shapes using this and `other` would look the same, so could
+    /// be drawn as one
+    pub fn is_compatible(&self, other: &Material) -> bool {
+        let same_texture = |a: &Option<Rc<Texture>>, b: &Option<Rc<Texture>>| match (a, b) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        let values = |m: &Material| m.uniform_values().map(|v| [v.x(), v.y(), v.z(), v.w()]);
+        self.sort_key() == other.sort_key()
+            && values(self) == values(other)
+            && self.alpha_test == other.alpha_test
+            && self.cull_mode == other.cull_mode
+            && self.sampling == other.sampling
+            && same_texture(&self.ambient_texture, &other.ambient_texture)
+            && same_texture(&self.env_map, &other.env_map)
+            // each would be animated separately
+            && self.animation.is_none()
+            && other.animation.is_none()
+    }
+
+    /// Ambient, diffuse, emission and specular values for the shader, then
+    /// the UV scale in xy and offset in zw
+    fn uniform_values(&self) -> [FVec4; 5] {
         let amb = if let Some(clr) = &self.ambient {
             clr.into()
         } else {
             FVec4::new(0.0, 0.0, 0.0, 0.0)
         };
 
-        let emi = if let Some(clr) = &self.colour {
+        let dif = if let Some(clr) = &self.colour {
             clr.into()
         } else {
             FVec4::new(0.0, 0.0, 0.0, 0.0)
         };
 
-        unsafe {
-            citro3d_sys::C3D_FVUnifSet(
-                citro3d::shader::Type::Vertex.into(),
-                uniforms.material_ambient.into(),
-                amb.x(),
-                amb.y(),
-                amb.z(),
-                amb.w(),
-            );
-            citro3d_sys::C3D_FVUnifSet(
-                citro3d::shader::Type::Vertex.into(),
-                uniforms.material_emission.into(),
-                emi.x(),
-                emi.y(),
-                emi.z(),
-                emi.w(),
-            );
+        // alpha comes from the diffuse colour alone, emission is only added
+        // to the colour channels
+        let emi = if let Some(clr) = &self.emission {
+            let clr: FVec4 = clr.into();
+            FVec4::new(clr.x(), clr.y(), clr.z(), 0.0)
+        } else {
+            FVec4::new(0.0, 0.0, 0.0, 0.0)
+        };
+
+        // shininess goes in w, the shader has no use for specular alpha
+        let spe = if let Some(clr) = &self.specular {
+            let clr: FVec4 = clr.into();
+            FVec4::new(clr.x(), clr.y(), clr.z(), self.shininess)
+        } else {
+            FVec4::new(0.0, 0.0, 0.0, self.shininess)
+        };
+
+        let uv = FVec4::new(
+            self.uv_scale.x,
+            self.uv_scale.y,
+            self.uv_offset.x + self.uv_scrolled.x,
+            self.uv_offset.y + self.uv_scrolled.y,
+        );
+
+        [amb, dif, emi, spe, uv]
+    }
+
+    /// Skipped if the last material drawn had the same values
+    pub fn set_uniforms(&self, gpu: &mut Instance, uniforms: &Uniforms, state: &mut DrawState) {
+        let [amb, dif, emi, spe, uv] = self.uniform_values();
+        let values = [&amb, &dif, &emi, &spe, &uv].map(|v| [v.x(), v.y(), v.z(), v.w()]);
+        if !state.update_material_uniforms(values) {
+            return;
+        }
+
+        gpu.bind_vertex_uniform(uniforms.material_ambient, amb);
+        gpu.bind_vertex_uniform(uniforms.material_diffuse, dif);
+        gpu.bind_vertex_uniform(uniforms.material_emission, emi);
+        gpu.bind_vertex_uniform(uniforms.material_specular, spe);
+        gpu.bind_vertex_uniform(uniforms.uv_transform, uv);
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new(None, None, None, None, 0.0, false)
+    }
+}
+
+/// Builder for [`Material`], each method documents the default used when
+/// it isn't called.
+#[derive(Debug)]
+pub struct MaterialBuilder {
+    texture: Option<Rc<Texture>>,
+    ambient_texture: Option<Rc<Texture>>,
+    env_map: Option<Rc<Texture>>,
+    colour: Option<Colour>,
+    emission: Option<Colour>,
+    ambient: Option<Colour>,
+    specular: Option<Colour>,
+    shininess: f32,
+    vertex_colours: bool,
+    blend_mode: BlendMode,
+    alpha_test: Option<u8>,
+    cull_mode: CullMode,
+    sampling: TextureSampling,
+    uv_offset: Vec2,
+    uv_scale: Vec2,
+    uv_scroll: Vec2,
+}
+
+impl Default for MaterialBuilder {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            ambient_texture: None,
+            env_map: None,
+            colour: Some(Colour::WHITE),
+            emission: None,
+            ambient: None,
+            specular: None,
+            shininess: 0.0,
+            vertex_colours: false,
+            blend_mode: BlendMode::Opaque,
+            alpha_test: None,
+            cull_mode: CullMode::Back,
+            sampling: TextureSampling::default(),
+            uv_offset: Vec2::new(0.0, 0.0),
+            uv_scale: Vec2::new(1.0, 1.0),
+            uv_scroll: Vec2::new(0.0, 0.0),
         }
     }
 }
+
+impl MaterialBuilder {
+    /// Main texture, none by default
+    pub fn texture(mut self, texture: impl Into<Option<Rc<Texture>>>) -> Self {
+        self.texture = texture.into();
+        self
+    }
+
+    /// Ambient occlusion map, none by default
+    pub fn ambient_texture(mut self, texture: impl Into<Option<Rc<Texture>>>) -> Self {
+        self.ambient_texture = texture.into();
+        self
+    }
+
+    /// See [`Material::set_env_map`], none by default
+    pub fn env_map(mut self, texture: impl Into<Option<Rc<Texture>>>) -> Self {
+        self.env_map = texture.into();
+        self
+    }
+
+    /// Diffuse colour, white by default
+    pub fn diffuse(mut self, colour: impl Into<Option<Colour>>) -> Self {
+        self.colour = colour.into();
+        self
+    }
+
+    /// Light given off regardless of lighting, none by default
+    pub fn emission(mut self, colour: impl Into<Option<Colour>>) -> Self {
+        self.emission = colour.into();
+        self
+    }
+
+    /// Ambient colour, none by default
+    pub fn ambient(mut self, colour: impl Into<Option<Colour>>) -> Self {
+        self.ambient = colour.into();
+        self
+    }
+
+    /// Specular colour and exponent, none and 0 by default
+    pub fn specular(mut self, colour: impl Into<Option<Colour>>, shininess: f32) -> Self {
+        self.specular = colour.into();
+        self.shininess = shininess;
+        self
+    }
+
+    /// Whether the vertices' own colours are multiplied in, off by default
+    pub fn vertex_colours(mut self, vertex_colours: bool) -> Self {
+        self.vertex_colours = vertex_colours;
+        self
+    }
+
+    /// Opaque by default
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// See [`Material::set_alpha_test`], off by default
+    pub fn alpha_test(mut self, threshold: u8) -> Self {
+        self.alpha_test = Some(threshold);
+        self
+    }
+
+    /// Back faces are culled by default
+    pub fn cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Linear filtering clamped to the edges by default
+    pub fn sampling(mut self, sampling: TextureSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// UVs are left as they are by default
+    pub fn uv_transform(mut self, offset: Vec2, scale: Vec2) -> Self {
+        self.uv_offset = offset;
+        self.uv_scale = scale;
+        self
+    }
+
+    /// See [`Material::set_uv_scroll`], not scrolling by default
+    pub fn uv_scroll(mut self, scroll: Vec2) -> Self {
+        self.uv_scroll = scroll;
+        self
+    }
+
+    pub fn build(self) -> Material {
+        let mut mat = Material::new(
+            self.texture,
+            self.colour,
+            self.ambient,
+            self.specular,
+            self.shininess,
+            self.vertex_colours,
+        );
+        mat.set_emission(self.emission);
+        mat.set_blend_mode(self.blend_mode);
+        mat.set_alpha_test(self.alpha_test);
+        mat.set_cull_mode(self.cull_mode);
+        mat.set_ambient_texture(self.ambient_texture);
+        mat.set_env_map(self.env_map);
+        mat.set_sampling(self.sampling);
+        mat.set_uv_offset(self.uv_offset);
+        mat.set_uv_scale(self.uv_scale);
+        mat.set_uv_scroll(self.uv_scroll);
+        mat
+    }
+}
@@ -0,0 +1,63 @@
+//! Clean up of indexed vertex data before it's put in a shape.
+
+use std::collections::HashMap;
+
+use crate::{math::Vec3, Vertex};
+
+/// Distance under which positions and UVs are treated as the same by
+/// [`weld`], enough to catch values differing only in the last decimal
+/// places an exporter writes
+pub const DEFAULT_WELD_TOLERANCE: f32 = 1e-5;
+
+/// Grid cell a position falls in, with cells `size` across
+fn cell(pos: &Vec3, size: f32) -> (i32, i32, i32) {
+    (
+        (pos.x / size).floor() as i32,
+        (pos.y / size).floor() as i32,
+        (pos.z / size).floor() as i32,
+    )
+}
+
+/// Merge vertices whose positions and UVs are all within `tolerance` of each
+/// other, returning the remaining vertices and `indices` pointing at them.
+/// The first of each set of merged vertices is kept as-is, so any other
+/// attributes (like normals) of the rest are lost.
+pub fn weld<T: Vertex>(verts: &[T], indices: &[u16], tolerance: f32) -> (Vec<T>, Vec<u16>) {
+    let close = |a: f32, b: f32| (a - b).abs() <= tolerance;
+    let same = |a: &T, b: &T| {
+        let (pa, pb) = (a.position(), b.position());
+        let (ua, ub) = (a.uv(), b.uv());
+        close(pa.x, pb.x)
+            && close(pa.y, pb.y)
+            && close(pa.z, pb.z)
+            && close(ua.x, ub.x)
+            && close(ua.y, ub.y)
+    };
+
+    // vertices are found by looking in the cells around them, which are at
+    // least as big as the tolerance so a match is never more than one away
+    let size = tolerance.max(f32::EPSILON);
+    let mut grid: HashMap<(i32, i32, i32), Vec<u16>> = HashMap::new();
+    let mut welded: Vec<T> = Vec::with_capacity(verts.len());
+    let mut remap = Vec::with_capacity(verts.len());
+    for v in verts {
+        let (x, y, z) = cell(v.position(), size);
+        let existing = (-1..=1)
+            .flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (dx, dy, dz))))
+            .filter_map(|(dx, dy, dz)| grid.get(&(x + dx, y + dy, z + dz)))
+            .flatten()
+            .find(|&&i| same(&welded[i as usize], v))
+            .copied();
+        let i = existing.unwrap_or_else(|| {
+            welded.push(v.clone());
+            grid.entry((x, y, z))
+                .or_default()
+                .push(welded.len() as u16 - 1);
+            welded.len() as u16 - 1
+        });
+        remap.push(i);
+    }
+
+    let indices = indices.iter().map(|&i| remap[i as usize]).collect();
+    (welded, indices)
+}
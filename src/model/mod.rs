@@ -1,44 +1,848 @@
-use citro3d::{math::Matrix4, uniform::Index, Instance};
-use vert_attr::VertAttrBuilder;
+use std::{cell::OnceCell, time::Duration};
 
-use crate::{Uniforms, Vec3};
+use citro3d::{math::Matrix4, texture::Tex, uniform::Index, Instance};
 
-use self::shape::Shape;
+use crate::{
+    logging::log_warn,
+    math::{Aabb, AxisConvention, Frustum, Rotation, Sphere, Transform, Vec3},
+    Uniforms, Vertex,
+};
 
+use self::{
+    animation::Animation,
+    colour::Colour,
+    material::{BlendMode, CullMode},
+    physics::RigidBody,
+    shape::Shape,
+    texenv::TexEnvConfig,
+    texture::sampling::TextureSampling,
+};
+
+pub mod animation;
+pub mod cache;
 pub mod colour;
+pub mod deferred;
+pub mod etc1;
+pub mod fog;
+pub mod ktx;
 pub mod material;
+pub mod mesh;
+pub mod physics;
+pub mod primitives;
+pub mod render_texture;
+pub mod scene;
 pub mod shape;
+mod strip;
+pub mod texenv;
 pub mod texture;
+mod wireframe;
+
+/// Counts of the work done drawing, see [`DrawState::take_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    pub texture_binds: u32,
+    /// Binds skipped as the texture was already bound the same way
+    pub binds_avoided: u32,
+    /// Shapes skipped as they were outside the view
+    pub shapes_culled: u32,
+}
+
+/// Amount of geometry in a model, see [`Model::stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GeometryStats {
+    pub shapes: usize,
+    pub verts: usize,
+    pub triangles: usize,
+    /// Linear memory used by vertex and index buffers
+    pub memory_bytes: usize,
+}
+
+impl std::ops::Add for GeometryStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            shapes: self.shapes + other.shapes,
+            verts: self.verts + other.verts,
+            triangles: self.triangles + other.triangles,
+            memory_bytes: self.memory_bytes + other.memory_bytes,
+        }
+    }
+}
+
+impl std::iter::Sum for GeometryStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |a, b| a + b)
+    }
+}
+
+/// GPU state carried between draw calls, used to skip redundant state changes.
+#[derive(Debug, Default)]
+pub struct DrawState {
+    texenv: Option<TexEnvConfig>,
+    blend_mode: Option<BlendMode>,
+    alpha_test: Option<Option<u8>>,
+    cull_mode: Option<CullMode>,
+    /// Texture bound to each unit and how it was set to be sampled
+    textures: [Option<(*const citro3d_sys::C3D_Tex, TextureSampling)>; 3],
+    material_uniforms: Option<[[f32; 4]; 5]>,
+    /// Whether the last vertex layout had a colour and second UVs
+    vertex_layout: Option<(bool, bool)>,
+    env_map: Option<bool>,
+    frustum: Option<Frustum>,
+    wireframe: bool,
+    /// Rotation the camera applies to the world, which billboards undo
+    camera_rotation: Rotation,
+    stats: DrawStats,
+}
+
+impl DrawState {
+    /// Counts since the last call to this
+    pub fn take_stats(&mut self) -> DrawStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    /// Cull against `frustum`, `None` draws everything
+    pub fn set_frustum(&mut self, frustum: Option<Frustum>) {
+        self.frustum = frustum;
+    }
+
+    pub fn set_camera_rotation(&mut self, rotation: Rotation) {
+        self.camera_rotation = rotation;
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Draw shapes as wireframes, to see how they're triangulated
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
+    /// Whether `bounds` (in world space) might be visible, counting `shapes`
+    /// as culled if not
+    fn is_visible(&mut self, bounds: &Sphere, shapes: u32) -> bool {
+        let visible = self.frustum.as_ref().map_or(true, |f| f.intersects(bounds));
+        if !visible {
+            self.stats.shapes_culled += shapes;
+        }
+        visible
+    }
+
+    /// Bind `tex` to texture `unit`, sampled with `sampling`
+    pub fn bind_texture(&mut self, unit: i32, tex: &Tex, sampling: &TextureSampling) {
+        let bound = Some((tex.as_raw(), *sampling));
+        let slot = &mut self.textures[unit as usize];
+        if *slot == bound {
+            self.stats.binds_avoided += 1;
+            return;
+        }
+        // the parameters are only sent to the GPU when the texture is bound
+        sampling.apply(tex);
+        tex.bind(unit);
+        *slot = bound;
+        self.stats.texture_binds += 1;
+    }
+
+    /// Record the material uniforms about to be set, returning false if
+    /// they're already set to these values.
+    pub(super) fn update_material_uniforms(&mut self, values: [[f32; 4]; 5]) -> bool {
+        if self.material_uniforms == Some(values) {
+            return false;
+        }
+        self.material_uniforms = Some(values);
+        true
+    }
+
+    /// Tell the shader which optional attributes `T` has
+    pub fn apply_vertex_layout<T: Vertex>(&mut self, gpu: &mut Instance, uniforms: &Uniforms) {
+        let layout = Some((T::HAS_COLOUR, T::HAS_SECOND_UV));
+        if self.vertex_layout == layout {
+            return;
+        }
+        gpu.bind_vertex_uniform(uniforms.use_vertex_colour, T::HAS_COLOUR);
+        gpu.bind_vertex_uniform(uniforms.use_second_uv, T::HAS_SECOND_UV);
+        self.vertex_layout = layout;
+    }
+
+    /// Tell the shader whether to generate sphere map UVs for texture 2
+    pub fn apply_env_map(&mut self, gpu: &mut Instance, uniforms: &Uniforms, env_map: bool) {
+        if self.env_map == Some(env_map) {
+            return;
+        }
+        gpu.bind_vertex_uniform(uniforms.use_env_map, env_map);
+        self.env_map = Some(env_map);
+    }
+
+    /// Set up blending for `blend_mode`, only writing depth for opaque
+    /// materials so translucent ones don't hide each other.
+    pub fn apply_blend_mode(&mut self, blend_mode: BlendMode) {
+        if self.blend_mode == Some(blend_mode) {
+            return;
+        }
+
+        let (src, dst) = match blend_mode {
+            BlendMode::Opaque => (ctru_sys::GPU_ONE, ctru_sys::GPU_ZERO),
+            BlendMode::AlphaBlend => (ctru_sys::GPU_SRC_ALPHA, ctru_sys::GPU_ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => (ctru_sys::GPU_SRC_ALPHA, ctru_sys::GPU_ONE),
+        };
+        let write_mask = if blend_mode.is_translucent() {
+            ctru_sys::GPU_WRITE_COLOR
+        } else {
+            ctru_sys::GPU_WRITE_ALL
+        };
+        unsafe {
+            citro3d_sys::C3D_AlphaBlend(
+                ctru_sys::GPU_BLEND_ADD,
+                ctru_sys::GPU_BLEND_ADD,
+                src,
+                dst,
+                src,
+                dst,
+            );
+            // the same test citro3d starts with, the depth buffer is reversed
+            citro3d_sys::C3D_DepthTest(true, ctru_sys::GPU_GREATER, write_mask);
+        }
+        self.blend_mode = Some(blend_mode);
+    }
+
+    /// Discard pixels with an alpha below `threshold`, or turn the test off
+    pub fn apply_alpha_test(&mut self, threshold: Option<u8>) {
+        if self.alpha_test == Some(threshold) {
+            return;
+        }
+
+        unsafe {
+            citro3d_sys::C3D_AlphaTest(
+                threshold.is_some(),
+                ctru_sys::GPU_GEQUAL,
+                threshold.unwrap_or(0) as _,
+            );
+        }
+        self.alpha_test = Some(threshold);
+    }
+
+    pub fn apply_cull_mode(&mut self, cull_mode: CullMode) {
+        if self.cull_mode == Some(cull_mode) {
+            return;
+        }
+
+        let mode = match cull_mode {
+            CullMode::None => ctru_sys::GPU_CULL_NONE,
+            CullMode::Back => ctru_sys::GPU_CULL_BACK_CCW,
+            CullMode::Front => ctru_sys::GPU_CULL_FRONT_CCW,
+        };
+        unsafe {
+            citro3d_sys::C3D_CullFace(mode);
+        }
+        self.cull_mode = Some(cull_mode);
+    }
+
+    pub fn apply_texenv(&mut self, gpu: &mut Instance, config: &TexEnvConfig) {
+        if self.texenv.as_ref() != Some(config) {
+            config.apply(gpu);
+            self.texenv = Some(config.clone());
+        }
+    }
+}
+
+/// How a billboard model is turned to face the camera, see
+/// [`Model::with_billboard`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BillboardMode {
+    /// Faces the camera head on from any angle, for particles
+    Spherical,
+    /// Only turns around the y axis, staying upright, for trees and the like
+    CylindricalY,
+}
+
+impl BillboardMode {
+    /// World space rotation turning +z towards the camera, given the
+    /// rotation it applies to the world
+    fn facing(self, camera: &Rotation) -> Rotation {
+        let to_world = camera.inverse();
+        match self {
+            BillboardMode::Spherical => to_world,
+            BillboardMode::CylindricalY => {
+                // towards the camera, away from where it's looking
+                let back = to_world.rotate(&Vec3::new(0.0, 0.0, 1.0));
+                if back.x.abs() <= f32::EPSILON && back.z.abs() <= f32::EPSILON {
+                    // looking straight up or down, any way round will do
+                    return Rotation::IDENTITY;
+                }
+                Rotation::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), back.x.atan2(back.z))
+            }
+        }
+    }
+}
+
+/// A shape in a [`Model`], by its position in [`Model::shapes`] or its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKey<'a> {
+    Index(usize),
+    Name(&'a str),
+}
+
+impl From<usize> for ShapeKey<'_> {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+impl<'a> From<&'a str> for ShapeKey<'a> {
+    fn from(name: &'a str) -> Self {
+        Self::Name(name)
+    }
+}
 
 #[derive(Debug)]
-pub struct Model<T: VertAttrBuilder + Clone> {
-    pub pos: Vec3,
-    pub rot: Vec3,
+pub struct Model<T: Vertex> {
+    pub name: String,
+    transform: Transform,
+    /// `transform` as a matrix, built when first drawn after it changes
+    matrix: OnceCell<Matrix4>,
     shapes: Vec<Shape<T>>,
+    /// Bounds of the vertices in model space
+    aabb: Aabb,
+    /// Sphere around all the shapes' bounds, in model space
+    bounds: Sphere,
+    /// Models placed relative to this one, so they move with it
+    children: Vec<Model<T>>,
+    /// Moves `transform` in [`Model::update`]
+    animation: Option<Animation>,
+    billboard: Option<BillboardMode>,
+    /// Moves `transform` in [`Model::update`], after `animation`
+    body: Option<RigidBody>,
+    /// Whether the model and its children are drawn at all
+    visible: bool,
 }
 
-impl<T: VertAttrBuilder + Clone> Model<T> {
-    pub fn new(pos: Vec3, rot: Vec3, shapes: Vec<Shape<T>>) -> Self {
-        Self { pos, rot, shapes }
+impl<T: Vertex> Model<T> {
+    pub fn new(name: impl Into<String>, transform: Transform, shapes: Vec<Shape<T>>) -> Self {
+        let bounds = Sphere::enclosing(shapes.iter().map(Shape::bounds)).unwrap_or_default();
+        let aabb = Aabb::from_points(shapes.iter().flat_map(|s| s.verts.iter().map(T::position)));
+        Self {
+            name: name.into(),
+            transform,
+            matrix: OnceCell::new(),
+            shapes,
+            aabb,
+            bounds,
+            children: Vec::new(),
+            animation: None,
+            billboard: None,
+            body: None,
+            visible: true,
+        }
+    }
+
+    pub fn with_aabb(mut self, aabb: Aabb) -> Self {
+        self.aabb = aabb;
+        self
     }
 
-    pub fn draw(&self, gpu: &mut Instance, uniforms: &Uniforms) {
-        let Vec3 { x, y, z } = self.pos;
+    /// Box around the shapes' vertices unless [`Model::with_aabb`] gave one.
+    /// A model whose box is a point isn't solid and is picked by its bounds
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
 
-        let mut transform = Matrix4::identity();
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
 
-        transform.scale(1.0, 1.0, 1.0);
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.matrix = OnceCell::new();
+    }
 
-        transform.rotate_x(-self.rot.y);
-        transform.rotate_y(self.rot.x);
-        transform.rotate_z(self.rot.z);
+    pub fn set_translation(&mut self, translation: Vec3) {
+        self.transform.translation = translation;
+        self.matrix = OnceCell::new();
+    }
 
-        transform.translate(x, y, z);
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.transform.rotation = rotation;
+        self.matrix = OnceCell::new();
+    }
 
-        gpu.bind_vertex_uniform(uniforms.model_matrix, &transform);
+    /// Turn a model built in `convention` to y up, for ones made in code
+    /// rather than loaded with [`crate::obj::LoadOptions::axis_convention`].
+    /// Done with the transform so the vertices are left alone, which turns
+    /// the children too as they're placed in the same space
+    pub fn convert_axes(&mut self, convention: AxisConvention) {
+        self.set_rotation(&self.transform.rotation * &convention.rotation());
+    }
 
-        for shape in &self.shapes {
-            shape.draw(gpu, uniforms);
+    pub fn set_scale(&mut self, scale: Vec3) {
+        self.transform.scale = scale;
+        self.matrix = OnceCell::new();
+    }
+
+    /// Turn the model so its -z axis points at `target`, which is in the
+    /// same space as its translation, keeping its y axis as close to `up` as
+    /// it can. Left as is if `target` is where the model already is.
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let Some(back) = self.transform.translation.sub(&target).normalized() else {
+            return;
+        };
+        // looking straight along `up` leaves it no say in the roll, so fall
+        // back to whichever axis is furthest from the direction
+        let right = up.cross(&back).normalized().unwrap_or_else(|| {
+            let fallback = if back.x.abs() < 0.9 {
+                Vec3::new(1.0, 0.0, 0.0)
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            };
+            // UNWRAP: the fallback is never parallel to `back`
+            fallback.cross(&back).normalized().unwrap()
+        });
+        let up = back.cross(&right);
+        // the model's axes are the columns of its rotation
+        self.set_rotation(Rotation::from_rows([
+            [right.x, up.x, back.x],
+            [right.y, up.y, back.y],
+            [right.z, up.z, back.z],
+        ]));
+    }
+
+    /// Draw with `matrix` as is, [`Model::transform`] becomes its
+    /// decomposition
+    pub fn set_matrix(&mut self, matrix: Matrix4) {
+        self.transform = Transform::from_matrix(&matrix);
+        self.matrix = OnceCell::from(matrix);
+    }
+
+    pub fn with_animation(mut self, animation: Animation) -> Self {
+        self.set_animation(Some(animation));
+        self
+    }
+
+    pub fn animation(&self) -> Option<&Animation> {
+        self.animation.as_ref()
+    }
+
+    /// Overwrites the transform every frame in [`Model::update`]
+    pub fn set_animation(&mut self, animation: Option<Animation>) {
+        self.animation = animation;
+    }
+
+    pub fn with_body(mut self, body: RigidBody) -> Self {
+        self.set_body(Some(body));
+        self
+    }
+
+    pub fn body(&self) -> Option<&RigidBody> {
+        self.body.as_ref()
+    }
+
+    pub fn body_mut(&mut self) -> Option<&mut RigidBody> {
+        self.body.as_mut()
+    }
+
+    pub fn set_body(&mut self, body: Option<RigidBody>) {
+        self.body = body;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Children included
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Turn the model's +z side to the camera in place of its rotation
+    pub fn with_billboard(mut self, mode: BillboardMode) -> Self {
+        self.billboard = Some(mode);
+        self
+    }
+
+    pub fn billboard(&self) -> Option<BillboardMode> {
+        self.billboard
+    }
+
+    pub fn set_billboard(&mut self, mode: Option<BillboardMode>) {
+        self.billboard = mode;
+    }
+
+    /// Matrix of the model's transform, only rebuilt after it's changed
+    pub fn matrix(&self) -> &Matrix4 {
+        self.matrix.get_or_init(|| self.transform.to_matrix())
+    }
+
+    pub fn bounds(&self) -> &Sphere {
+        &self.bounds
+    }
+
+    /// How much geometry the model and its children have
+    pub fn stats(&self) -> GeometryStats {
+        self.shapes
+            .iter()
+            .map(|s| GeometryStats {
+                shapes: 1,
+                verts: s.vert_count(),
+                triangles: s.triangle_count(),
+                memory_bytes: s.memory_bytes(),
+            })
+            .chain(self.children.iter().map(Model::stats))
+            .sum()
+    }
+
+    /// Attach `child`, whose transform is then relative to this model's.
+    /// Moving, rotating or scaling this model does the same to it, about
+    /// this model's origin.
+    pub fn add_child(&mut self, child: Model<T>) {
+        self.children.push(child);
+    }
+
+    pub fn children(&self) -> &[Model<T>] {
+        &self.children
+    }
+
+    /// First model called `name` among the children, their children and so
+    /// on, depth first
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Model<T>> {
+        for child in &mut self.children {
+            if child.name == name {
+                return Some(child);
+            }
+            if let Some(found) = child.child_mut(name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// This model and every visible descendant, each placed in world space given the
+    /// parent's transform to it is `parent`, `None` at the top level, and
+    /// billboards turned against `camera`
+    fn flatten<'a>(
+        &'a self,
+        parent: Option<&Transform>,
+        camera: &Rotation,
+        out: &mut Vec<Placed<'a, T>>,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let mut world = parent.map(|p| p * &self.transform);
+        if let Some(mode) = self.billboard {
+            let world = world.get_or_insert_with(|| self.transform.clone());
+            world.rotation = mode.facing(camera);
+        }
+        out.push(Placed {
+            model: self,
+            world: world.clone(),
+        });
+        let own = world.as_ref().unwrap_or(&self.transform);
+        for child in &self.children {
+            child.flatten(Some(own), camera, out);
+        }
+    }
+
+    pub fn shapes(&self) -> &[Shape<T>] {
+        &self.shapes
+    }
+
+    pub fn iter_shapes_mut(&mut self) -> impl Iterator<Item = &mut Shape<T>> {
+        self.shapes.iter_mut()
+    }
+
+    /// False if there's no such shape
+    pub fn set_shape_visible<'a>(&mut self, key: impl Into<ShapeKey<'a>>, visible: bool) -> bool {
+        let shape = match key.into() {
+            ShapeKey::Index(i) => self.shapes.get_mut(i),
+            ShapeKey::Name(name) => self.shapes.iter_mut().find(|s| s.name() == name),
+        };
+        let Some(shape) = shape else {
+            return false;
+        };
+        shape.set_visible(visible);
+        true
+    }
+
+    /// Children included
+    pub fn set_tint(&mut self, tint: Option<Colour>) {
+        for shape in &mut self.shapes {
+            shape.material_mut().set_tint(tint.clone());
+        }
+        for child in &mut self.children {
+            child.set_tint(tint.clone());
+        }
+    }
+
+    pub fn is_tinted(&self) -> bool {
+        self.shapes.iter().any(|s| s.material().tint().is_some())
+            || self.children.iter().any(Model::is_tinted)
+    }
+
+    /// Merge shapes which look the same, so the model takes fewer draw calls.
+    /// Shapes which can't be merged are left as they are, and shapes of
+    /// children are only merged with each other. Returns how many fewer
+    /// shapes there are.
+    pub fn optimize(&mut self) -> usize {
+        let merged_children = self.children.iter_mut().map(Model::optimize).sum::<usize>();
+        let before = self.shapes.len();
+        let mut groups: Vec<Vec<Shape<T>>> = Vec::new();
+        for shape in std::mem::take(&mut self.shapes) {
+            let group = groups.iter_mut().find(|g| {
+                let first = &g[0];
+                first.material().is_compatible(shape.material())
+                    && first.prim_type == shape.prim_type
+                    && first.is_visible() == shape.is_visible()
+            });
+            match group {
+                Some(group) => group.push(shape),
+                None => groups.push(vec![shape]),
+            }
+        }
+        for mut group in groups {
+            if group.len() == 1 {
+                self.shapes.append(&mut group);
+                continue;
+            }
+            match Shape::merge_from(&mut group) {
+                Ok(merged) => self.shapes.push(merged),
+                Err(e) => {
+                    log_warn!("not merging shapes in {}: {e}", self.name);
+                    self.shapes.append(&mut group);
+                }
+            }
+        }
+        merged_children + before - self.shapes.len()
+    }
+
+    /// Advance animations, the children's too, by `dt`. Called once per frame
+    pub fn update(&mut self, dt: Duration) {
+        if let Some(anim) = &mut self.animation {
+            // a finished animation would keep undoing anything else moving
+            // the model
+            if !anim.is_finished() {
+                if let Some(transform) = anim.advance(dt) {
+                    self.set_transform(transform);
+                }
+            }
+        }
+        if let Some(body) = &mut self.body {
+            let mut transform = self.transform.clone();
+            if body.update(&mut transform, &self.aabb, dt) {
+                self.set_transform(transform);
+            }
+        }
+        for shape in &mut self.shapes {
+            shape.update(dt);
+        }
+        for child in &mut self.children {
+            child.update(dt);
+        }
+    }
+
+    /// Whether any of the shapes have a translucent material
+    pub fn is_translucent(&self) -> bool {
+        self.shapes
+            .iter()
+            .any(|s| s.material().blend_mode().is_translucent())
+    }
+
+    /// Draw the model's shapes once at each of `transforms`, used in place of
+    /// its own, sharing the geometry and setting up each material once.
+    /// Instances outside the frustum cost no more than a sphere test.
+    /// Children aren't drawn, and translucent shapes aren't sorted between
+    /// instances.
+    pub fn draw_instances(
+        &self,
+        gpu: &mut Instance,
+        uniforms: &Uniforms,
+        state: &mut DrawState,
+        eye: &Vec3,
+        transforms: &[Transform],
+    ) {
+        if !self.visible {
+            return;
+        }
+        let shape_count = self.shapes.len() as u32;
+        let instances = transforms
+            .iter()
+            .filter(|t| state.is_visible(&self.bounds.transformed(t), shape_count))
+            .map(|t| (t, t.to_matrix()))
+            .collect::<Vec<_>>();
+
+        for translucent in [false, true] {
+            for shape in &self.shapes {
+                if shape.material().blend_mode().is_translucent() != translucent {
+                    continue;
+                }
+                let visible = instances
+                    .iter()
+                    .filter_map(|(t, matrix)| {
+                        let bounds = shape.bounds().transformed(t);
+                        let distance = bounds.center.sub(eye).length();
+                        state.is_visible(&bounds, 1).then_some((matrix, distance))
+                    })
+                    .collect::<Vec<_>>();
+                shape.draw_instances(gpu, uniforms, state, &visible);
+            }
+        }
+    }
+
+    /// Draw every shape of the model and its children, opaque ones first,
+    /// with the level of detail for a camera at `eye`. Use [`draw_sorted`] to
+    /// draw several models with translucent shapes correctly.
+    pub fn draw(&self, gpu: &mut Instance, uniforms: &Uniforms, state: &mut DrawState, eye: &Vec3) {
+        let mut models = Vec::new();
+        self.flatten(None, &state.camera_rotation, &mut models);
+        for translucent in [false, true] {
+            for placed in &models {
+                placed.draw_pass(gpu, uniforms, state, eye, translucent);
+            }
+        }
+    }
+}
+
+/// Model along with where it ends up in the world once its parents'
+/// transforms are applied
+struct Placed<'a, T: Vertex> {
+    model: &'a Model<T>,
+    /// Transform to world space, `None` when that's just the model's own
+    world: Option<Transform>,
+}
+
+impl<T: Vertex> Placed<'_, T> {
+    fn transform(&self) -> &Transform {
+        self.world.as_ref().unwrap_or(&self.model.transform)
+    }
+
+    /// Set the model matrix, reusing the model's own when it's at the top
+    /// level
+    fn bind(&self, gpu: &mut Instance, uniforms: &Uniforms) {
+        match &self.world {
+            Some(world) => gpu.bind_vertex_uniform(uniforms.model_matrix, &world.to_matrix()),
+            None => gpu.bind_vertex_uniform(uniforms.model_matrix, self.model.matrix()),
+        }
+    }
+
+    /// Draw just the translucent or just the opaque shapes, not including
+    /// the children's
+    fn draw_pass(
+        &self,
+        gpu: &mut Instance,
+        uniforms: &Uniforms,
+        state: &mut DrawState,
+        eye: &Vec3,
+        translucent: bool,
+    ) {
+        let world = self.transform();
+        let shapes = self
+            .model
+            .shapes
+            .iter()
+            .filter(|s| s.material().blend_mode().is_translucent() == translucent)
+            .collect::<Vec<_>>();
+        if shapes.is_empty()
+            || !state.is_visible(&self.model.bounds.transformed(world), shapes.len() as u32)
+        {
+            return;
+        }
+
+        self.bind(gpu, uniforms);
+
+        for shape in shapes {
+            let bounds = shape.bounds().transformed(world);
+            if state.is_visible(&bounds, 1) {
+                shape.draw(gpu, uniforms, state, bounds.center.sub(eye).length());
+            }
         }
     }
 }
+
+/// Find the first model called `name`, e.g. an object from an OBJ file.
+pub fn find_by_name<'a, T: Vertex>(models: &'a [Model<T>], name: &str) -> Option<&'a Model<T>> {
+    models.iter().find(|m| m.name == name)
+}
+
+pub fn find_by_name_mut<'a, T: Vertex>(
+    models: &'a mut [Model<T>],
+    name: &str,
+) -> Option<&'a mut Model<T>> {
+    models.iter_mut().find(|m| m.name == name)
+}
+
+/// Draw `models` with all the opaque shapes first, then the translucent
+/// ones furthest from `eye` first, so each blends over everything behind it.
+/// Translucent shapes are sorted a model at a time, by the center of its
+/// bounds.
+///
+/// Opaque shapes are drawn in order of their [`Material::sort_key`], so ones
+/// sharing textures and state are drawn together.
+///
+/// Models and shapes outside the frustum set with [`DrawState::set_frustum`]
+/// are skipped. Children are drawn as if they were models in `models`.
+///
+/// [`Material::sort_key`]: material::Material::sort_key
+pub fn draw_sorted<T: Vertex>(
+    models: &[Model<T>],
+    eye: &Vec3,
+    gpu: &mut Instance,
+    uniforms: &Uniforms,
+    state: &mut DrawState,
+) {
+    let mut flat = Vec::new();
+    for model in models {
+        model.flatten(None, &state.camera_rotation, &mut flat);
+    }
+    let models = flat;
+
+    let visible = models
+        .iter()
+        .map(|p| {
+            let opaque = p
+                .model
+                .shapes
+                .iter()
+                .filter(|s| !s.material().blend_mode().is_translucent());
+            state.is_visible(
+                &p.model.bounds.transformed(p.transform()),
+                opaque.count() as u32,
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut opaque = models
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| visible[i])
+        .flat_map(|(i, p)| p.model.shapes.iter().map(move |s| (i, s)))
+        .filter(|(_, s)| !s.material().blend_mode().is_translucent())
+        .filter_map(|(i, s)| {
+            let bounds = s.bounds().transformed(models[i].transform());
+            let distance = bounds.center.sub(eye).length();
+            state.is_visible(&bounds, 1).then_some((i, s, distance))
+        })
+        .collect::<Vec<_>>();
+    // stable, so shapes with the same key keep their order within a model
+    opaque.sort_by_key(|(_, s, _)| s.material().sort_key());
+    let mut bound_model = None;
+    for (i, shape, distance) in opaque {
+        if bound_model != Some(i) {
+            models[i].bind(gpu, uniforms);
+            bound_model = Some(i);
+        }
+        shape.draw(gpu, uniforms, state, distance);
+    }
+
+    let mut translucent = models
+        .iter()
+        .filter(|p| p.model.is_translucent())
+        .map(|p| {
+            let center = p.transform().apply(&p.model.aabb.center());
+            (p, center.sub(eye).length())
+        })
+        .collect::<Vec<_>>();
+    translucent.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    for (placed, _) in translucent {
+        placed.draw_pass(gpu, uniforms, state, eye, true);
+    }
+}
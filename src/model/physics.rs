@@ -0,0 +1,98 @@
+//! Toy physics: falling and bouncing off a flat ground.
+
+use std::time::Duration;
+
+use crate::math::{Aabb, Transform, Vec3};
+
+/// Time simulated at once, fixed so bounces don't depend on the frame rate
+const STEP: Duration = Duration::from_millis(8);
+/// Most steps taken in one update, so a long stall doesn't take even longer
+/// to catch up on
+const MAX_STEPS: u32 = 8;
+
+/// Velocity and how it changes, see [`Model::set_body`](super::Model::set_body)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBody {
+    pub velocity: Vec3,
+    /// Downward acceleration, in units per second per second
+    pub gravity: f32,
+    /// Fraction of the speed kept bouncing off the ground
+    pub bounciness: f32,
+    /// Height of the ground in the space the model's transform is in
+    pub ground: f32,
+    /// Time passed which is less than a step, so not simulated yet
+    pending: Duration,
+}
+
+impl RigidBody {
+    pub fn new(gravity: f32, bounciness: f32, ground: f32) -> Self {
+        Self {
+            velocity: Vec3::splat(0.0),
+            gravity,
+            bounciness,
+            ground,
+            pending: Duration::ZERO,
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// Move `transform` on by `dt`, with `aabb` being the bounds of the model
+    /// it places. Returns whether it moved.
+    pub(super) fn update(&mut self, transform: &mut Transform, aabb: &Aabb, dt: Duration) -> bool {
+        self.pending += dt;
+        let mut steps = 0;
+        let mut moved = false;
+        while self.pending >= STEP {
+            self.pending -= STEP;
+            steps += 1;
+            if steps > MAX_STEPS {
+                self.pending = Duration::ZERO;
+                break;
+            }
+            moved |= self.step(transform, aabb);
+        }
+        moved
+    }
+
+    fn step(&mut self, transform: &mut Transform, aabb: &Aabb) -> bool {
+        let secs = STEP.as_secs_f32();
+        let resting =
+            self.velocity == Vec3::splat(0.0) && self.bottom(transform, aabb) <= self.ground;
+        if resting {
+            return false;
+        }
+        self.velocity.y -= self.gravity * secs;
+        transform.translation = transform.translation.add(&self.velocity.scale(secs));
+
+        let below = self.ground - self.bottom(transform, aabb);
+        if below > 0.0 {
+            transform.translation.y += below;
+            self.velocity.y = -self.velocity.y * self.bounciness;
+            // bounces too small to leave the ground for a step would jitter
+            // forever, so come to rest instead
+            if self.velocity.y <= self.gravity * secs * 2.0 {
+                self.velocity = Vec3::splat(0.0);
+            }
+        }
+        true
+    }
+
+    /// Height of the lowest corner of `aabb` once placed by `transform`
+    fn bottom(&self, transform: &Transform, aabb: &Aabb) -> f32 {
+        let (min, max) = (&aabb.min, &aabb.max);
+        (0..8)
+            .map(|i| {
+                let corner = Vec3::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                );
+                transform.apply(&corner).y
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+}
@@ -0,0 +1,225 @@
+//! Meshes made in code, centered on the origin and wound counter-clockwise.
+
+use std::f32::consts::{PI, TAU};
+
+use citro3d::buffer::Primitive;
+
+use crate::{
+    math::{Vec2, Vec3},
+    VertNorm,
+};
+
+use super::{
+    material::Material,
+    shape::{Shape, ShapeError},
+};
+
+/// Vertices and the indices of the triangles between them.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub verts: Vec<VertNorm>,
+    pub indices: Vec<u16>,
+}
+
+impl Mesh {
+    /// Make a shape drawing the mesh with `mat`
+    pub fn into_shape(self, mat: Material) -> Result<Shape<VertNorm>, ShapeError> {
+        Shape::new_indexed(mat, Primitive::Triangles, &self.verts, &self.indices)
+    }
+
+    /// Add a `columns` x `rows` grid of quads, from vertices laid out a row
+    /// at a time by `vert`. Row 0 is the top edge, seen from the front.
+    fn grid(&mut self, columns: u16, rows: u16, mut vert: impl FnMut(u16, u16) -> VertNorm) {
+        let start = self.verts.len() as u16;
+        for row in 0..=rows {
+            for col in 0..=columns {
+                self.verts.push(vert(col, row));
+            }
+        }
+        let stride = columns + 1;
+        for row in 0..rows {
+            for col in 0..columns {
+                let (a, b) = (start + row * stride + col, start + (row + 1) * stride + col);
+                self.indices
+                    .extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+    }
+}
+
+/// Cube `size` along each side, each face with the whole texture on it.
+pub fn cube(size: f32) -> Mesh {
+    let half = size / 2.0;
+    // normal, then the directions right and up across the face seen from
+    // outside
+    let faces = [
+        (
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+        (
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+        (
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+        (
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+        (
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ),
+        (
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ),
+    ];
+    let mut mesh = Mesh::default();
+    for (norm, right, up) in faces {
+        mesh.grid(1, 1, |col, row| {
+            let (x, y) = (col as f32 * 2.0 - 1.0, 1.0 - row as f32 * 2.0);
+            VertNorm {
+                pos: norm.add(&right.scale(x)).add(&up.scale(y)).scale(half),
+                tex: Vec2::new(col as f32, 1.0 - row as f32),
+                norm: norm.clone(),
+            }
+        });
+    }
+    mesh
+}
+
+/// Sphere made of `slices` segments around the y axis and `stacks` rings
+/// from pole to pole, with the texture wrapped around it once.
+pub fn uv_sphere(radius: f32, slices: u16, stacks: u16) -> Mesh {
+    let mut mesh = Mesh::default();
+    mesh.grid(slices, stacks, |seg, ring| {
+        let lat = PI * ring as f32 / stacks as f32;
+        let lon = TAU * seg as f32 / slices as f32;
+        let norm = Vec3::new(lat.sin() * lon.cos(), lat.cos(), -lat.sin() * lon.sin());
+        VertNorm {
+            pos: norm.scale(radius),
+            tex: Vec2::new(
+                seg as f32 / slices as f32,
+                1.0 - ring as f32 / stacks as f32,
+            ),
+            norm,
+        }
+    });
+    mesh
+}
+
+/// Flat `width` x `depth` rectangle facing up the y axis, split into
+/// `subdivisions` quads along each side.
+pub fn plane(width: f32, depth: f32, subdivisions: u16) -> Mesh {
+    let n = subdivisions.max(1);
+    let mut mesh = Mesh::default();
+    mesh.grid(n, n, |col, row| {
+        let (u, v) = (col as f32 / n as f32, row as f32 / n as f32);
+        VertNorm {
+            pos: Vec3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth),
+            tex: Vec2::new(u, 1.0 - v),
+            norm: Vec3::new(0.0, 1.0, 0.0),
+        }
+    });
+    mesh
+}
+
+/// Cone along the y axis with its tip at the top, its sides made of
+/// `segments` triangles and its base capped like [`cylinder`]'s.
+pub fn cone(radius: f32, height: f32, segments: u16) -> Mesh {
+    let half = height / 2.0;
+    let rim = |seg: u16| {
+        let lon = TAU * seg as f32 / segments as f32;
+        (lon.cos(), -lon.sin())
+    };
+
+    let mut mesh = Mesh::default();
+    mesh.grid(segments, 1, |seg, ring| {
+        let (x, z) = rim(seg);
+        // the tip is a ring of zero radius, so each side keeps its own normal
+        let r = ring as f32 * radius;
+        VertNorm {
+            pos: Vec3::new(x * r, half - ring as f32 * height, z * r),
+            tex: Vec2::new(seg as f32 / segments as f32, 1.0 - ring as f32),
+            norm: Vec3::new(x * height, radius, z * height)
+                .normalized()
+                .unwrap_or(Vec3::new(0.0, 1.0, 0.0)),
+        }
+    });
+
+    let norm = Vec3::new(0.0, -1.0, 0.0);
+    let center = mesh.verts.len() as u16;
+    mesh.verts.push(VertNorm {
+        pos: norm.scale(half),
+        tex: Vec2::new(0.5, 0.5),
+        norm: norm.clone(),
+    });
+    for seg in 0..segments {
+        let (x, z) = rim(seg);
+        mesh.verts.push(VertNorm {
+            pos: Vec3::new(x * radius, -half, z * radius),
+            tex: Vec2::new(0.5 + x / 2.0, 0.5 + z / 2.0),
+            norm: norm.clone(),
+        });
+        let (a, b) = (center + 1 + seg, center + 1 + (seg + 1) % segments);
+        mesh.indices.extend_from_slice(&[center, b, a]);
+    }
+    mesh
+}
+
+/// Capped cylinder along the y axis, its sides made of `segments` quads with
+/// the texture wrapped around them once. The caps have the texture laid
+/// flat across them.
+pub fn cylinder(radius: f32, height: f32, segments: u16) -> Mesh {
+    let half = height / 2.0;
+    let rim = |seg: u16| {
+        let lon = TAU * seg as f32 / segments as f32;
+        (lon.cos(), -lon.sin())
+    };
+
+    let mut mesh = Mesh::default();
+    mesh.grid(segments, 1, |seg, ring| {
+        let (x, z) = rim(seg);
+        VertNorm {
+            pos: Vec3::new(x * radius, half - ring as f32 * height, z * radius),
+            tex: Vec2::new(seg as f32 / segments as f32, 1.0 - ring as f32),
+            norm: Vec3::new(x, 0.0, z),
+        }
+    });
+
+    for y in [1.0, -1.0] {
+        let norm = Vec3::new(0.0, y, 0.0);
+        let center = mesh.verts.len() as u16;
+        mesh.verts.push(VertNorm {
+            pos: norm.scale(half),
+            tex: Vec2::new(0.5, 0.5),
+            norm: norm.clone(),
+        });
+        for seg in 0..segments {
+            let (x, z) = rim(seg);
+            mesh.verts.push(VertNorm {
+                pos: Vec3::new(x * radius, y * half, z * radius),
+                tex: Vec2::new(0.5 + x / 2.0, 0.5 - z * y / 2.0),
+                norm: norm.clone(),
+            });
+            let (a, b) = (center + 1 + seg, center + 1 + (seg + 1) % segments);
+            // the bottom is seen from below, so goes round the other way
+            if y > 0.0 {
+                mesh.indices.extend_from_slice(&[center, a, b]);
+            } else {
+                mesh.indices.extend_from_slice(&[center, b, a]);
+            }
+        }
+    }
+    mesh
+}
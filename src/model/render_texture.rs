@@ -0,0 +1,94 @@
+//! Textures which can be rendered into.
+
+use std::rc::Rc;
+
+use citro3d::Instance;
+
+use super::{
+    colour::Colour,
+    deferred,
+    texture::{Texture, TextureError, TextureLocation},
+};
+
+/// A texture with an offscreen render target drawing into it. The texture
+/// can be put on a [`Material`](super::material::Material) like any other,
+/// but mustn't be drawn by anything rendered into it.
+pub struct RenderTexture {
+    texture: Rc<Texture>,
+    target: *mut citro3d_sys::C3D_RenderTarget,
+}
+
+impl RenderTexture {
+    /// Create a `width` x `height` render texture, both of which must be
+    /// powers of two. It's put in VRAM if there's room, as the GPU will be
+    /// writing to it every frame.
+    pub fn new(width: u16, height: u16) -> Result<Self, TextureError> {
+        // in an Rc before the GPU texture is made, so it never moves
+        let texture = Rc::new(
+            Texture::solid(width, height, &Colour::new(0, 0, 0, 0xFF))
+                .with_location(TextureLocation::Vram),
+        );
+        let Some(tex) = texture.citro_tex() else {
+            return Err(TextureError::Malformed("failed to allocate render texture"));
+        };
+        // SAFETY: the texture is alive and stays so as long as the target,
+        // which holds a reference to it
+        let target = unsafe {
+            citro3d_sys::C3D_RenderTargetCreateFromTex(
+                tex.as_raw() as *mut _,
+                ctru_sys::GPU_TEXFACE_2D,
+                0,
+                ctru_sys::GPU_RB_DEPTH16 as _,
+            )
+        };
+        if target.is_null() {
+            return Err(TextureError::Malformed("failed to create render target"));
+        }
+        Ok(Self { texture, target })
+    }
+
+    /// The texture drawn into, to put on a material
+    pub fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+
+    /// Clear the colour and depth, to be called before [`RenderTexture::select`]
+    /// each frame like [`citro3d::render::Target::clear`].
+    pub fn clear(&mut self, colour: u32) {
+        // SAFETY: the target is valid for as long as self
+        unsafe {
+            citro3d_sys::C3D_RenderTargetClear(self.target, citro3d_sys::C3D_CLEAR_ALL, colour, 0);
+        }
+    }
+
+    /// Make this the target for following draws, in place of
+    /// [`Instance::select_render_target`].
+    pub fn select(&mut self, _gpu: &mut Instance) {
+        // SAFETY: the target is valid for as long as self, and taking the
+        // instance means this is only called while it's alive
+        unsafe {
+            citro3d_sys::C3D_FrameDrawOn(self.target);
+        }
+    }
+}
+
+impl Drop for RenderTexture {
+    fn drop(&mut self) {
+        // released before the texture, which is dropped after this
+        deferred::release(Target(self.target));
+    }
+}
+
+/// Deletes the render target when dropped, once it's made it through the
+/// deferred queue
+struct Target(*mut citro3d_sys::C3D_RenderTarget);
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        // SAFETY: the target was created in RenderTexture::new and nothing
+        // has used it since it was released
+        unsafe {
+            citro3d_sys::C3D_RenderTargetDelete(self.0);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Everything drawn in a frame and where it's seen from.
+
+use std::time::Duration;
+
+use citro3d::{math::Matrix4, Instance};
+
+use crate::{
+    camera::Camera,
+    math::{Frustum, Transform, Vec3},
+    Uniforms, Vertex,
+};
+
+use super::{draw_sorted, fog::SceneSettings, DrawState, Model};
+
+/// Times the camera's pushed out of everything it's in, so being pushed out
+/// of one model into another (like in a corner) is caught
+const COLLISION_PASSES: usize = 3;
+
+/// What can be seen through `projection` from `camera`, in world space
+fn view_frustum(projection: &Matrix4, camera: &Matrix4) -> Frustum {
+    let mut combined = citro3d_sys::C3D_Mtx { m: [0.0; 16] };
+    // SAFETY: all three point at valid matrices, and out isn't either input
+    unsafe {
+        citro3d_sys::Mtx_Multiply(&mut combined, projection.as_raw(), camera.as_raw());
+    }
+    Frustum::from_matrix(&Matrix4::from_raw(combined))
+}
+
+#[derive(Debug)]
+pub struct Scene<T: Vertex> {
+    models: Vec<Model<T>>,
+    /// Models drawn many times over, at each of their transforms
+    instanced: Vec<(Model<T>, Vec<Transform>)>,
+    pub camera: Camera,
+    /// Colour of the light over everything, as RGBA from 0 to 1
+    pub light_colour: [f32; 4],
+    /// Colour render targets are cleared to before drawing, as 0xRRGGBBAA
+    pub clear_colour: u32,
+    settings: SceneSettings,
+}
+
+impl<T: Vertex> Scene<T> {
+    pub fn new(camera: Camera, models: Vec<Model<T>>) -> Self {
+        Self {
+            models,
+            instanced: Vec::new(),
+            camera,
+            light_colour: [1.0; 4],
+            clear_colour: 0,
+            settings: SceneSettings::default(),
+        }
+    }
+
+    pub fn add_model(&mut self, model: Model<T>) {
+        self.models.push(model);
+    }
+
+    /// Draw `model` at each of `transforms` rather than its own, see
+    /// [`Model::draw_instances`]. Only worth it for models without
+    /// translucent shapes, as they aren't sorted.
+    pub fn add_instanced(&mut self, model: Model<T>, transforms: Vec<Transform>) {
+        self.instanced.push((model, transforms));
+    }
+
+    /// The old ones are freed once the GPU's done with them
+    pub fn set_models(&mut self, models: Vec<Model<T>>) {
+        self.models = models;
+    }
+
+    pub fn models(&self) -> &[Model<T>] {
+        &self.models
+    }
+
+    pub fn models_mut(&mut self) -> &mut [Model<T>] {
+        &mut self.models
+    }
+
+    /// First top level model called `name`
+    pub fn model(&self, name: &str) -> Option<&Model<T>> {
+        self.models.iter().find(|m| m.name == name)
+    }
+
+    pub fn model_mut(&mut self, name: &str) -> Option<&mut Model<T>> {
+        self.models.iter_mut().find(|m| m.name == name)
+    }
+
+    /// Nearest visible top level model the ray from `origin` in `dir` hits,
+    /// by its position in [`Scene::models`] and how far along the ray it is
+    /// in lengths of `dir`. Hitting a child counts as hitting the model it's
+    /// under. Tested against each model's [`Model::aabb`], or its bounds if
+    /// it hasn't got one, so it's only as precise as those.
+    pub fn pick(&self, origin: &Vec3, dir: &Vec3) -> Option<(usize, f32)> {
+        let camera = self.camera.transform().rotation;
+        let mut placed = Vec::new();
+        let mut nearest: Option<(usize, f32)> = None;
+        for (i, model) in self.models.iter().enumerate() {
+            placed.clear();
+            model.flatten(None, &camera, &mut placed);
+            for p in &placed {
+                // the ray's carried into the model's space rather than the
+                // box out of it, keeping the box aligned
+                let to_model = p.transform().inverse();
+                let local_origin = to_model.apply(origin);
+                let local_dir = to_model.apply(&origin.add(dir)).sub(&local_origin);
+                let aabb = p.model.aabb();
+                let hit = if aabb.min == aabb.max {
+                    p.model.bounds().ray_hit(&local_origin, &local_dir)
+                } else {
+                    aabb.ray_hit(&local_origin, &local_dir)
+                };
+                if let Some(t) = hit {
+                    if nearest.map_or(true, |(_, best)| t < best) {
+                        nearest = Some((i, t));
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Keep the camera from going into any model, if [`Camera::collide`] is
+    /// set. Called after moving it, it's pushed back out the shortest way so
+    /// it slides along whatever it ran into. Only models with an
+    /// [`Model::aabb`] are solid, as the box is what's tested.
+    pub fn resolve_camera_collision(&mut self) {
+        if !self.camera.collide {
+            return;
+        }
+        let radius = self.camera.collision_radius;
+        let rotation = self.camera.transform().rotation;
+        let mut placed = Vec::new();
+        for model in &self.models {
+            model.flatten(None, &rotation, &mut placed);
+        }
+
+        let mut pos = self.camera.pos.clone();
+        for _ in 0..COLLISION_PASSES {
+            let mut moved = false;
+            for p in &placed {
+                let aabb = p.model.aabb();
+                if aabb.min == aabb.max {
+                    continue;
+                }
+                // done in model space so the box stays aligned, with the
+                // radius undone by the smallest scale so it's never too small
+                let transform = p.transform();
+                let scale = &transform.scale;
+                let smallest = scale.x.abs().min(scale.y.abs()).min(scale.z.abs());
+                let local = transform.inverse().apply(&pos);
+                if let Some(out) = aabb.push_out(&local, radius / smallest.max(f32::EPSILON)) {
+                    pos = transform.apply(&out);
+                    moved = true;
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+        self.camera.pos = pos;
+    }
+
+    /// Fog and anything else set once for the whole scene
+    pub fn settings_mut(&mut self) -> &mut SceneSettings {
+        &mut self.settings
+    }
+
+    /// Advance every model's animations and the camera's smoothing by `dt`,
+    /// called once per frame
+    pub fn update(&mut self, dt: Duration) {
+        self.camera.update(dt);
+        for model in &mut self.models {
+            model.update(dt);
+        }
+        for (model, _) in &mut self.instanced {
+            model.update(dt);
+        }
+    }
+
+    /// Draw everything from the camera through `projection`, into whichever
+    /// render target is selected. Models outside the view are culled and
+    /// translucent shapes sorted, see [`draw_sorted`].
+    pub fn draw(
+        &mut self,
+        gpu: &mut Instance,
+        uniforms: &Uniforms,
+        state: &mut DrawState,
+        projection: &Matrix4,
+    ) {
+        let camera = self.camera.transform();
+        let camera_matrix = self.camera.view_matrix();
+        gpu.bind_vertex_uniform(uniforms.camera_matrix, &camera_matrix);
+        gpu.bind_vertex_uniform(uniforms.projection_matrix, projection);
+        let [r, g, b, a] = self.light_colour;
+        // SAFETY: the uniform index came from the bound program
+        unsafe {
+            citro3d_sys::C3D_FVUnifSet(
+                citro3d::shader::Type::Vertex.into(),
+                uniforms.light_colour.into(),
+                r,
+                g,
+                b,
+                a,
+            );
+        }
+        let (near, far) = (self.camera.projection.near(), self.camera.projection.far());
+        self.settings.apply(near, far);
+
+        state.set_camera_rotation(camera.rotation.clone());
+        state.set_frustum(Some(view_frustum(projection, &camera_matrix)));
+        let eye = self.camera.eye();
+        // first, so anything translucent in the rest blends over them
+        for (model, transforms) in &self.instanced {
+            model.draw_instances(gpu, uniforms, state, &eye, transforms);
+        }
+        draw_sorted(&self.models, &eye, gpu, uniforms, state);
+    }
+}
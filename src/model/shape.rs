@@ -1,93 +1,623 @@
-use crate::Uniforms;
+use std::{cell::OnceCell, fmt, mem, time::Duration};
 
-use super::material::Material;
-use citro3d::{
-    attrib,
-    buffer::{self, Primitive},
-    Instance,
+use crate::{math::Sphere, Uniforms, Vertex};
+
+use super::{
+    deferred, material::Material, strip, texture::sampling::TextureSampling, wireframe, DrawState,
 };
+use citro3d::{attrib, buffer::Primitive, math::Matrix4, Instance};
 use ctru::linear::LinearAllocator;
-use vert_attr::VertAttrBuilder;
 
 #[derive(Debug)]
-pub struct Shape<T: VertAttrBuilder + Clone> {
-    mat: Material,
-    prim_type: Primitive,
+pub enum ShapeError {
+    /// There wasn't room in linear memory for the shape's buffers, `needed`
+    /// being the size in bytes of the one which didn't fit
+    OutOfLinearMemory { needed: usize },
+    /// An index refers past the end of the vertices, which would have the
+    /// GPU reading garbage or hanging
+    IndexOutOfRange { index: u16, len: usize },
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShapeError::OutOfLinearMemory { needed } => {
+                write!(
+                    f,
+                    "out of linear memory (needed {} KiB)",
+                    needed.div_ceil(1024)
+                )
+            }
+            ShapeError::IndexOutOfRange { index, len } => {
+                write!(f, "index {index} is out of range for {len} vertices")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+/// Why shapes couldn't be combined by [`Shape::merge`]
+#[derive(Debug)]
+pub enum MergeError {
+    /// There were no shapes to merge
+    Empty,
+    /// The shape at this index draws a different kind of primitive to the
+    /// first
+    PrimitiveMismatch(usize),
+    /// The shape at this index has a material which looks different to the
+    /// first's
+    MaterialMismatch(usize),
+    /// Only triangle lists can be joined, strips and fans can't be
+    NotTriangles,
+    /// The shape at this index has levels of detail, which aren't merged
+    HasLods(usize),
+    /// The merged shape would have too many vertices to index
+    TooManyVertices(usize),
+    Shape(ShapeError),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::Empty => write!(f, "no shapes to merge"),
+            MergeError::PrimitiveMismatch(i) => {
+                write!(f, "shape {i} draws different primitives to the first")
+            }
+            MergeError::MaterialMismatch(i) => {
+                write!(f, "shape {i} has a different material to the first")
+            }
+            MergeError::NotTriangles => write!(f, "only triangle lists can be merged"),
+            MergeError::HasLods(i) => write!(f, "shape {i} has levels of detail"),
+            MergeError::TooManyVertices(count) => {
+                write!(f, "{count} vertices is too many for one shape")
+            }
+            MergeError::Shape(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl From<ShapeError> for MergeError {
+    fn from(e: ShapeError) -> Self {
+        Self::Shape(e)
+    }
+}
+
+/// Make room for `additional` more items in `buf`, failing rather than
+/// aborting if linear memory runs out. The GPU may still be reading the old
+/// buffer, so rather than reallocating in place the items are moved to a new
+/// one and the old is freed through [`deferred`].
+fn reserve<E: 'static>(
+    buf: &mut Vec<E, LinearAllocator>,
+    additional: usize,
+) -> Result<(), ShapeError> {
+    if buf.capacity() - buf.len() >= additional {
+        return Ok(());
+    }
+    let needed = buf.len() + additional;
+    let mut grown = Vec::new_in(LinearAllocator);
+    grown
+        .try_reserve_exact(needed.max(buf.capacity() * 2))
+        .map_err(|_| ShapeError::OutOfLinearMemory {
+            needed: needed * mem::size_of::<E>(),
+        })?;
+    grown.append(buf);
+    deferred::release(mem::replace(buf, grown));
+    Ok(())
+}
+
+/// Copy `items` into linear memory
+fn linear_copy<E: Clone + 'static>(items: &[E]) -> Result<Vec<E, LinearAllocator>, ShapeError> {
+    let mut buf = Vec::new_in(LinearAllocator);
+    reserve(&mut buf, items.len())?;
+    buf.extend_from_slice(items);
+    Ok(buf)
+}
+
+/// Where the GPU reads `verts` from. `C3D_SetBufInfo` copies it, so it's
+/// never written through
+fn buf_info<T>(verts: &[T], attr_info: &attrib::Info) -> citro3d_sys::C3D_BufInfo {
+    let mut buf_info = citro3d_sys::C3D_BufInfo::default();
+    // SAFETY: only the pointer is stored, the caller keeps the vertices where
+    // they are for as long as the info is used
+    let res = unsafe {
+        citro3d_sys::BufInfo_Init(&mut buf_info);
+        citro3d_sys::BufInfo_Add(
+            &mut buf_info,
+            verts.as_ptr().cast(),
+            std::mem::size_of::<T>() as _,
+            attr_info.attr_count(),
+            attr_info.permutation(),
+        )
+    };
+    assert!(res >= 0, "failed to add vertex buffer");
+    buf_info
+}
+
+/// Lower detail version of a shape, drawn from further away
+#[derive(Debug)]
+struct Lod<T: Vertex> {
+    /// Furthest from the camera this is drawn, see [`Shape::with_lods`]
+    max_distance: f32,
     verts: Vec<T, LinearAllocator>,
-    attr_info: attrib::Info,
+    /// For `verts`, which are never added to so this stays valid
+    buf_info: citro3d_sys::C3D_BufInfo,
 }
 
-impl<T: VertAttrBuilder + Clone> Shape<T> {
-    pub fn new(mat: Material, prim_type: Primitive, verts: &[T]) -> Self {
-        let mut vertex_buffer = Vec::with_capacity_in(verts.len(), LinearAllocator);
-        vertex_buffer.extend_from_slice(verts);
+#[derive(Debug)]
+pub struct Shape<T: Vertex> {
+    name: String,
+    mat: Material,
+    pub(super) prim_type: Primitive,
+    pub(super) verts: Vec<T, LinearAllocator>,
+    pub(super) indices: Option<Vec<u16, LinearAllocator>>,
+    attr_info: attrib::Info,
+    /// Built by [`Shape::finalize`], cleared when vertices are added
+    buf_info: Option<citro3d_sys::C3D_BufInfo>,
+    max_distance: f32,
+    /// Lower detail levels, by increasing distance
+    lods: Vec<Lod<T>>,
+    bounds: Sphere,
+    visible: bool,
+    /// Made the first time it's drawn as a wireframe
+    wireframe: OnceCell<Vec<Shape<T>>>,
+}
 
-        let attr_info = T::vert_attrs();
+impl<T: Vertex> Shape<T> {
+    pub fn new(mat: Material, prim_type: Primitive, verts: &[T]) -> Result<Self, ShapeError> {
+        let mut shape = Self::with_capacity(mat, prim_type, verts.len())?;
+        shape.extend_from_slice(verts)?;
+        shape.finalize();
+        Ok(shape)
+    }
 
-        Self {
+    /// Create a shape with room for `capacity` vertices, to be added with
+    /// [`Shape::push_vert`] or [`Shape::extend_from_slice`]. It isn't drawn
+    /// until [`Shape::finalize`] is called.
+    pub fn with_capacity(
+        mat: Material,
+        prim_type: Primitive,
+        capacity: usize,
+    ) -> Result<Self, ShapeError> {
+        let mut verts = Vec::new_in(LinearAllocator);
+        reserve(&mut verts, capacity)?;
+        Ok(Self {
+            name: String::new(),
             mat,
             prim_type,
-            verts: vertex_buffer,
-            attr_info,
+            verts,
+            indices: None,
+            attr_info: T::vert_attrs(),
+            buf_info: None,
+            max_distance: f32::INFINITY,
+            lods: Vec::new(),
+            bounds: Sphere::default(),
+            visible: true,
+            wireframe: OnceCell::new(),
+        })
+    }
+
+    /// Add a vertex, after which the shape needs finalizing again
+    pub fn push_vert(&mut self, vert: T) -> Result<(), ShapeError> {
+        reserve(&mut self.verts, 1)?;
+        self.verts.push(vert);
+        self.buf_info = None;
+        self.wireframe = OnceCell::new();
+        Ok(())
+    }
+
+    /// Add `verts`, after which the shape needs finalizing again
+    pub fn extend_from_slice(&mut self, verts: &[T]) -> Result<(), ShapeError> {
+        reserve(&mut self.verts, verts.len())?;
+        self.verts.extend_from_slice(verts);
+        self.buf_info = None;
+        self.wireframe = OnceCell::new();
+        Ok(())
+    }
+
+    /// Whether the shape can be drawn, i.e. it's been finalized since
+    /// vertices were last added
+    pub fn is_finalized(&self) -> bool {
+        self.buf_info.is_some()
+    }
+
+    /// Point the GPU at the vertices as they are now and update the bounds.
+    /// Needed after adding vertices, shapes which haven't been finalized
+    /// aren't drawn.
+    pub fn finalize(&mut self) {
+        // the buffer stays where it is until more vertices are added, which
+        // clears this
+        self.buf_info = Some(buf_info(&self.verts, &self.attr_info));
+        self.bounds = Sphere::from_points(self.verts.iter().map(T::position));
+    }
+
+    /// Create a shape which is drawn by indexing into `verts`, so vertices
+    /// shared between primitives only need to be stored once.
+    pub fn new_indexed(
+        mat: Material,
+        prim_type: Primitive,
+        verts: &[T],
+        indices: &[u16],
+    ) -> Result<Self, ShapeError> {
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= verts.len()) {
+            return Err(ShapeError::IndexOutOfRange {
+                index,
+                len: verts.len(),
+            });
         }
+        let index_buffer = linear_copy(indices)?;
+
+        let mut shape = Self::new(mat, prim_type, verts)?;
+        shape.indices = Some(index_buffer);
+        Ok(shape)
     }
 
-    pub fn draw(&self, gpu: &mut Instance, uniforms: &Uniforms) {
-        let tex = self.mat.get_texture();
-        self.mat.set_uniforms(gpu, uniforms);
+    /// Create a shape with several levels of detail, each `(max_distance,
+    /// verts)` being drawn when the camera is up to `max_distance` from the
+    /// shape. Nothing is drawn past the furthest level, so give it a
+    /// distance of [`f32::INFINITY`] to always draw something.
+    ///
+    /// # Panics
+    /// If `lods` is empty.
+    pub fn with_lods(
+        mat: Material,
+        prim_type: Primitive,
+        mut lods: Vec<(f32, Vec<T>)>,
+    ) -> Result<Self, ShapeError> {
+        lods.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let mut lods = lods.into_iter();
+        let (max_distance, verts) = lods
+            .next()
+            .expect("a shape needs at least one level of detail");
+        let mut shape = Self::new(mat, prim_type, &verts)?.with_max_distance(max_distance);
+        for (max_distance, verts) in lods {
+            shape.add_lod(max_distance, &verts)?;
+        }
+        Ok(shape)
+    }
 
-        let stage0 = citro3d::texenv::Stage::new(0).unwrap();
+    /// Only draw the full detail vertices when the camera is up to
+    /// `max_distance` away, past which the levels added with
+    /// [`Shape::add_lod`] are used
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
 
-        if let Some(t) = tex {
-            t.bind(0);
-
-            if self.mat.use_vertex_colours() {
-                gpu.texenv(stage0)
-                    .src(
-                        citro3d::texenv::Mode::BOTH,
-                        citro3d::texenv::Source::Texture0,
-                        Some(citro3d::texenv::Source::PrimaryColor),
-                        None,
-                    )
-                    .func(
-                        citro3d::texenv::Mode::BOTH,
-                        citro3d::texenv::CombineFunc::Add,
-                    );
-            } else {
-                gpu.texenv(stage0)
-                    .src(
-                        citro3d::texenv::Mode::BOTH,
-                        citro3d::texenv::Source::Texture0,
-                        None,
-                        None,
-                    )
-                    .func(
-                        citro3d::texenv::Mode::BOTH,
-                        citro3d::texenv::CombineFunc::Replace,
-                    );
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    /// Lower detail levels as `(max_distance, verts)`, by increasing
+    /// distance
+    pub(super) fn lods(&self) -> impl Iterator<Item = (f32, &[T])> {
+        self.lods
+            .iter()
+            .map(|lod| (lod.max_distance, &lod.verts[..]))
+    }
+
+    /// Add a lower detail level drawn with `prim_type` like the rest of the
+    /// shape, from where the previous level stops to `max_distance` away.
+    /// Levels must be added by increasing distance.
+    pub fn add_lod(&mut self, max_distance: f32, verts: &[T]) -> Result<(), ShapeError> {
+        let verts = linear_copy(verts)?;
+        let buf_info = buf_info(&verts, &self.attr_info);
+        self.lods.push(Lod {
+            max_distance,
+            verts,
+            buf_info,
+        });
+        Ok(())
+    }
+
+    /// Number of full detail vertices
+    pub fn vert_count(&self) -> usize {
+        self.verts.len()
+    }
+
+    /// Number of triangles drawn at full detail, not counting degenerate
+    /// ones joining strips
+    pub fn triangle_count(&self) -> usize {
+        self.triangles().len()
+    }
+
+    /// Vertex indices of each triangle drawn at full detail, skipping
+    /// degenerate ones
+    pub(super) fn triangles(&self) -> Vec<[usize; 3]> {
+        let order = match &self.indices {
+            Some(indices) => indices.iter().map(|&i| i as usize).collect::<Vec<_>>(),
+            None => (0..self.verts.len()).collect(),
+        };
+        let tris = match self.prim_type {
+            Primitive::Triangles => order.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            Primitive::TriangleStrip => order.windows(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            Primitive::TriangleFan => order
+                .windows(2)
+                .skip(1)
+                .map(|t| [order[0], t[0], t[1]])
+                .collect(),
+            // made by a geometry shader, which we don't have
+            Primitive::GeometryPrim => Vec::new(),
+        };
+        tris.into_iter()
+            .filter(|[a, b, c]: &[usize; 3]| a != b && b != c && c != a)
+            .collect()
+    }
+
+    /// Redraw an indexed triangle list as a strip if that takes fewer
+    /// indices, so the GPU processes fewer vertices. Returns whether it was
+    /// converted, the triangles drawn are the same either way.
+    pub fn stripify(&mut self) -> Result<bool, ShapeError> {
+        if self.prim_type != Primitive::Triangles {
+            return Ok(false);
+        }
+        let Some(strip) = self.indices.as_deref().and_then(strip::stripify) else {
+            return Ok(false);
+        };
+        if let Some(old) = self.indices.replace(linear_copy(&strip)?) {
+            deferred::release(old);
+        }
+        self.prim_type = Primitive::TriangleStrip;
+        Ok(true)
+    }
+
+    /// Linear memory used by the vertices and indices, every level of
+    /// detail included
+    pub fn memory_bytes(&self) -> usize {
+        let verts =
+            self.verts.capacity() + self.lods.iter().map(|l| l.verts.capacity()).sum::<usize>();
+        let indices = self.indices.as_ref().map_or(0, Vec::capacity);
+        verts * std::mem::size_of::<T>() + indices * std::mem::size_of::<u16>()
+    }
+
+    /// Level of detail to draw `distance` from the camera, 0 being full
+    /// detail, or `None` if it's too far away to draw at all
+    fn lod_for(&self, distance: f32) -> Option<usize> {
+        if distance <= self.max_distance {
+            return Some(0);
+        }
+        self.lods
+            .iter()
+            .position(|lod| distance <= lod.max_distance)
+            .map(|i| i + 1)
+    }
+
+    /// Combine `shapes` into one, so they're drawn with a single draw call.
+    /// They must all be triangle lists with materials which look the same,
+    /// the first shape's name and material are kept.
+    pub fn merge(mut shapes: Vec<Shape<T>>) -> Result<Self, MergeError> {
+        Self::merge_from(&mut shapes)
+    }
+
+    /// [`Shape::merge`], leaving `shapes` as they were if it fails. On
+    /// success the first's material has been moved out, so they should be
+    /// dropped.
+    pub(super) fn merge_from(shapes: &mut [Shape<T>]) -> Result<Self, MergeError> {
+        let Some(first) = shapes.first() else {
+            return Err(MergeError::Empty);
+        };
+        if shapes.len() > 1 && first.prim_type != Primitive::Triangles {
+            return Err(MergeError::NotTriangles);
+        }
+        for (i, shape) in shapes.iter().enumerate() {
+            if shape.prim_type != first.prim_type {
+                return Err(MergeError::PrimitiveMismatch(i));
+            }
+            if !shape.mat.is_compatible(&first.mat) {
+                return Err(MergeError::MaterialMismatch(i));
+            }
+            if !shape.lods.is_empty() {
+                return Err(MergeError::HasLods(i));
+            }
+        }
+
+        let vert_count = shapes.iter().map(|s| s.verts.len()).sum::<usize>();
+        let mut verts = Vec::with_capacity(vert_count);
+        let mut indices = Vec::new();
+        let indexed = shapes.iter().any(|s| s.indices.is_some());
+        if indexed && vert_count > u16::MAX as usize + 1 {
+            return Err(MergeError::TooManyVertices(vert_count));
+        }
+        for shape in shapes.iter() {
+            // rebased onto where the shape's vertices start
+            let base = verts.len() as u16;
+            verts.extend_from_slice(&shape.verts);
+            if !indexed {
+                continue;
+            }
+            match &shape.indices {
+                Some(own) => indices.extend(own.iter().map(|i| base + i)),
+                None => indices.extend((0..shape.verts.len() as u16).map(|i| base + i)),
+            }
+        }
+
+        let mut merged = if indexed {
+            Self::new_indexed(Material::default(), first.prim_type, &verts, &indices)?
+        } else {
+            Self::new(Material::default(), first.prim_type, &verts)?
+        };
+        merged.name = first.name.clone();
+        merged.visible = first.visible;
+        merged.max_distance = first.max_distance;
+        merged.mat = std::mem::take(&mut shapes[0].mat);
+        Ok(merged)
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn bounds(&self) -> &Sphere {
+        &self.bounds
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Show or hide the shape, hidden shapes are skipped when drawing
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.mat
+    }
+
+    pub fn material_mut(&mut self) -> &mut Material {
+        &mut self.mat
+    }
+
+    /// Advance anything animated about the shape by `dt`
+    pub fn update(&mut self, dt: Duration) {
+        self.mat.update(dt);
+    }
+
+    /// Draw the shape. Textures are created on the GPU once, when the
+    /// material is made, so this only binds them, uploading nothing but
+    /// regions changed through [`Material::update_texture_region`].
+    ///
+    /// `distance` is how far the shape is from the camera, which picks the
+    /// level of detail.
+    pub fn draw(
+        &self,
+        gpu: &mut Instance,
+        uniforms: &Uniforms,
+        state: &mut DrawState,
+        distance: f32,
+    ) {
+        if !self.visible || !self.is_finalized() {
+            return;
+        }
+        let Some(lod) = self.lod_for(distance) else {
+            return;
+        };
+        if state.wireframe() {
+            // of the full detail vertices, it's for debugging them
+            for wire in self.wireframe.get_or_init(|| wireframe::build(self)) {
+                wire.draw_mesh(gpu, uniforms, state, 0);
             }
         } else {
-            let env = gpu.texenv(stage0);
-            env.reset();
-
-            env.src(
-                citro3d::texenv::Mode::BOTH,
-                citro3d::texenv::Source::PrimaryColor,
-                None,
-                None,
-            )
-            .func(
-                citro3d::texenv::Mode::BOTH,
-                citro3d::texenv::CombineFunc::Replace,
-            );
-        }
-
-        let mut buf_info = buffer::Info::new();
-        let buf_vtos = buf_info
-            .add(&self.verts, &self.attr_info)
-            .expect("failed to bind verts");
+            self.draw_mesh(gpu, uniforms, state, lod);
+        }
+    }
+
+    /// Draw the shape once with each of `instances`, a model matrix and the
+    /// distance from the camera, only setting up the material for the first.
+    /// The model matrix is left bound to the last instance's.
+    pub(super) fn draw_instances(
+        &self,
+        gpu: &mut Instance,
+        uniforms: &Uniforms,
+        state: &mut DrawState,
+        instances: &[(&Matrix4, f32)],
+    ) {
+        if !self.visible || !self.is_finalized() || instances.is_empty() {
+            return;
+        }
+        if state.wireframe() {
+            for &(matrix, distance) in instances {
+                gpu.bind_vertex_uniform(uniforms.model_matrix, matrix);
+                self.draw(gpu, uniforms, state, distance);
+            }
+            return;
+        }
+        self.apply_material(gpu, uniforms, state);
+        for &(matrix, distance) in instances {
+            if let Some(lod) = self.lod_for(distance) {
+                gpu.bind_vertex_uniform(uniforms.model_matrix, matrix);
+                self.submit(gpu, state, lod);
+            }
+        }
+    }
+
+    /// Draw level of detail `lod`, as numbered by [`Shape::lod_for`]
+    fn draw_mesh(
+        &self,
+        gpu: &mut Instance,
+        uniforms: &Uniforms,
+        state: &mut DrawState,
+        lod: usize,
+    ) {
+        self.apply_material(gpu, uniforms, state);
+        self.submit(gpu, state, lod);
+    }
+
+    /// Set the GPU up to draw with the material
+    fn apply_material(&self, gpu: &mut Instance, uniforms: &Uniforms, state: &mut DrawState) {
+        self.mat.sync_textures();
+        let tex = self.mat.get_texture();
+        self.mat.set_uniforms(gpu, uniforms, state);
+        state.apply_vertex_layout::<T>(gpu, uniforms);
+
+        if let Some(t) = tex {
+            state.bind_texture(0, t, self.mat.sampling());
+        }
+        if let Some(t) = self.mat.get_ambient_texture() {
+            state.bind_texture(1, t, self.mat.sampling());
+        }
+        let env_map = self.mat.get_env_map();
+        if let Some(t) = env_map {
+            // sphere maps are never tiled
+            state.bind_texture(2, t, &TextureSampling::default());
+        }
+        state.apply_env_map(gpu, uniforms, env_map.is_some());
+        state.apply_texenv(gpu, self.mat.texenv());
+        state.apply_blend_mode(self.mat.blend_mode());
+        state.apply_alpha_test(self.mat.alpha_test());
+        state.apply_cull_mode(self.mat.cull_mode());
+    }
+
+    /// Issue the draw call for level of detail `lod`, with the material
+    /// already set up
+    fn submit(&self, gpu: &mut Instance, state: &mut DrawState, lod: usize) {
+        let (buf_info, indices, vert_count) = match lod.checked_sub(1) {
+            Some(i) => {
+                let lod = &self.lods[i];
+                (&lod.buf_info, None, lod.verts.len())
+            }
+            // unfinalized shapes have nothing safe to point the GPU at
+            None => match &self.buf_info {
+                Some(buf_info) => (buf_info, self.indices.as_ref(), self.verts.len()),
+                None => return,
+            },
+        };
 
         gpu.set_attr_info(&self.attr_info);
-        gpu.draw_arrays(self.prim_type, buf_vtos);
+        state.stats.draw_calls += 1;
+        let prim = self.prim_type as ctru_sys::GPU_Primitive_t;
+        // SAFETY: the buffer info points at the level's verts, which are
+        // alive and in linear memory as are the indices
+        unsafe {
+            citro3d_sys::C3D_SetBufInfo(buf_info as *const _ as *mut _);
+            match indices {
+                Some(indices) => citro3d_sys::C3D_DrawElements(
+                    prim,
+                    indices.len() as _,
+                    citro3d_sys::C3D_UNSIGNED_SHORT as _,
+                    indices.as_ptr().cast(),
+                ),
+                None => citro3d_sys::C3D_DrawArrays(prim, 0, vert_count as _),
+            }
+        }
+    }
+}
+
+impl<T: Vertex> Drop for Shape<T> {
+    fn drop(&mut self) {
+        deferred::release(mem::replace(&mut self.verts, Vec::new_in(LinearAllocator)));
+        if let Some(indices) = self.indices.take() {
+            deferred::release(indices);
+        }
+        for lod in self.lods.drain(..) {
+            deferred::release(lod.verts);
+        }
     }
 }
@@ -0,0 +1,163 @@
+//! Indexed triangle lists to strips, joined with degenerate triangles.
+
+use std::collections::HashMap;
+
+/// Triangles in `indices` rotated to start at their lowest index, which
+/// keeps their winding, and sorted so lists can be compared
+fn canonical(tris: impl Iterator<Item = [u16; 3]>) -> Vec<[u16; 3]> {
+    let mut tris = tris
+        .filter(|[a, b, c]| a != b && b != c && c != a)
+        .map(|t| {
+            let first = (0..3).min_by_key(|&i| t[i]).unwrap_or(0);
+            [t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+        })
+        .collect::<Vec<_>>();
+    tris.sort_unstable();
+    tris
+}
+
+/// Triangles drawn by the strip `strip`, in their drawn winding
+fn strip_triangles(strip: &[u16]) -> impl Iterator<Item = [u16; 3]> + '_ {
+    strip.windows(3).enumerate().map(|(k, w)| {
+        if k % 2 == 0 {
+            [w[0], w[1], w[2]]
+        } else {
+            [w[1], w[0], w[2]]
+        }
+    })
+}
+
+/// Grow a strip from triangle `start`, beginning at its `rotation`th corner
+fn grow(
+    tris: &[[u16; 3]],
+    edges: &HashMap<(u16, u16), usize>,
+    used: &[bool],
+    start: usize,
+    rotation: usize,
+) -> (Vec<u16>, Vec<usize>) {
+    let t = tris[start];
+    let mut strip = vec![t[rotation], t[(rotation + 1) % 3], t[(rotation + 2) % 3]];
+    let mut taken = vec![start];
+    loop {
+        let (x, y) = (strip[strip.len() - 2], strip[strip.len() - 1]);
+        // the next triangle is drawn (x, y, z) or, every other one, (y, x, z)
+        let edge = if (strip.len() - 2) % 2 == 0 {
+            (x, y)
+        } else {
+            (y, x)
+        };
+        let Some(&next) = edges.get(&edge) else {
+            break;
+        };
+        if used[next] || taken.contains(&next) {
+            break;
+        }
+        let [a, b, c] = tris[next];
+        // the corner not on the shared edge
+        let z = [(a, b, c), (b, c, a), (c, a, b)]
+            .into_iter()
+            .find(|&(p, q, _)| (p, q) == edge)
+            .map(|(_, _, r)| r);
+        let Some(z) = z else {
+            break;
+        };
+        strip.push(z);
+        taken.push(next);
+    }
+    (strip, taken)
+}
+
+/// Turn the triangle list `indices` into a single strip drawing the same
+/// triangles. `None` if that wouldn't take fewer indices than the list.
+pub(super) fn stripify(indices: &[u16]) -> Option<Vec<u16>> {
+    let tris = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .collect::<Vec<_>>();
+    // each directed edge to the triangle it belongs to, a neighbour across
+    // an edge has it going the other way
+    let mut edges = HashMap::new();
+    for (i, &[a, b, c]) in tris.iter().enumerate() {
+        for edge in [(a, b), (b, c), (c, a)] {
+            edges.entry(edge).or_insert(i);
+        }
+    }
+
+    let mut used = vec![false; tris.len()];
+    let mut out: Vec<u16> = Vec::new();
+    for start in 0..tris.len() {
+        if used[start] {
+            continue;
+        }
+        // UNWRAP: there are always three rotations to try
+        let (strip, taken) = (0..3)
+            .map(|rotation| grow(&tris, &edges, &used, start, rotation))
+            .max_by_key(|(strip, _)| strip.len())
+            .unwrap();
+        for t in taken {
+            used[t] = true;
+        }
+
+        if let (Some(&last), Some(&first)) = (out.last(), strip.first()) {
+            out.extend([last, first]);
+            // the strip has to start on an even triangle to keep its winding
+            if out.len() % 2 == 1 {
+                out.push(first);
+            }
+        }
+        out.extend(strip);
+    }
+
+    debug_assert_eq!(
+        canonical(strip_triangles(&out)),
+        canonical(tris.iter().copied()),
+        "strip draws different triangles to the list"
+    );
+    (out.len() < indices.len()).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangle list of a `size` x `size` grid of quads, counter-clockwise
+    /// seen from above, with vertex indices starting at `base`
+    fn grid(size: u16, base: u16) -> Vec<u16> {
+        let row = size + 1;
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = base + y * row + x;
+                indices.extend([i, i + row, i + 1, i + 1, i + row, i + row + 1]);
+            }
+        }
+        indices
+    }
+
+    fn list_triangles(indices: &[u16]) -> Vec<[u16; 3]> {
+        canonical(indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]))
+    }
+
+    #[test]
+    fn grid_strip_draws_the_same_triangles() {
+        let indices = grid(8, 0);
+        let strip = stripify(&indices).expect("a grid should be shorter as a strip");
+
+        assert!(strip.len() < indices.len());
+        assert_eq!(canonical(strip_triangles(&strip)), list_triangles(&indices));
+    }
+
+    #[test]
+    fn separate_grids_are_joined_keeping_winding() {
+        let mut indices = grid(4, 0);
+        indices.extend(grid(3, 100));
+        let strip = stripify(&indices).expect("grids should be shorter as a strip");
+
+        assert_eq!(canonical(strip_triangles(&strip)), list_triangles(&indices));
+    }
+
+    #[test]
+    fn lone_triangle_is_left_as_a_list() {
+        assert_eq!(stripify(&[0, 1, 2]), None);
+    }
+}
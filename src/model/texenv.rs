@@ -0,0 +1,311 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use citro3d::{
+    texenv::{CombineFunc, Mode, Source, Stage},
+    Instance,
+};
+
+use super::colour::Colour;
+
+/// Number of texenv stages the PICA200 has.
+pub const MAX_STAGES: usize = 6;
+
+/// Inputs and combiner for a single texenv stage. `func` is used for both the
+/// colour and alpha channels unless `alpha_func` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TexEnvStage {
+    pub sources: (Source, Option<Source>, Option<Source>),
+    pub func: CombineFunc,
+    pub alpha_func: Option<CombineFunc>,
+    /// Colour read through [`Source::Constant`], packed as the GPU wants it
+    pub constant: Option<u32>,
+}
+
+impl TexEnvStage {
+    pub fn new(sources: (Source, Option<Source>, Option<Source>), func: CombineFunc) -> Self {
+        Self {
+            sources,
+            func,
+            alpha_func: None,
+            constant: None,
+        }
+    }
+
+    pub fn with_alpha_func(mut self, func: CombineFunc) -> Self {
+        self.alpha_func = Some(func);
+        self
+    }
+
+    pub fn with_constant(mut self, colour: &Colour) -> Self {
+        self.constant = Some(u32::from_le_bytes([
+            colour.r(),
+            colour.g(),
+            colour.b(),
+            colour.a(),
+        ]));
+        self
+    }
+}
+
+/// Full description of the texenv pipeline for a material. Stages past the
+/// ones described here are reset to pass through the previous stage.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TexEnvConfig {
+    stages: Vec<TexEnvStage>,
+}
+
+impl TexEnvConfig {
+    /// Output the interpolated vertex colour as-is
+    pub fn vertex_colour() -> Self {
+        Self {
+            stages: vec![TexEnvStage::new(
+                (Source::PrimaryColor, None, None),
+                CombineFunc::Replace,
+            )],
+        }
+    }
+
+    /// Output texture 0 as-is
+    pub fn textured() -> Self {
+        Self {
+            stages: vec![TexEnvStage::new(
+                (Source::Texture0, None, None),
+                CombineFunc::Replace,
+            )],
+        }
+    }
+
+    /// Texture 0 with its alpha multiplied by the vertex colour's
+    pub fn textured_translucent() -> Self {
+        Self {
+            stages: vec![TexEnvStage::new(
+                (Source::Texture0, Some(Source::PrimaryColor), None),
+                CombineFunc::Replace,
+            )
+            .with_alpha_func(CombineFunc::Modulate)],
+        }
+    }
+
+    /// Combine texture 0 with the vertex colour
+    pub fn textured_vertex_colour() -> Self {
+        Self {
+            stages: vec![TexEnvStage::new(
+                (Source::Texture0, Some(Source::PrimaryColor), None),
+                CombineFunc::Add,
+            )],
+        }
+    }
+
+    /// Like [`Self::textured_vertex_colour`] but multiplying the alpha
+    /// channels, so a translucent material colour carries through
+    pub fn textured_vertex_colour_translucent() -> Self {
+        Self {
+            stages: vec![TexEnvStage::new(
+                (Source::Texture0, Some(Source::PrimaryColor), None),
+                CombineFunc::Add,
+            )
+            .with_alpha_func(CombineFunc::Modulate)],
+        }
+    }
+
+    /// Output texture 0 multiplied by `colour`
+    pub fn tinted(colour: &Colour) -> Self {
+        Self {
+            stages: vec![TexEnvStage::new(
+                (Source::Texture0, Some(Source::Constant), None),
+                CombineFunc::Modulate,
+            )
+            .with_constant(colour)],
+        }
+    }
+
+    /// Config for a material's main texture and colour, before any extra
+    /// stages are added
+    pub fn base(textured: bool, vertex_colours: bool, translucent: bool) -> Self {
+        match (textured, vertex_colours, translucent) {
+            (true, true, false) => Self::textured_vertex_colour(),
+            (true, true, true) => Self::textured_vertex_colour_translucent(),
+            (true, false, false) => Self::textured(),
+            (true, false, true) => Self::textured_translucent(),
+            (false, _, _) => Self::vertex_colour(),
+        }
+    }
+
+    /// Output `colour` everywhere, ignoring textures and lighting
+    pub fn flat(colour: &Colour) -> Self {
+        Self {
+            stages: vec![
+                TexEnvStage::new((Source::Constant, None, None), CombineFunc::Replace)
+                    .with_constant(colour),
+            ],
+        }
+    }
+
+    /// Fails if all the hardware stages are already used
+    pub fn with_tint(self, colour: &Colour) -> Result<Self, Self> {
+        self.with_stage(
+            TexEnvStage::new(
+                (Source::Previous, Some(Source::Constant), None),
+                CombineFunc::Modulate,
+            )
+            .with_constant(colour),
+        )
+    }
+
+    /// Append a stage, fails if all the hardware stages are already used.
+    pub fn with_stage(mut self, stage: TexEnvStage) -> Result<Self, Self> {
+        if self.stages.len() >= MAX_STAGES {
+            return Err(self);
+        }
+        self.stages.push(stage);
+        Ok(self)
+    }
+
+    /// Hash of the config, equal for equal configs
+    pub fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for stage in &self.stages {
+            let (s0, s1, s2) = stage.sources;
+            (s0 as u32).hash(&mut hasher);
+            s1.map(|s| s as u32).hash(&mut hasher);
+            s2.map(|s| s as u32).hash(&mut hasher);
+            (stage.func as u32).hash(&mut hasher);
+            stage.alpha_func.map(|f| f as u32).hash(&mut hasher);
+            stage.constant.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    pub fn stages(&self) -> &[TexEnvStage] {
+        &self.stages
+    }
+
+    pub fn apply(&self, gpu: &mut Instance) {
+        for i in 0..MAX_STAGES {
+            // UNWRAP: MAX_STAGES is the number of stages the hardware has
+            let env = gpu.texenv(Stage::new(i).unwrap());
+            env.reset();
+
+            if let Some(stage) = self.stages.get(i) {
+                let (s0, s1, s2) = stage.sources;
+                env.src(Mode::BOTH, s0, s1, s2);
+                if let Some(constant) = stage.constant {
+                    env.color(constant);
+                }
+                match stage.alpha_func {
+                    Some(alpha_func) => {
+                        env.func(Mode::RGB, stage.func)
+                            .func(Mode::ALPHA, alpha_func);
+                    }
+                    None => {
+                        env.func(Mode::BOTH, stage.func);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untextured_is_vertex_colour() {
+        let expected = [TexEnvStage::new(
+            (Source::PrimaryColor, None, None),
+            CombineFunc::Replace,
+        )];
+        for (vertex_colours, translucent) in [(false, false), (true, false), (true, true)] {
+            let config = TexEnvConfig::base(false, vertex_colours, translucent);
+            assert_eq!(config.stages(), expected);
+        }
+    }
+
+    #[test]
+    fn textured_materials() {
+        let texture_only = TexEnvStage::new((Source::Texture0, None, None), CombineFunc::Replace);
+        assert_eq!(
+            TexEnvConfig::base(true, false, false).stages(),
+            [texture_only]
+        );
+
+        let with_colour = TexEnvStage::new(
+            (Source::Texture0, Some(Source::PrimaryColor), None),
+            CombineFunc::Add,
+        );
+        assert_eq!(
+            TexEnvConfig::base(true, true, false).stages(),
+            [with_colour]
+        );
+    }
+
+    #[test]
+    fn translucent_materials_keep_the_colour_alpha() {
+        let glass = TexEnvConfig::base(true, false, true);
+        assert_eq!(
+            glass.stages(),
+            [TexEnvStage {
+                sources: (Source::Texture0, Some(Source::PrimaryColor), None),
+                func: CombineFunc::Replace,
+                alpha_func: Some(CombineFunc::Modulate),
+                constant: None,
+            }]
+        );
+
+        let tinted_glass = TexEnvConfig::base(true, true, true);
+        assert_eq!(
+            tinted_glass.stages(),
+            [TexEnvStage {
+                sources: (Source::Texture0, Some(Source::PrimaryColor), None),
+                func: CombineFunc::Add,
+                alpha_func: Some(CombineFunc::Modulate),
+                constant: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn tint_is_appended_as_its_own_stage() {
+        let red = Colour::new(0xFF, 0, 0, 0xFF);
+        let config = TexEnvConfig::textured().with_tint(&red).unwrap();
+        assert_eq!(
+            config.stages(),
+            [
+                TexEnvStage::new((Source::Texture0, None, None), CombineFunc::Replace),
+                TexEnvStage {
+                    sources: (Source::Previous, Some(Source::Constant), None),
+                    func: CombineFunc::Modulate,
+                    alpha_func: None,
+                    constant: Some(0xFF00_00FF),
+                },
+            ]
+        );
+        assert_ne!(config.key(), TexEnvConfig::textured().key());
+    }
+
+    #[test]
+    fn stages_run_out() {
+        let stage = TexEnvStage::new((Source::Previous, None, None), CombineFunc::Replace);
+        let mut config = TexEnvConfig::default();
+        for _ in 0..MAX_STAGES {
+            config = config.with_stage(stage).unwrap();
+        }
+        assert!(config.with_stage(stage).is_err());
+    }
+
+    #[test]
+    fn equal_configs_have_equal_keys() {
+        assert_eq!(
+            TexEnvConfig::base(true, true, false).key(),
+            TexEnvConfig::textured_vertex_colour().key()
+        );
+        assert_ne!(
+            TexEnvConfig::textured_vertex_colour().key(),
+            TexEnvConfig::textured_vertex_colour_translucent().key()
+        );
+    }
+}
@@ -1,7 +1,224 @@
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    fmt, ptr,
+};
+
+use citro3d::texture::{ColorFormat, Tex, TexParams};
+use ctru::linear::LinearAllocator;
+
+use crate::{logging::log_warn, math::Vec2};
+
+use self::{decode::Image, swizzle::tiled_index};
+
+use super::{colour::Colour, deferred};
+
+pub mod atlas;
+pub mod decode;
+pub mod mipmap;
+pub mod sampling;
+pub mod shared;
+pub mod swizzle;
+
+/// Largest texture the GPU can sample from, in either dimension
+const MAX_SIZE: u32 = 1024;
+
+/// Pixel formats a texture can be stored as on the GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureFormat {
+    #[default]
+    Rgba8,
+    Rgb565,
+    Rgba4,
+    /// Alpha only, the colour channels read as black
+    A8,
+    Etc1,
+    /// ETC1 with a separate 4-bit alpha channel
+    Etc1A4,
+}
+
+impl TextureFormat {
+    /// Number of bytes a `width` x `height` image takes up in this format
+    pub fn image_size(self, width: u16, height: u16) -> usize {
+        let pixels = width as usize * height as usize;
+        match self {
+            TextureFormat::Rgba8 => pixels * 4,
+            TextureFormat::Rgb565 | TextureFormat::Rgba4 => pixels * 2,
+            TextureFormat::A8 => pixels,
+            // 8 bytes per 4x4 block
+            TextureFormat::Etc1 => pixels / 2,
+            // plus another 8 bytes of alpha
+            TextureFormat::Etc1A4 => pixels,
+        }
+    }
+
+    /// Size of a single texel, `None` for block compressed formats
+    pub fn bytes_per_pixel(self) -> Option<usize> {
+        match self {
+            TextureFormat::Rgba8 => Some(4),
+            TextureFormat::Rgb565 | TextureFormat::Rgba4 => Some(2),
+            TextureFormat::A8 => Some(1),
+            TextureFormat::Etc1 | TextureFormat::Etc1A4 => None,
+        }
+    }
+
+    /// RGB565 for opaque images, RGBA8 for ones with alpha
+    pub fn for_image(image: &Image) -> Self {
+        if image.has_alpha {
+            TextureFormat::Rgba8
+        } else {
+            TextureFormat::Rgb565
+        }
+    }
+
+    /// One RGBA8 pixel as a texel in this format, `None` for block
+    /// compressed ones
+    pub fn encode(self, [r, g, b, a]: [u8; 4]) -> Option<Vec<u8>> {
+        let [r, g, b, a] = [r, g, b, a].map(u16::from);
+        match self {
+            TextureFormat::Rgba8 => Some([a, b, g, r].map(|c| c as u8).to_vec()),
+            TextureFormat::Rgb565 => {
+                let texel = (r >> 3) << 11 | (g >> 2) << 5 | b >> 3;
+                Some(texel.to_le_bytes().to_vec())
+            }
+            TextureFormat::Rgba4 => {
+                let texel = (r >> 4) << 12 | (g >> 4) << 8 | (b >> 4) << 4 | a >> 4;
+                Some(texel.to_le_bytes().to_vec())
+            }
+            TextureFormat::A8 => Some(vec![a as u8]),
+            TextureFormat::Etc1 | TextureFormat::Etc1A4 => None,
+        }
+    }
+
+    fn colour_format(self) -> ColorFormat {
+        match self {
+            TextureFormat::Rgba8 => ColorFormat::Rgba8,
+            TextureFormat::Rgb565 => ColorFormat::Rgb565,
+            TextureFormat::Rgba4 => ColorFormat::Rgba4,
+            TextureFormat::A8 => ColorFormat::A8,
+            TextureFormat::Etc1 => ColorFormat::Etc1,
+            TextureFormat::Etc1A4 => ColorFormat::Etc1A4,
+        }
+    }
+}
+
+/// Which memory the GPU copy of a texture is allocated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextureLocation {
+    /// Linear heap, shared with vertex buffers
+    #[default]
+    Linear,
+    /// Video memory, which is small but otherwise unused by textures
+    Vram,
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(std::io::Error),
+    /// The data isn't a well formed texture container
+    Malformed(&'static str),
+    /// Pixel format we can't upload, as it appears in the container
+    UnsupportedFormat(String),
+    /// Texture data doesn't match the size implied by its dimensions and format
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// A region update reaches past the edge of the texture
+    OutOfBounds,
+    /// The material being updated has no texture
+    NoTexture,
+    /// Region updates would leave the smaller mip levels out of date
+    Mipmapped,
+    /// The file isn't in an image format we can decode
+    UnknownFileType,
+    /// There's no file at the path given
+    NotFound(String),
+    /// The image is 0 pixels across in at least one dimension
+    Empty,
+    Png(png::DecodingError),
+    /// Not a power of two between 8 and 1024
+    BadDimension(u16),
+    /// The image is bigger than the GPU can handle once padded
+    TooLarge {
+        width: u32,
+        height: u32,
+    },
+    /// Fewer than one mip level, or more than halving down to 8 texels gives
+    BadLevelCount {
+        levels: u8,
+        max: u8,
+    },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Io(e) => write!(f, "failed to read texture: {e}"),
+            TextureError::Malformed(why) => write!(f, "malformed texture: {why}"),
+            TextureError::UnsupportedFormat(format) => {
+                write!(f, "unsupported texture format {format}")
+            }
+            TextureError::SizeMismatch { expected, actual } => write!(
+                f,
+                "texture data is {actual} bytes but its size and format need {expected}"
+            ),
+            TextureError::OutOfBounds => write!(f, "region is outside the texture"),
+            TextureError::NoTexture => write!(f, "material has no texture"),
+            TextureError::Mipmapped => write!(f, "mipmapped textures can't be updated"),
+            TextureError::UnknownFileType => {
+                write!(f, "unrecognised image file, only PNG and KTX are supported")
+            }
+            TextureError::NotFound(path) => write!(f, "texture file {path} not found"),
+            TextureError::Empty => write!(f, "image has no pixels"),
+            TextureError::Png(e) => write!(f, "failed to decode PNG: {e}"),
+            TextureError::BadDimension(size) if !size.is_power_of_two() => {
+                write!(f, "{size} is not a power of two")
+            }
+            TextureError::BadDimension(size) => write!(
+                f,
+                "{size} is outside the 8 to {MAX_SIZE} texels textures can be"
+            ),
+            TextureError::TooLarge { width, height } => write!(
+                f,
+                "{width}x{height} image is larger than the {MAX_SIZE}x{MAX_SIZE} maximum"
+            ),
+            TextureError::BadLevelCount { levels, max } => write!(
+                f,
+                "texture has {levels} mip levels but its size allows 1 to {max}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<std::io::Error> for TextureError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<png::DecodingError> for TextureError {
+    fn from(value: png::DecodingError) -> Self {
+        Self::Png(value)
+    }
+}
+
 pub struct Texture {
     pub(super) width: u16,
     pub(super) height: u16,
-    pub(super) data: Vec<u8>,
+    pub(super) image_width: u16,
+    pub(super) image_height: u16,
+    format: TextureFormat,
+    /// Number of mip levels in `data`, including the full size image
+    pub(super) levels: u8,
+    /// Kept around so regions can be updated
+    pub(super) data: RefCell<Vec<u8>>,
+    /// Whole rows of tiles changed since the last sync
+    dirty: Cell<Option<(usize, usize)>>,
+    /// Changes to linear if VRAM was asked for but full
+    location: Cell<TextureLocation>,
+    citro_tex: OnceCell<Option<Tex>>,
 }
 
 impl std::fmt::Debug for Texture {
@@ -9,16 +226,429 @@ impl std::fmt::Debug for Texture {
         f.debug_struct("Texture")
             .field("width", &self.width)
             .field("height", &self.height)
+            .field("image_width", &self.image_width)
+            .field("image_height", &self.image_height)
+            .field("format", &self.format)
+            .field("levels", &self.levels)
+            .field("location", &self.location.get())
             .finish()
     }
 }
 
 impl Texture {
     pub fn new(width: u16, height: u16, data: Vec<u8>) -> Self {
+        shared::track(data.len());
         Self {
             width,
             height,
-            data,
+            image_width: width,
+            image_height: height,
+            format: TextureFormat::Rgba8,
+            levels: 1,
+            data: RefCell::new(data),
+            dirty: Cell::new(None),
+            location: Cell::new(TextureLocation::Linear),
+            citro_tex: OnceCell::new(),
+        }
+    }
+
+    /// VRAM falls back to linear memory if it's full
+    pub fn with_location(self, location: TextureLocation) -> Self {
+        self.location.set(location);
+        self
+    }
+
+    pub fn location(&self) -> TextureLocation {
+        self.location.get()
+    }
+
+    /// RGBA8 texture filled with a single colour.
+    pub fn solid(width: u16, height: u16, colour: &Colour) -> Self {
+        // UNWRAP: RGBA8 can always be encoded
+        let texel = TextureFormat::Rgba8
+            .encode([colour.r(), colour.g(), colour.b(), colour.a()])
+            .unwrap();
+        let texels = width as usize * height as usize;
+        Self::new(width, height, texel.repeat(texels))
+    }
+
+    /// Smallest texture the GPU can sample, for when something has to be bound
+    pub fn solid_8x8(colour: &Colour) -> Self {
+        Self::solid(8, 8, colour)
+    }
+
+    /// Magenta and black "missing texture" checkerboard
+    pub fn checkerboard(width: u16, height: u16, cell: u16) -> Self {
+        // RGBA8 texels are stored ABGR
+        const MAGENTA: [u8; 4] = [0xFF, 0xFF, 0x00, 0xFF];
+        const BLACK: [u8; 4] = [0xFF, 0x00, 0x00, 0x00];
+
+        let cell = cell.max(1) as usize;
+        let mut data = vec![0; TextureFormat::Rgba8.image_size(width, height)];
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let colour = if (x / cell + y / cell) % 2 == 0 {
+                    MAGENTA
+                } else {
+                    BLACK
+                };
+                let texel = tiled_index(width, x, y);
+                data[texel * 4..(texel + 1) * 4].copy_from_slice(&colour);
+            }
+        }
+        Self::new(width, height, data)
+    }
+
+    /// Images which aren't a power-of-two size are padded, see
+    /// [`Texture::uv_scale`]
+    pub fn from_file(path: &str) -> Result<Self, TextureError> {
+        let bytes = std::fs::read(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => TextureError::NotFound(path.to_owned()),
+            _ => TextureError::Io(e),
+        })?;
+        let image = decode::decode(&bytes)?;
+        Self::from_image(&image, TextureFormat::for_image(&image), true)
+    }
+
+    /// From the bytes made by `include_texture!`
+    pub fn from_embedded(bytes: &[u8]) -> Result<Self, TextureError> {
+        const HEADER_SIZE: usize = 8;
+        let Some(header) = bytes.get(..HEADER_SIZE) else {
+            return Err(TextureError::Malformed("truncated embedded texture header"));
+        };
+        let width = u16::from_le_bytes([header[0], header[1]]);
+        let height = u16::from_le_bytes([header[2], header[3]]);
+        // numbered as in the model cache
+        let format = match header[4] {
+            0 => TextureFormat::Rgba8,
+            1 => TextureFormat::Rgb565,
+            2 => TextureFormat::Etc1,
+            3 => TextureFormat::Rgba4,
+            4 => TextureFormat::A8,
+            5 => TextureFormat::Etc1A4,
+            other => return Err(TextureError::UnsupportedFormat(other.to_string())),
+        };
+        Self::with_format(width, height, format, 1, bytes[HEADER_SIZE..].to_vec())
+    }
+
+    /// Padded out to the next power-of-two size, see [`Texture::uv_scale`]
+    pub fn from_image(
+        image: &Image,
+        format: TextureFormat,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        if image.width == 0 || image.height == 0 {
+            return Err(TextureError::Empty);
         }
+        let padded = image.padded_to_pot();
+        if padded.width > MAX_SIZE || padded.height > MAX_SIZE {
+            return Err(TextureError::TooLarge {
+                width: image.width,
+                height: image.height,
+            });
+        }
+        let (width, height) = (padded.width as u16, padded.height as u16);
+        Ok(
+            Self::from_rgba(width, height, padded.pixels, format, mipmaps)?
+                .with_image_size(image.width as u16, image.height as u16),
+        )
+    }
+
+    /// From row-major RGBA8 pixels, both dimensions must be powers of two
+    pub fn from_rgba(
+        width: u16,
+        height: u16,
+        pixels: Vec<u8>,
+        format: TextureFormat,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        let Some(bpp) = format.bytes_per_pixel() else {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "{format:?} can't be encoded from pixels"
+            )));
+        };
+        let chain = if mipmaps {
+            mipmap::chain(width, height, pixels)
+        } else {
+            vec![(width, height, pixels)]
+        };
+        let levels = chain.len() as u8;
+        let mut data = Vec::with_capacity(
+            chain
+                .iter()
+                .map(|(w, h, _)| format.image_size(*w, *h))
+                .sum(),
+        );
+        for (w, h, pixels) in chain {
+            let texels = pixels
+                .chunks_exact(4)
+                // UNWRAP: formats with a texel size can all be encoded
+                .flat_map(|p| format.encode([p[0], p[1], p[2], p[3]]).unwrap())
+                .collect::<Vec<_>>();
+            data.extend(swizzle::swizzle(w, h, bpp, &texels));
+        }
+        let mut tex = Self::new(width, height, data);
+        tex.format = format;
+        tex.levels = levels;
+        Ok(tex)
+    }
+
+    pub fn has_mipmaps(&self) -> bool {
+        self.levels > 1
+    }
+
+    /// From data already in the GPU's layout, `levels` mip levels starting
+    /// with the full size one
+    pub fn with_format(
+        width: u16,
+        height: u16,
+        format: TextureFormat,
+        levels: u8,
+        data: Vec<u8>,
+    ) -> Result<Self, TextureError> {
+        Self::check_size(width, height, levels)?;
+        let expected = (0..levels)
+            .map(|l| format.image_size((width >> l).max(8), (height >> l).max(8)))
+            .sum::<usize>();
+        if data.len() != expected {
+            return Err(TextureError::SizeMismatch {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let mut tex = Self::new(width, height, data);
+        tex.format = format;
+        tex.levels = levels;
+        Ok(tex)
+    }
+
+    pub(super) fn check_size(width: u16, height: u16, levels: u8) -> Result<(), TextureError> {
+        for size in [width, height] {
+            if !size.is_power_of_two() || !(8..=MAX_SIZE as u16).contains(&size) {
+                return Err(TextureError::BadDimension(size));
+            }
+        }
+        let max = mipmap::level_count(width, height);
+        if levels == 0 || levels > max {
+            return Err(TextureError::BadLevelCount { levels, max });
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Size of the image within a padded texture
+    pub fn with_image_size(mut self, width: u16, height: u16) -> Self {
+        self.image_width = width;
+        self.image_height = height;
+        self
+    }
+
+    /// Scale from the image's UVs to the padded texture's
+    pub fn uv_scale(&self) -> Vec2 {
+        Vec2::new(
+            self.image_width as f32 / self.width as f32,
+            self.image_height as f32 / self.height as f32,
+        )
+    }
+
+    /// Whether the texture is bigger than the image in it
+    pub fn is_padded(&self) -> bool {
+        (self.image_width, self.image_height) != (self.width, self.height)
+    }
+
+    /// Uploaded the first time this is called
+    pub fn citro_tex(&self) -> Option<&Tex> {
+        self.citro_tex
+            .get_or_init(|| {
+                let params = |location| {
+                    TexParams::new_2d(self.width, self.height)
+                        .format(self.format.colour_format())
+                        .max_level(self.levels - 1)
+                        .on_vram(location == TextureLocation::Vram)
+                };
+                let t = match Tex::new(params(self.location.get())) {
+                    Ok(t) => t,
+                    Err(_) if self.location.get() == TextureLocation::Vram => {
+                        log_warn!(
+                            "no room in VRAM for a {}x{} texture, using linear memory",
+                            self.width,
+                            self.height
+                        );
+                        self.location.set(TextureLocation::Linear);
+                        Tex::new(params(TextureLocation::Linear)).ok()?
+                    }
+                    Err(_) => return None,
+                };
+                self.upload(&t);
+                Some(t)
+            })
+            .as_ref()
+    }
+
+    fn upload(&self, t: &Tex) {
+        let data = self.data.borrow();
+        match self.location.get() {
+            TextureLocation::Linear => t.upload(&data),
+            // VRAM is written by DMA, which can only read from linear memory
+            TextureLocation::Vram => {
+                let mut staging = Vec::with_capacity_in(data.len(), LinearAllocator);
+                staging.extend_from_slice(&data);
+                t.upload(&staging);
+                deferred::release(staging);
+            }
+        }
+    }
+
+    /// Write row-major `pixels`, already in the texture's format, at
+    /// (`x`, `y`) in memory order. Reaches the GPU on the next
+    /// [`Texture::sync`]
+    pub fn update_region(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        let Some(bpp) = self.format.bytes_per_pixel() else {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "{:?} can't be partially updated",
+                self.format
+            )));
+        };
+        if self.levels > 1 {
+            return Err(TextureError::Mipmapped);
+        }
+        if x as u32 + width as u32 > self.width as u32
+            || y as u32 + height as u32 > self.height as u32
+        {
+            return Err(TextureError::OutOfBounds);
+        }
+        let expected = width as usize * height as usize * bpp;
+        if pixels.len() != expected {
+            return Err(TextureError::SizeMismatch {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+
+        let mut data = self.data.borrow_mut();
+        for row in 0..height as usize {
+            for col in 0..width as usize {
+                let texel = tiled_index(self.width, x as usize + col, y as usize + row);
+                let src = (row * width as usize + col) * bpp;
+                data[texel * bpp..(texel + 1) * bpp].copy_from_slice(&pixels[src..src + bpp]);
+            }
+        }
+
+        // tiles are stored a row at a time, so the rows the region touches
+        // are one contiguous range
+        let tile_row = self.width as usize * 8 * bpp;
+        let start = y as usize / 8 * tile_row;
+        let end = (y as usize + height as usize).div_ceil(8) * tile_row;
+        self.dirty.set(Some(match self.dirty.get() {
+            Some((s, e)) => (s.min(start), e.max(end)),
+            None => (start, end),
+        }));
+        Ok(())
+    }
+
+    /// Copy pending region updates to the GPU, VRAM textures are uploaded
+    /// whole
+    pub fn sync(&self) {
+        let Some((start, end)) = self.dirty.take() else {
+            return;
+        };
+        let Some(Some(t)) = self.citro_tex.get() else {
+            return;
+        };
+        match self.location.get() {
+            TextureLocation::Linear => {
+                let data = self.data.borrow();
+                let changed = &data[start..end];
+                // SAFETY: linear textures are plain CPU accessible memory
+                // laid out exactly like `data`, which the range is within.
+                // The GPU isn't reading it as this is called while building a
+                // frame, and the cache is flushed so it sees the new texels
+                unsafe {
+                    let dst = (*t.as_raw()).__bindgen_anon_1.data.cast::<u8>().add(start);
+                    ptr::copy_nonoverlapping(changed.as_ptr(), dst, changed.len());
+                    ctru_sys::GSPGPU_FlushDataCache(dst.cast(), changed.len() as u32);
+                }
+            }
+            TextureLocation::Vram => self.upload(t),
+        }
+    }
+}
+
+/// Next power of two the GPU can sample from
+pub fn padded_size(size: u32) -> u32 {
+    size.next_power_of_two().max(8)
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        shared::untrack(self.data.get_mut().len());
+        if let Some(Some(tex)) = self.citro_tex.take() {
+            deferred::release(tex);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_update_only_touches_its_tiles() {
+        const SIZE: u16 = 128;
+        let tex = Texture::new(SIZE, SIZE, vec![0; SIZE as usize * SIZE as usize * 4]);
+        tex.update_region(56, 56, 16, 16, &[0xFF; 16 * 16 * 4])
+            .unwrap();
+
+        // the region covers tiles 7 and 8 in both directions
+        let tiles_per_row = SIZE as usize / 8;
+        let data = tex.data.borrow();
+        for (i, tile) in data.chunks_exact(64 * 4).enumerate() {
+            let (tx, ty) = (i % tiles_per_row, i / tiles_per_row);
+            let inside = (7..9).contains(&tx) && (7..9).contains(&ty);
+            let expected = if inside { 0xFF } else { 0 };
+            assert!(tile.iter().all(|&b| b == expected), "tile ({tx}, {ty})");
+        }
+
+        let tile_row = SIZE as usize * 8 * 4;
+        assert_eq!(tex.dirty.get(), Some((7 * tile_row, 9 * tile_row)));
+    }
+
+    #[test]
+    fn region_update_rejects_bad_regions() {
+        let tex = Texture::new(16, 16, vec![0; 16 * 16 * 4]);
+        assert!(matches!(
+            tex.update_region(12, 0, 8, 8, &[0; 8 * 8 * 4]),
+            Err(TextureError::OutOfBounds)
+        ));
+        assert!(matches!(
+            tex.update_region(0, 0, 8, 8, &[0; 4]),
+            Err(TextureError::SizeMismatch { .. })
+        ));
+
+        let mipmapped =
+            Texture::from_rgba(16, 16, vec![0; 16 * 16 * 4], TextureFormat::Rgba8, true).unwrap();
+        assert!(matches!(
+            mipmapped.update_region(0, 0, 8, 8, &[0; 8 * 8 * 4]),
+            Err(TextureError::Mipmapped)
+        ));
     }
 }
@@ -0,0 +1,160 @@
+//! Packing small textures onto the shelves of shared atlases.
+
+use std::rc::Rc;
+
+use crate::math::Vec2;
+
+use super::{swizzle::tiled_index, Texture, TextureFormat};
+
+/// Where an image ended up in an atlas, as a transform from UVs over the
+/// image to UVs over the atlas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UvRect {
+    pub offset: Vec2,
+    pub scale: Vec2,
+}
+
+impl UvRect {
+    /// UVs covering the image in a texture on its own
+    pub fn for_texture(tex: &Texture) -> Self {
+        Self {
+            offset: Vec2::new(0.0, 0.0),
+            scale: tex.uv_scale(),
+        }
+    }
+
+    pub fn apply(&self, uv: &Vec2) -> Vec2 {
+        Vec2::new(
+            self.offset.x + uv.x * self.scale.x,
+            self.offset.y + uv.y * self.scale.y,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AtlasEntry {
+    /// Index of the atlas in the list returned by [`pack`]
+    pub atlas: usize,
+    pub rect: UvRect,
+}
+
+struct Shelf {
+    y: u16,
+    height: u16,
+    /// Where the next texture goes along the shelf
+    x: u16,
+}
+
+struct Builder {
+    format: TextureFormat,
+    shelves: Vec<Shelf>,
+    data: Vec<u8>,
+}
+
+impl Builder {
+    fn new(format: TextureFormat, size: u16) -> Self {
+        Self {
+            format,
+            shelves: Vec::new(),
+            data: vec![0; format.image_size(size, size)],
+        }
+    }
+
+    /// Find room for a `width` x `height` image, returning where it goes
+    fn place(&mut self, size: u16, width: u16, height: u16) -> Option<(u16, u16)> {
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .find(|s| s.height >= height && size - s.x >= width)
+        {
+            let x = shelf.x;
+            shelf.x += width;
+            return Some((x, shelf.y));
+        }
+        let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+        if size - y < height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+}
+
+/// Pack `textures` into as many `size` x `size` atlases as they need, `size`
+/// being a power of two the GPU accepts. Each atlas only holds textures of a
+/// single format.
+///
+/// Returns the atlases and where each texture went in them, `None` for
+/// textures which can't go in an atlas because they're block compressed or
+/// too big.
+pub fn pack(textures: &[Rc<Texture>], size: u16) -> (Vec<Texture>, Vec<Option<AtlasEntry>>) {
+    let mut builders: Vec<Builder> = Vec::new();
+    let mut entries = vec![None; textures.len()];
+
+    // tallest first, so shelves are filled by similar sized textures
+    let mut order = (0..textures.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(textures[i].image_height));
+
+    for i in order {
+        let tex = &textures[i];
+        let Some(bpp) = tex.format.bytes_per_pixel() else {
+            continue;
+        };
+        let (width, height) = (tex.image_width, tex.image_height);
+        if width > size || height > size {
+            continue;
+        }
+
+        let mut placed = None;
+        for (idx, builder) in builders.iter_mut().enumerate() {
+            if builder.format != tex.format {
+                continue;
+            }
+            if let Some(pos) = builder.place(size, width, height) {
+                placed = Some((idx, pos));
+                break;
+            }
+        }
+        let (idx, (x0, y0)) = match placed {
+            Some(placed) => placed,
+            None => {
+                let mut builder = Builder::new(tex.format, size);
+                // UNWRAP: the texture fits in an empty atlas, checked above
+                let pos = builder.place(size, width, height).unwrap();
+                builders.push(builder);
+                (builders.len() - 1, pos)
+            }
+        };
+
+        let src = tex.data.borrow();
+        let dst = &mut builders[idx].data;
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let from = tiled_index(tex.width, x, y) * bpp;
+                let to = tiled_index(size, x0 as usize + x, y0 as usize + y) * bpp;
+                dst[to..to + bpp].copy_from_slice(&src[from..from + bpp]);
+            }
+        }
+        entries[i] = Some(AtlasEntry {
+            atlas: idx,
+            rect: UvRect {
+                offset: Vec2::new(x0 as f32 / size as f32, y0 as f32 / size as f32),
+                scale: Vec2::new(width as f32 / size as f32, height as f32 / size as f32),
+            },
+        });
+    }
+
+    let atlases = builders
+        .into_iter()
+        .map(|b| {
+            // UNWRAP: the data is the right size for the format, and the
+            // size is a power of two
+            Texture::with_format(size, size, b.format, 1, b.data).unwrap()
+        })
+        .collect();
+    (atlases, entries)
+}
@@ -0,0 +1,114 @@
+//! Decoding of image files into RGBA8 pixels.
+
+use super::{padded_size, TextureError};
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// A decoded image, 8 bits per channel RGBA.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major pixels, top row first
+    pub pixels: Vec<u8>,
+    /// Whether any pixel is less than fully opaque
+    pub has_alpha: bool,
+}
+
+impl Image {
+    /// Copy the image into the top left of one padded out to the next
+    /// power-of-two size, see [`padded_size`]. The last column and row are
+    /// repeated into the padding so filtering and mipmaps don't bleed it into
+    /// the edges of the image.
+    pub fn padded_to_pot(&self) -> Image {
+        let (width, height) = (padded_size(self.width), padded_size(self.height));
+        if (width, height) == (self.width, self.height) {
+            return self.clone();
+        }
+
+        let (row_len, padded_row_len) = (self.width as usize * 4, width as usize * 4);
+        let mut pixels = vec![0; padded_row_len * height as usize];
+        for (row, padded) in self
+            .pixels
+            .chunks_exact(row_len)
+            .zip(pixels.chunks_exact_mut(padded_row_len))
+        {
+            padded[..row_len].copy_from_slice(row);
+            let last = &row[row_len - 4..];
+            for texel in padded[row_len..].chunks_exact_mut(4) {
+                texel.copy_from_slice(last);
+            }
+        }
+        let last_row = (self.height as usize - 1) * padded_row_len;
+        for y in self.height as usize..height as usize {
+            pixels.copy_within(last_row..last_row + padded_row_len, y * padded_row_len);
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            has_alpha: self.has_alpha,
+        }
+    }
+}
+
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(PNG_SIGNATURE)
+}
+
+/// Read the dimensions of an image from its header, without decoding it.
+pub fn image_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    // the IHDR chunk always comes first, straight after the signature
+    let header = bytes.get(..24)?;
+    if !is_png(header) || &header[12..16] != b"IHDR" {
+        return None;
+    }
+    // UNWRAP: the slices are always 4 bytes long
+    let width = u32::from_be_bytes(header[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(header[20..24].try_into().unwrap());
+    Some((width, height))
+}
+
+/// Decode an image file. Only PNG is supported.
+pub fn decode(bytes: &[u8]) -> Result<Image, TextureError> {
+    if is_png(bytes) {
+        decode_png(bytes)
+    } else {
+        Err(TextureError::UnknownFileType)
+    }
+}
+
+/// Decode a PNG of any colour type and bit depth, expanding it to RGBA8.
+pub fn decode_png(bytes: &[u8]) -> Result<Image, TextureError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    buf.truncate(frame.buffer_size());
+
+    let pixels = match frame.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 0xFF])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&l| [l, l, l, 0xFF]).collect(),
+        // normalize_to_color8 expands palettes
+        png::ColorType::Indexed => {
+            return Err(TextureError::Malformed("PNG palette wasn't expanded"))
+        }
+    };
+    let has_alpha = pixels.chunks_exact(4).any(|p| p[3] != 0xFF);
+    Ok(Image {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+        has_alpha,
+    })
+}
@@ -0,0 +1,56 @@
+//! Mipmap chains from RGBA8 images.
+
+/// Smallest size, in either dimension, the GPU accepts for a mip level
+const MIN_LEVEL_SIZE: u16 = 8;
+
+/// Number of levels, including the full size image, in a chain for a
+/// `width` x `height` texture. Levels stop once either dimension reaches 8.
+pub fn level_count(width: u16, height: u16) -> u8 {
+    let mut levels = 1;
+    let (mut w, mut h) = (width, height);
+    while w / 2 >= MIN_LEVEL_SIZE && h / 2 >= MIN_LEVEL_SIZE {
+        w /= 2;
+        h /= 2;
+        levels += 1;
+    }
+    levels
+}
+
+/// Halve a row-major RGBA8 image in both dimensions, averaging each 2x2
+/// block of pixels. Both dimensions must be even.
+pub fn downsample(width: u16, height: u16, pixels: &[u8]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (out_width, out_height) = (width / 2, height / 2);
+    let mut out = vec![0; out_width * out_height * 4];
+    for y in 0..out_height {
+        for x in 0..out_width {
+            for channel in 0..4 {
+                let sum: u32 = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .into_iter()
+                    .map(|(dx, dy)| {
+                        pixels[((y * 2 + dy) * width + x * 2 + dx) * 4 + channel] as u32
+                    })
+                    .sum();
+                // round to nearest rather than always down, so repeated
+                // halving doesn't darken the image
+                out[(y * out_width + x) * 4 + channel] = ((sum + 2) / 4) as u8;
+            }
+        }
+    }
+    out
+}
+
+/// Every level of the chain for a row-major RGBA8 image, starting with the
+/// image itself.
+pub fn chain(width: u16, height: u16, pixels: Vec<u8>) -> Vec<(u16, u16, Vec<u8>)> {
+    let levels = level_count(width, height);
+    let mut chain = Vec::with_capacity(levels as usize);
+    chain.push((width, height, pixels));
+    for _ in 1..levels {
+        // UNWRAP: the chain always has the full size image in it
+        let (w, h, prev) = chain.last().unwrap();
+        let next = downsample(*w, *h, prev);
+        chain.push((w / 2, h / 2, next));
+    }
+    chain
+}
@@ -0,0 +1,82 @@
+//! Texture filtering and wrapping, set per material.
+
+use citro3d::texture::Tex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// Take the closest texel, for pixel art
+    Nearest,
+    /// Blend the four closest texels
+    #[default]
+    Linear,
+}
+
+impl Filter {
+    fn to_gpu(self) -> ctru_sys::GPU_TEXTURE_FILTER_PARAM {
+        match self {
+            Filter::Nearest => ctru_sys::GPU_NEAREST,
+            Filter::Linear => ctru_sys::GPU_LINEAR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    /// Repeat the edge texels
+    #[default]
+    Clamp,
+    /// Tile the texture
+    Repeat,
+    /// Tile the texture, flipping every other copy
+    Mirror,
+}
+
+impl Wrap {
+    fn to_gpu(self) -> ctru_sys::GPU_TEXTURE_WRAP_PARAM {
+        match self {
+            Wrap::Clamp => ctru_sys::GPU_CLAMP_TO_EDGE,
+            Wrap::Repeat => ctru_sys::GPU_REPEAT,
+            Wrap::Mirror => ctru_sys::GPU_MIRRORED_REPEAT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextureSampling {
+    /// Also used between mip levels
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+}
+
+impl TextureSampling {
+    /// Nearest neighbour filtering, which keeps pixel art sharp
+    pub const NEAREST: Self = Self {
+        min_filter: Filter::Nearest,
+        mag_filter: Filter::Nearest,
+        wrap_u: Wrap::Clamp,
+        wrap_v: Wrap::Clamp,
+    };
+
+    /// Linear filtering which tiles the texture in both directions
+    pub const REPEAT: Self = Self {
+        min_filter: Filter::Linear,
+        mag_filter: Filter::Linear,
+        wrap_u: Wrap::Repeat,
+        wrap_v: Wrap::Repeat,
+    };
+
+    /// Set the parameters on `tex`, done before it's bound as textures are
+    /// shared between materials.
+    pub fn apply(&self, tex: &Tex) {
+        let raw = tex.as_raw() as *mut _;
+        // SAFETY: the pointer is to a live texture, these only update its
+        // parameter fields which are read when it's next bound
+        unsafe {
+            citro3d_sys::C3D_TexSetFilter(raw, self.mag_filter.to_gpu(), self.min_filter.to_gpu());
+            citro3d_sys::C3D_TexSetFilterMipmap(raw, self.min_filter.to_gpu());
+            citro3d_sys::C3D_TexSetWrap(raw, self.wrap_u.to_gpu(), self.wrap_v.to_gpu());
+        }
+    }
+}
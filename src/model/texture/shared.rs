@@ -0,0 +1,62 @@
+//! Textures loaded from files, shared by weak reference.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use super::Texture;
+
+thread_local! {
+    static TEXTURES: RefCell<HashMap<String, Weak<Texture>>> = RefCell::new(HashMap::new());
+    static LIVE: Cell<TextureStats> = const { Cell::new(TextureStats { live: 0, bytes: 0 }) };
+}
+
+/// Every texture in existence, shared or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextureStats {
+    /// Number of textures
+    pub live: usize,
+    /// Size of their texel data, the same on the CPU and GPU side
+    pub bytes: usize,
+}
+
+pub struct TextureCache;
+
+impl TextureCache {
+    /// Get the texture loaded from `key` (usually its path), calling `load`
+    /// to load it if nothing is using it right now.
+    pub fn get_or_load(key: &str, load: impl FnOnce() -> Texture) -> Rc<Texture> {
+        if let Some(tex) = TEXTURES.with_borrow(|t| t.get(key).and_then(Weak::upgrade)) {
+            return tex;
+        }
+        // loaded outside the borrow, in case loading shares textures too
+        let tex = Rc::new(load());
+        TEXTURES.with_borrow_mut(|t| {
+            t.retain(|_, tex| tex.strong_count() > 0);
+            t.insert(key.to_owned(), Rc::downgrade(&tex));
+        });
+        tex
+    }
+
+    pub fn stats() -> TextureStats {
+        LIVE.get()
+    }
+}
+
+/// Count a texture with `bytes` of data as created
+pub(super) fn track(bytes: usize) {
+    LIVE.set(TextureStats {
+        live: LIVE.get().live + 1,
+        bytes: LIVE.get().bytes + bytes,
+    });
+}
+
+/// Count a texture with `bytes` of data as dropped
+pub(super) fn untrack(bytes: usize) {
+    LIVE.set(TextureStats {
+        live: LIVE.get().live - 1,
+        bytes: LIVE.get().bytes - bytes,
+    });
+}
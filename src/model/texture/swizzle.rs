@@ -0,0 +1,71 @@
+//! Row-major pixels to the GPU's grid of Morton ordered 8x8 tiles.
+
+/// Swizzle a row-major `width` x `height` image of `bpp` byte texels into
+/// the tiled layout. Both dimensions must be multiples of 8, which every
+/// power-of-two size the GPU accepts is.
+pub fn swizzle(width: u16, height: u16, bpp: usize, pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(width % 8, 0, "texture width must be a multiple of 8");
+    assert_eq!(height % 8, 0, "texture height must be a multiple of 8");
+    assert_eq!(pixels.len(), width as usize * height as usize * bpp);
+
+    let mut tiled = vec![0; pixels.len()];
+    for (y, row) in pixels.chunks_exact(width as usize * bpp).enumerate() {
+        for (x, texel) in row.chunks_exact(bpp).enumerate() {
+            let idx = tiled_index(width, x, y);
+            tiled[idx * bpp..(idx + 1) * bpp].copy_from_slice(texel);
+        }
+    }
+    tiled
+}
+
+/// Index of the texel at (`x`, `y`) in a texture `width` texels across.
+pub fn tiled_index(width: u16, x: usize, y: usize) -> usize {
+    let tile = (y / 8) * (width as usize / 8) + x / 8;
+    tile * 64 + morton_index(x % 8, y % 8)
+}
+
+/// Offset of a texel within an 8x8 tile, interleaving the bits of `x` and `y`.
+fn morton_index(x: usize, y: usize) -> usize {
+    (0..3)
+        .map(|bit| ((x >> bit) & 1) << (2 * bit) | ((y >> bit) & 1) << (2 * bit + 1))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::texture::TextureFormat;
+
+    /// Row-major index (`y * 8 + x`) of the texel at each position of an 8x8
+    /// tile, the order tex3ds writes them in
+    const TEX3DS_TILE: [u8; 64] = [
+        0, 1, 8, 9, 2, 3, 10, 11, 16, 17, 24, 25, 18, 19, 26, 27, //
+        4, 5, 12, 13, 6, 7, 14, 15, 20, 21, 28, 29, 22, 23, 30, 31, //
+        32, 33, 40, 41, 34, 35, 42, 43, 48, 49, 56, 57, 50, 51, 58, 59, //
+        36, 37, 44, 45, 38, 39, 46, 47, 52, 53, 60, 61, 54, 55, 62, 63, //
+    ];
+
+    #[test]
+    fn rgba8_tile_matches_tex3ds() {
+        // red is the texel's row-major index, so each one can be found again
+        let texels = (0..64u8)
+            .flat_map(|i| TextureFormat::Rgba8.encode([i, 0x80, 0x40, 0xFF]).unwrap())
+            .collect::<Vec<_>>();
+        let tiled = swizzle(8, 8, 4, &texels);
+
+        // and stored alpha first, ABGR
+        let expected = TEX3DS_TILE
+            .iter()
+            .flat_map(|&i| [0xFF, 0x40, 0x80, i])
+            .collect::<Vec<_>>();
+        assert_eq!(tiled, expected);
+    }
+
+    #[test]
+    fn tiles_are_row_major() {
+        assert_eq!(tiled_index(16, 7, 7), 63);
+        assert_eq!(tiled_index(16, 8, 0), 64);
+        assert_eq!(tiled_index(16, 0, 8), 128);
+        assert_eq!(tiled_index(16, 9, 9), 128 + 64 + 3);
+    }
+}
@@ -0,0 +1,81 @@
+//! Wireframes of shapes, each edge a pair of crossed ribbons.
+
+use std::collections::BTreeSet;
+
+use citro3d::buffer::Primitive;
+
+use crate::{logging::log_warn, math::Vec3, Vertex};
+
+use super::{
+    colour::Colour,
+    material::{CullMode, Material},
+    shape::Shape,
+    texenv::TexEnvConfig,
+};
+
+/// Colour the edges are drawn in
+const COLOUR: Colour = Colour::new(0x40, 0xFF, 0x40, 0xFF);
+/// Width of the ribbons relative to the radius of the shape
+const WIDTH: f32 = 0.005;
+/// Vertices each edge expands to, two quads
+const VERTS_PER_EDGE: usize = 8;
+/// Most edges which fit in a single indexed shape
+const MAX_EDGES: usize = (u16::MAX as usize + 1) / VERTS_PER_EDGE;
+
+/// Build shapes drawing every edge of `shape`'s triangles. Empty if there's
+/// no room for them in linear memory.
+pub(super) fn build<T: Vertex>(shape: &Shape<T>) -> Vec<Shape<T>> {
+    let mut edges = BTreeSet::new();
+    for [a, b, c] in shape.triangles() {
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            edges.insert((from.min(to), from.max(to)));
+        }
+    }
+    let edges = edges.into_iter().collect::<Vec<_>>();
+    let width = shape.bounds().radius * WIDTH;
+
+    let material = || {
+        let mut mat = Material::builder().cull_mode(CullMode::None).build();
+        mat.set_texenv(Some(TexEnvConfig::flat(&COLOUR)));
+        mat
+    };
+
+    let mut shapes = Vec::new();
+    for chunk in edges.chunks(MAX_EDGES) {
+        let mut verts = Vec::with_capacity(chunk.len() * VERTS_PER_EDGE);
+        let mut indices = Vec::with_capacity(chunk.len() * 12);
+        for &(a, b) in chunk {
+            let (a, b) = (&shape.verts[a], &shape.verts[b]);
+            let (from, to) = (a.position(), b.position());
+            let dir = to
+                .sub(from)
+                .normalized()
+                .unwrap_or(Vec3::new(0.0, 0.0, 1.0));
+            let side = dir
+                .cross(&Vec3::new(0.0, 1.0, 0.0))
+                .normalized()
+                .unwrap_or(Vec3::new(1.0, 0.0, 0.0));
+            let up = side.cross(&dir);
+
+            for offset in [side, up] {
+                let offset = offset.scale(width / 2.0);
+                let start = verts.len() as u16;
+                verts.extend([
+                    a.with_position(from.sub(&offset)),
+                    a.with_position(from.add(&offset)),
+                    b.with_position(to.add(&offset)),
+                    b.with_position(to.sub(&offset)),
+                ]);
+                indices.extend([0, 1, 2, 0, 2, 3].map(|i| start + i));
+            }
+        }
+        match Shape::new_indexed(material(), Primitive::Triangles, &verts, &indices) {
+            Ok(wire) => shapes.push(wire.with_name(format!("{} wireframe", shape.name()))),
+            Err(e) => {
+                log_warn!("not showing the wireframe of {}: {e}", shape.name());
+                return Vec::new();
+            }
+        }
+    }
+    shapes
+}
@@ -1,109 +1,1012 @@
-use std::{fs::read, iter::repeat};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use crate::{
-    model::{colour::Colour, material::Material, shape::Shape, texture::Texture, Model},
-    Vec2, Vec3, Vert,
+    logging::{log_info, log_warn},
+    math::{Aabb, AxisConvention, Transform},
+    model::{
+        colour::Colour,
+        etc1, ktx,
+        material::{BlendMode, CullMode, Material},
+        mesh,
+        shape::{Shape, ShapeError},
+        texture::{
+            atlas::{self, UvRect},
+            decode, padded_size,
+            sampling::TextureSampling,
+            shared::TextureCache,
+            Texture, TextureError, TextureFormat, TextureLocation,
+        },
+        Model,
+    },
+    Vec2, Vec3, VertCol, VertLightmap, VertNorm, Vertex,
+};
+
+use self::winding::WindingResult;
+
+mod indices;
+mod lines;
+mod raw;
+mod smoothing;
+mod winding;
+
+/// Options controlling how OBJ models and their textures are loaded.
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// Use a `.ktx` file next to a referenced texture if there is one
+    pub prefer_ktx: bool,
+    pub mipmaps: bool,
+    /// Use a `.etc1` file next to a referenced image if there is one, see
+    /// [`crate::model::etc1`]
+    pub prefer_etc1: bool,
+    pub texture_location: TextureLocation,
+    /// Pack textures into shared atlases this many texels across, a power of
+    /// two up to 1024
+    pub atlas_size: Option<u16>,
+    /// Flip triangles which face inwards, see [`winding::correct_winding`]
+    pub fix_winding: bool,
+    /// Fit the whole file in a cube `target_size` across at the origin
+    pub normalize: bool,
+    pub target_size: f32,
+    /// Center the whole file on the origin without scaling it
+    pub recenter: bool,
+    /// Combine groups in an object which use the same material
+    pub merge_by_material: bool,
+    /// Reverse every triangle, computed normals are flipped to match
+    pub flip_winding: bool,
+    /// For toolchains which put V = 0 at the bottom of the image
+    pub flip_uv_v: bool,
+    pub cull_mode: CullMode,
+    /// Distance past which a crude level of detail with every other
+    /// triangle dropped is drawn
+    pub lod_distance: Option<f32>,
+    /// See [`Shape::stripify`]
+    pub stripify: bool,
+    /// See [`mesh::weld`], usually [`mesh::DEFAULT_WELD_TOLERANCE`]
+    pub weld_tolerance: Option<f32>,
+    /// Applied to every model's transform rather than the vertices
+    pub scale: f32,
+    /// Which way is up in the file
+    pub axis_convention: AxisConvention,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Obj(obj::ObjError),
+    /// A file referenced by the model couldn't be resolved
+    Missing(String),
+    /// A face (counting from 1 in file order) references an element which
+    /// doesn't exist
+    IndexOutOfRange {
+        face: usize,
+        index: isize,
+    },
+    Texture {
+        path: String,
+        err: TextureError,
+    },
+    /// A shape for the group couldn't be created
+    Shape {
+        group: String,
+        err: ShapeError,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Obj(e) => write!(f, "failed to parse obj: {e}"),
+            LoadError::Missing(path) => write!(f, "missing file {path}"),
+            LoadError::IndexOutOfRange { face, index } => {
+                write!(
+                    f,
+                    "face {face} references index {index} which is out of range"
+                )
+            }
+            LoadError::Texture { path, err } => write!(f, "failed to load texture {path}: {err}"),
+            LoadError::Shape {
+                group,
+                err: ShapeError::OutOfLinearMemory { needed },
+            } => write!(
+                f,
+                "out of linear memory loading group {group} (needed {} KiB)",
+                needed.div_ceil(1024)
+            ),
+            LoadError::Shape { group, err } => write!(f, "failed to load group {group}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<obj::ObjError> for LoadError {
+    fn from(e: obj::ObjError) -> Self {
+        Self::Obj(e)
+    }
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            prefer_ktx: true,
+            mipmaps: true,
+            prefer_etc1: true,
+            texture_location: TextureLocation::Linear,
+            atlas_size: None,
+            fix_winding: false,
+            normalize: false,
+            target_size: 1.0,
+            recenter: false,
+            merge_by_material: false,
+            flip_winding: false,
+            flip_uv_v: true,
+            cull_mode: CullMode::Back,
+            lod_distance: None,
+            stripify: false,
+            weld_tolerance: None,
+            scale: 1.0,
+            axis_convention: AxisConvention::YUp,
+        }
+    }
+}
+
+/// Normal used for faces that have no `vn` reference and are too degenerate
+/// to compute one from.
+const PLACEHOLDER_NORMAL: Vec3 = Vec3 {
+    x: 0.0,
+    y: 0.0,
+    z: 1.0,
 };
 
-pub fn parse_obj(path: &str) -> Vec<Model<Vert>> {
-    let mut obj = obj::Obj::load(path).unwrap();
-    obj.load_mtls().unwrap();
+/// Width of `l` lines relative to the size of the whole file
+const LINE_WIDTH: f32 = 0.005;
+
+/// Largest number of unique vertices a single indexed shape can address.
+const MAX_INDEXED_VERTS: usize = u16::MAX as usize + 1;
+
+/// Identifies a unique vertex within a group, so faces sharing a corner can
+/// share a single entry in the vertex buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct VertKey {
+    pos: usize,
+    tex: Option<usize>,
+    norm: NormKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NormKey {
+    /// Normal referenced from a `vn` record
+    Indexed(usize),
+    /// Flat normal computed for the face with this index, can't be shared
+    /// with other faces
+    Face(usize),
+    /// Normal averaged over the faces sharing the position in this smoothing
+    /// group, distinct groups meeting at a position keep separate vertices
+    Smooth(u32),
+}
+
+/// Properties read from an MTL material which end up in a [`Material`].
+#[derive(Debug, Clone)]
+struct MtlProps {
+    colour: Option<Colour>,
+    emission: Option<Colour>,
+    specular: Option<Colour>,
+    shininess: f32,
+    /// Opacity from `d` (or `Tr`, which is its inverse)
+    alpha: f32,
+    texture: Option<Rc<Texture>>,
+    /// Where the image is in `texture`, when that's an atlas
+    uv_rect: Option<UvRect>,
+    ambient_texture: Option<Rc<Texture>>,
+}
+
+impl Default for MtlProps {
+    fn default() -> Self {
+        Self {
+            colour: None,
+            emission: None,
+            specular: None,
+            shininess: 0.0,
+            alpha: 1.0,
+            texture: None,
+            uv_rect: None,
+            ambient_texture: None,
+        }
+    }
+}
+
+/// Faces from one or more groups which are drawn with the same material.
+struct Batch {
+    /// Name of the first group in the batch
+    name: String,
+    /// Name of the material the groups reference
+    material: Option<String>,
+    props: MtlProps,
+    meshes: Vec<IndexedMesh>,
+}
+
+/// Vertex and index buffers for (part of) a group, ready to become a [`Shape`].
+#[derive(Default)]
+struct IndexedMesh {
+    verts: Vec<VertNorm>,
+    /// Key each vertex in `verts` was created from
+    keys: Vec<VertKey>,
+    indices: Vec<u16>,
+    lookup: HashMap<VertKey, u16>,
+}
+
+impl IndexedMesh {
+    /// Whether adding a face with these corners would need more vertices
+    /// than a `u16` index can address.
+    fn would_overflow(&self, keys: &[VertKey]) -> bool {
+        let new_verts = keys.iter().filter(|k| !self.lookup.contains_key(k)).count();
+        self.verts.len() + new_verts > MAX_INDEXED_VERTS
+    }
+
+    fn push(&mut self, key: VertKey, make_vert: impl FnOnce() -> VertNorm) {
+        let idx = *self.lookup.entry(key).or_insert_with(|| {
+            self.verts.push(make_vert());
+            self.keys.push(key);
+            (self.verts.len() - 1) as u16
+        });
+        self.indices.push(idx);
+    }
+}
+
+/// Vertex types models can be loaded into.
+pub trait ObjVertex: Vertex {
+    /// Build a vertex from the loaded attributes, `colour` is only present if
+    /// the file has vertex colours.
+    fn from_obj(vert: &VertNorm, colour: Option<&Colour>) -> Self;
+}
+
+impl ObjVertex for VertNorm {
+    fn from_obj(vert: &VertNorm, _colour: Option<&Colour>) -> Self {
+        vert.clone()
+    }
+}
+
+impl ObjVertex for VertCol {
+    fn from_obj(vert: &VertNorm, colour: Option<&Colour>) -> Self {
+        VertCol {
+            pos: vert.pos.clone(),
+            tex: vert.tex.clone(),
+            norm: vert.norm.clone(),
+            colour: colour.cloned().unwrap_or(Colour::WHITE),
+        }
+    }
+}
+
+impl ObjVertex for VertLightmap {
+    /// OBJ files only have one set of UVs, so texture 1 shares them
+    fn from_obj(vert: &VertNorm, colour: Option<&Colour>) -> Self {
+        VertLightmap {
+            pos: vert.pos.clone(),
+            tex: vert.tex.clone(),
+            norm: vert.norm.clone(),
+            colour: colour.cloned().unwrap_or(Colour::WHITE),
+            tex1: vert.tex.clone(),
+        }
+    }
+}
+
+/// Part of loading a model which progress is being reported for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStage {
+    Textures,
+    Objects,
+    Groups,
+}
+
+impl fmt::Display for LoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LoadStage::Textures => "textures",
+            LoadStage::Objects => "objects",
+            LoadStage::Groups => "groups",
+        })
+    }
+}
+
+/// Called as loading moves through each texture, object and group with the
+/// stage, the number of the item about to be loaded (from 1) and the total.
+pub type Progress<'a> = Option<&'a mut dyn FnMut(LoadStage, usize, usize)>;
+
+pub fn parse_obj<T: ObjVertex>(path: &str, progress: Progress) -> Result<Vec<Model<T>>, LoadError> {
+    parse_obj_with(path, &LoadOptions::default(), progress)
+}
+
+pub fn parse_obj_with<T: ObjVertex>(
+    path: &str,
+    options: &LoadOptions,
+    progress: Progress,
+) -> Result<Vec<Model<T>>, LoadError> {
+    parse_obj_with_sources(path, options, progress).map(|(models, _)| models)
+}
+
+/// As [`parse_obj_with`], also giving the path of every material library and
+/// texture it tried to read, whether they exist or not. Textures already in
+/// the [`TextureCache`] aren't read so aren't included.
+pub fn parse_obj_with_sources<T: ObjVertex>(
+    path: &str,
+    options: &LoadOptions,
+    progress: Progress,
+) -> Result<(Vec<Model<T>>, Vec<PathBuf>), LoadError> {
+    let bytes = fs::read(path).map_err(|_| LoadError::Missing(path.to_owned()))?;
+    let base = Path::new(path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let sources = RefCell::new(Vec::new());
+    let models = parse_obj_impl(
+        &bytes,
+        |name| {
+            let path = resolve_path(&base, name);
+            let bytes = fs::read(&path).ok();
+            sources.borrow_mut().push(path);
+            bytes
+        },
+        Some(&base),
+        options,
+        progress,
+    )?;
+    let mut sources = sources.into_inner();
+    sources.sort();
+    sources.dedup();
+    Ok((models, sources))
+}
+
+/// Parse a model from memory, e.g. one embedded with `include_bytes!`.
+/// `mtl_resolver` is asked for the contents of any material libraries and
+/// textures the model references, by the name they are referenced with.
+pub fn parse_obj_from_bytes<T: ObjVertex>(
+    obj: &[u8],
+    mtl_resolver: impl Fn(&str) -> Option<Vec<u8>>,
+) -> Result<Vec<Model<T>>, LoadError> {
+    parse_obj_from_bytes_with(obj, mtl_resolver, &LoadOptions::default(), None)
+}
+
+/// As [`parse_obj_from_bytes`], with `options`. Textures are shared with
+/// other models through [`TextureCache`] by the name they're referenced
+/// with.
+pub fn parse_obj_from_bytes_with<T: ObjVertex>(
+    obj: &[u8],
+    mtl_resolver: impl Fn(&str) -> Option<Vec<u8>>,
+    options: &LoadOptions,
+    progress: Progress,
+) -> Result<Vec<Model<T>>, LoadError> {
+    parse_obj_impl(obj, mtl_resolver, None, options, progress)
+}
 
-    let vertices = obj
+/// `base` is the directory the model was loaded from, which texture names
+/// are resolved against to key them in the [`TextureCache`].
+fn parse_obj_impl<T: ObjVertex>(
+    obj: &[u8],
+    mtl_resolver: impl Fn(&str) -> Option<Vec<u8>>,
+    base: Option<&Path>,
+    options: &LoadOptions,
+    mut progress: Progress,
+) -> Result<Vec<Model<T>>, LoadError> {
+    let mut report = |stage, done, total| {
+        if let Some(progress) = progress.as_mut() {
+            progress(stage, done, total);
+        }
+    };
+    let obj_bytes = obj;
+    // colours are only collected if they have somewhere to go
+    let raw = raw::scan(obj_bytes, T::HAS_COLOUR);
+    let vertex_colours = raw.colours;
+    let mut obj = obj::Obj {
+        data: obj::ObjData::load_buf(obj_bytes)?,
+        path: PathBuf::new(),
+    };
+    let face_count = obj
+        .data
+        .objects
+        .iter()
+        .flat_map(|o| &o.groups)
+        .map(|g| g.polys.len())
+        .sum::<usize>();
+    let raw_faces = if raw.faces.len() == face_count {
+        &raw.faces[..]
+    } else {
+        log_warn!(
+            "found {} faces in the file but parsed {face_count}, ignoring relative indices and smoothing groups",
+            raw.faces.len()
+        );
+        &[]
+    };
+    indices::resolve_indices(raw_faces, &mut obj.data)?;
+    // materials which fail to load are reported again as missing when a
+    // group references them, so carry on with what did load
+    if let Err(e) = obj.load_mtls_fn(|_, name| {
+        mtl_resolver(name)
+            .map(Cursor::new)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_owned()))
+    }) {
+        log_warn!("failed to load material libraries: {e:?}");
+    }
+
+    let mut vertices = obj
         .data
         .position
         .iter()
-        .map(|e| Vec3 {
-            x: e[0],
-            y: e[1],
-            z: e[2],
+        .map(|e| {
+            options.axis_convention.convert(&Vec3 {
+                x: e[0],
+                y: e[1],
+                z: e[2],
+            })
         })
         .collect::<Vec<_>>();
 
+    // applied to the vertex data rather than the model transform so positions
+    // are in the same space as everything else that reads them
+    if options.normalize || options.recenter {
+        let bounds = Aabb::from_points(&vertices);
+        let offset = bounds.center();
+        let extents = bounds.extents();
+        let largest = extents.x.max(extents.y).max(extents.z) * 2.0;
+        let scale = if options.normalize && largest > f32::EPSILON {
+            options.target_size / largest
+        } else {
+            1.0
+        };
+        log_info!("normalizing model, offset {offset:?} scale {scale}");
+        for v in &mut vertices {
+            *v = v.sub(&offset).scale(scale);
+        }
+    }
+
     let tex_coords = obj
         .data
         .texture
         .iter()
-        .map(|e| Vec2 {
-            x: e[0] * (480.0 / 512.0),
-            y: (1.0 - e[1] * (395.0 / 512.0)),
+        .map(|e| Vec2 { x: e[0], y: e[1] })
+        .collect::<Vec<_>>();
+
+    let normals = obj
+        .data
+        .normal
+        .iter()
+        .map(|e| {
+            options.axis_convention.convert(&Vec3 {
+                x: e[0],
+                y: e[1],
+                z: e[2],
+            })
         })
         .collect::<Vec<_>>();
 
-    obj.data
+    let smoothing_groups = (0..face_count)
+        .map(|face| raw_faces.get(face).map_or(0, |f| f.smoothing))
+        .collect::<Vec<_>>();
+    let smooth_normals = smoothing::smooth_normals(
+        obj.data
+            .objects
+            .iter()
+            .flat_map(|o| &o.groups)
+            .flat_map(|g| &g.polys)
+            .zip(smoothing_groups.iter().copied()),
+        &vertices,
+        options.flip_winding,
+    );
+
+    // materials referencing the same image share a single texture
+    let mut textures: HashMap<String, Rc<Texture>> = HashMap::new();
+    let load_or_checkerboard = |t: &str| {
+        let key = match base {
+            Some(base) => resolve_path(base, t).to_string_lossy().into_owned(),
+            None => t.to_owned(),
+        };
+        TextureCache::get_or_load(&key, || {
+            let tex = load_texture(t, options, &mtl_resolver).unwrap_or_else(|e| {
+                log_warn!("{e}, using a checkerboard instead");
+                Texture::checkerboard(64, 64, 8)
+            });
+            tex.with_location(options.texture_location)
+        })
+    };
+    // textures used by the groups are loaded up front, as they're the slow
+    // part and this way progress can be reported with a known total
+    let mut texture_names: Vec<&String> = Vec::new();
+    let used_materials = obj
+        .data
         .objects
         .iter()
-        .map(|e| {
-            let shapes = e
-                .groups
-                .iter()
-                .map(|g| {
-                    let mat = &g.material;
-                    let (col, tex) = if let Some(m) = mat {
-                        match m {
-                            obj::ObjMaterial::Ref(_) => todo!(),
-                            obj::ObjMaterial::Mtl(m) => {
-                                let col = m.kd.map(|rgb| {
-                                    Colour::new(
-                                        (rgb[0] * 255.0) as u8,
-                                        (rgb[1] * 255.0) as u8,
-                                        (rgb[2] * 255.0) as u8,
-                                        0xFF,
-                                    )
-                                });
-
-                                let tex = m
-                                    .map_kd
-                                    .as_ref()
-                                    .map(|t| Texture::new(512, 512, read(t).unwrap()));
-
-                                (col, tex)
+        .flat_map(|o| &o.groups)
+        .filter_map(|g| match g.material.as_ref()? {
+            obj::ObjMaterial::Mtl(m) => Some(m.as_ref()),
+            obj::ObjMaterial::Ref(name) => find_material(&obj.data.material_libs, name),
+        });
+    // textures whose UVs are shared with an ambient map, so can't be moved
+    let mut with_ambient: Vec<&String> = Vec::new();
+    for m in used_materials {
+        for name in [&m.map_kd, &m.map_ka].into_iter().flatten() {
+            if !texture_names.contains(&name) {
+                texture_names.push(name);
+            }
+            if m.map_ka.is_some() {
+                with_ambient.push(name);
+            }
+        }
+    }
+    for (i, name) in texture_names.iter().enumerate() {
+        report(LoadStage::Textures, i + 1, texture_names.len());
+        textures.insert(name.to_string(), load_or_checkerboard(name));
+    }
+
+    let mut uv_rects: HashMap<String, UvRect> = HashMap::new();
+    if let Some(size) = options.atlas_size {
+        let names = texture_names
+            .iter()
+            .filter(|&&name| !with_ambient.contains(&name))
+            .collect::<Vec<_>>();
+        let to_pack = names
+            .iter()
+            .map(|&&name| textures[name].clone())
+            .collect::<Vec<_>>();
+        let (atlases, entries) = atlas::pack(&to_pack, size);
+        let atlases = atlases
+            .into_iter()
+            .map(|t| Rc::new(t.with_location(options.texture_location)))
+            .collect::<Vec<_>>();
+        for (name, entry) in names.into_iter().zip(entries) {
+            if let Some(entry) = entry {
+                textures.insert(name.to_string(), atlases[entry.atlas].clone());
+                uv_rects.insert(name.to_string(), entry.rect);
+            }
+        }
+        log_info!(
+            "packed {} textures into {} atlases",
+            uv_rects.len(),
+            atlases.len()
+        );
+    }
+
+    let mut mtl_props = |m: &obj::Material| {
+        let mut cached_texture = |t: &String| {
+            textures
+                .entry(t.clone())
+                .or_insert_with(|| load_or_checkerboard(t))
+                .clone()
+        };
+        let texture = m.map_kd.as_ref().map(&mut cached_texture);
+        let uv_rect = m.map_kd.as_ref().and_then(|t| uv_rects.get(t).cloned());
+        let ambient_texture = m.map_ka.as_ref().map(&mut cached_texture);
+
+        let alpha = m.d.or(m.tr.map(|tr| 1.0 - tr)).unwrap_or(1.0);
+        let kd = m.kd.unwrap_or([1.0, 1.0, 1.0]);
+
+        MtlProps {
+            colour: (m.kd.is_some() || alpha < 1.0).then(|| colour_from_rgba(kd, alpha)),
+            emission: m.ke.map(|rgb| colour_from_rgba(rgb, 1.0)),
+            specular: m.ks.map(|rgb| colour_from_rgba(rgb, 1.0)),
+            shininess: m.ns.unwrap_or(0.0),
+            alpha,
+            texture,
+            uv_rect,
+            ambient_texture,
+        }
+    };
+
+    let mut resolve_material = |material: Option<&obj::ObjMaterial>| match material {
+        Some(obj::ObjMaterial::Mtl(m)) => mtl_props(m),
+        Some(obj::ObjMaterial::Ref(name)) => match find_material(&obj.data.material_libs, name) {
+            Some(m) => mtl_props(m),
+            None => {
+                log_warn!("material {name} not found, using magenta");
+                MtlProps {
+                    colour: Some(Colour::new(0xFF, 0x00, 0xFF, 0xFF)),
+                    ..Default::default()
+                }
+            }
+        },
+        None => MtlProps::default(),
+    };
+
+    // lines are batched by object and material, like merged groups
+    let line_width = Aabb::from_points(&vertices).extents().length() * 2.0 * LINE_WIDTH;
+    let mut line_batches: Vec<(String, Option<String>, Vec<usize>)> = Vec::new();
+    for line in raw.lines {
+        let existing = line_batches
+            .iter()
+            .position(|(o, m, _)| *o == line.object && *m == line.material);
+        let idx = match existing {
+            Some(idx) => idx,
+            None => {
+                line_batches.push((line.object, line.material, Vec::new()));
+                line_batches.len() - 1
+            }
+        };
+        let points = &mut line_batches[idx].2;
+        // stored as pairs of segment ends
+        for seg in line.points.windows(2) {
+            points.extend_from_slice(seg);
+        }
+    }
+    let mut line_shapes: Vec<(String, Vec<usize>, Vec<Shape<T>>)> = Vec::new();
+    for (object, material, points) in line_batches {
+        let props = MtlProps {
+            texture: None,
+            ambient_texture: None,
+            ..resolve_material(material.map(obj::ObjMaterial::Ref).as_ref())
+        };
+        let segments = points
+            .chunks_exact(2)
+            .map(|seg| (vertices[seg[0]].clone(), vertices[seg[1]].clone()))
+            .collect::<Vec<_>>();
+        let shapes = segments
+            .chunks(lines::MAX_SEGMENTS)
+            .map(|segments| {
+                let (verts, indices) = lines::ribbons(segments, line_width);
+                let verts = verts
+                    .iter()
+                    .map(|v| T::from_obj(v, None))
+                    .collect::<Vec<_>>();
+                Shape::new_indexed(
+                    make_material(&props, vertex_colours.is_some(), options.cull_mode),
+                    citro3d::buffer::Primitive::Triangles,
+                    &verts,
+                    &indices,
+                )
+                .map(|s| s.with_name("lines"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| LoadError::Shape {
+                group: format!("{object}/lines"),
+                err,
+            })?;
+        match line_shapes.iter_mut().find(|(o, _, _)| *o == object) {
+            Some((_, all_points, all_shapes)) => {
+                all_points.extend(points);
+                all_shapes.extend(shapes);
+            }
+            None => {
+                line_shapes.push((object, points, shapes));
+            }
+        }
+    }
+
+    let mut flipped_tris = 0;
+    let mut flipped_shapes = 0;
+    let mut welded_verts = 0;
+    let transform = Transform {
+        scale: Vec3::splat(options.scale),
+        ..Transform::IDENTITY
+    };
+
+    let mut face_idx = 0;
+    let mut models = Vec::new();
+    let group_count = obj.data.objects.iter().map(|o| o.groups.len()).sum();
+    let mut group_idx = 0;
+    for (object_idx, e) in obj.data.objects.iter().enumerate() {
+        report(LoadStage::Objects, object_idx + 1, obj.data.objects.len());
+        let mut shapes = Vec::new();
+        let mut batches: Vec<Batch> = Vec::new();
+        for g in &e.groups {
+            group_idx += 1;
+            report(LoadStage::Groups, group_idx, group_count);
+            let material_name = g.material.as_ref().map(|m| match m {
+                obj::ObjMaterial::Mtl(m) => m.name.clone(),
+                obj::ObjMaterial::Ref(name) => name.clone(),
+            });
+            let existing = options
+                .merge_by_material
+                .then(|| batches.iter().position(|b| b.material == material_name))
+                .flatten();
+            let batch_idx = match existing {
+                Some(idx) => idx,
+                None => {
+                    let props = resolve_material(g.material.as_ref());
+                    batches.push(Batch {
+                        name: g.name.clone(),
+                        material: material_name,
+                        props,
+                        meshes: vec![IndexedMesh::default()],
+                    });
+                    batches.len() - 1
+                }
+            };
+            let Batch { props, meshes, .. } = &mut batches[batch_idx];
+            let uv_rect = match (&props.uv_rect, &props.texture) {
+                (Some(rect), _) => rect.clone(),
+                (None, Some(t)) => UvRect::for_texture(t),
+                (None, None) => UvRect {
+                    offset: Vec2::new(0.0, 0.0),
+                    scale: Vec2::new(1.0, 1.0),
+                },
+            };
+
+            for p in &g.polys {
+                let smoothing_group = smoothing_groups[face_idx];
+                let mut corners = p.0.iter().take(3).collect::<Vec<_>>();
+                if options.flip_winding && corners.len() == 3 {
+                    corners.swap(1, 2);
+                }
+                let face_normal = face_normal(
+                    &corners
+                        .iter()
+                        .map(|i| vertices[i.0].clone())
+                        .collect::<Vec<_>>(),
+                );
+                let keys = corners
+                    .iter()
+                    .map(|i| VertKey {
+                        pos: i.0,
+                        tex: i.1,
+                        norm: match (i.2, smoothing_group) {
+                            (Some(n), _) => NormKey::Indexed(n),
+                            (None, 0) => NormKey::Face(face_idx),
+                            (None, group) => NormKey::Smooth(group),
+                        },
+                    })
+                    .collect::<Vec<_>>();
+                face_idx += 1;
+
+                // UNWRAP: there is always at least one mesh
+                let mut mesh = meshes.last_mut().unwrap();
+                if mesh.would_overflow(&keys) {
+                    meshes.push(IndexedMesh::default());
+                    mesh = meshes.last_mut().unwrap();
+                }
+
+                for key in keys {
+                    mesh.push(key, || VertNorm {
+                        pos: vertices[key.pos].clone(),
+                        tex: key.tex.map_or(Vec2::new(0.0, 0.0), |t| {
+                            let uv = uv_rect.apply(&tex_coords[t]);
+                            Vec2::new(uv.x, if options.flip_uv_v { 1.0 - uv.y } else { uv.y })
+                        }),
+                        norm: match key.norm {
+                            NormKey::Indexed(n) => normals[n].clone(),
+                            NormKey::Face(_) => face_normal.clone(),
+                            NormKey::Smooth(group) => smooth_normals
+                                .get(&(key.pos, group))
+                                .unwrap_or(&face_normal)
+                                .clone(),
+                        },
+                    });
+                }
+            }
+        }
+
+        for batch in batches {
+            let batch_shapes = batch
+                .meshes
+                .into_iter()
+                .filter(|m| !m.indices.is_empty())
+                .map(|mut m| {
+                    if options.fix_winding {
+                        match winding::correct_winding(&mut m) {
+                            WindingResult::Checked { flipped: 0 } => {}
+                            WindingResult::Checked { flipped } => {
+                                flipped_tris += flipped;
+                                flipped_shapes += 1;
                             }
+                            WindingResult::Open => log_info!(
+                                "{}/{} is an open surface, not fixing its winding",
+                                e.name,
+                                batch.name
+                            ),
                         }
-                    } else {
-                        (None, None)
-                    };
-                    let polys = g
-                        .polys
+                    }
+                    let mut verts = m
+                        .verts
                         .iter()
-                        .flat_map(|p| {
-                            let verts =
-                                p.0.iter()
-                                    .map(|i| vertices[i.0].clone())
-                                    .take(3)
-                                    .collect::<Vec<_>>();
-                            let texs = p
-                                .0
-                                .iter()
-                                .map(|i| i.1.map_or(Vec2::new(0.0, 0.0), |t| tex_coords[t].clone()))
-                                .take(3)
-                                .collect::<Vec<_>>();
-                            verts
-                                .into_iter()
-                                .zip(texs)
-                                .map(|(v, t)| Vert { pos: v, tex: t })
-                                .collect::<Vec<_>>()
+                        .zip(&m.keys)
+                        .map(|(v, k)| {
+                            T::from_obj(v, vertex_colours.as_ref().and_then(|c| c.get(k.pos)))
                         })
                         .collect::<Vec<_>>();
-                    Shape::new(
-                        Material::new(
-                            tex.or_else(|| {
-                                Some(Texture::new(
-                                    64,
-                                    64,
-                                    repeat(0).take(64 * 64 * 4).collect::<Vec<_>>(),
-                                ))
-                            }),
-                            col,
-                            None,
-                            true,
-                        ),
+                    if let Some(tolerance) = options.weld_tolerance {
+                        let before = verts.len();
+                        (verts, m.indices) = mesh::weld(&verts, &m.indices, tolerance);
+                        welded_verts += before - verts.len();
+                    }
+                    Shape::new_indexed(
+                        make_material(&batch.props, vertex_colours.is_some(), options.cull_mode),
                         citro3d::buffer::Primitive::Triangles,
-                        &polys,
+                        &verts,
+                        &m.indices,
                     )
+                    .and_then(|s| {
+                        let mut s = s.with_name(batch.name.clone());
+                        if let Some(distance) = options.lod_distance {
+                            let coarse = m
+                                .indices
+                                .chunks_exact(3)
+                                .step_by(2)
+                                .flatten()
+                                .map(|&i| verts[i as usize].clone())
+                                .collect::<Vec<_>>();
+                            s = s.with_max_distance(distance);
+                            s.add_lod(f32::INFINITY, &coarse)?;
+                        }
+                        if options.stripify {
+                            s.stripify()?;
+                        }
+                        Ok(s)
+                    })
                 })
-                .collect::<Vec<_>>();
-            Model::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0), shapes)
-        })
-        .collect::<_>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| LoadError::Shape {
+                    group: format!("{}/{}", e.name, batch.name),
+                    err,
+                })?;
+            shapes.extend(batch_shapes);
+        }
+        let mut line_points = Vec::new();
+        if let Some(idx) = line_shapes.iter().position(|(o, _, _)| *o == e.name) {
+            let (_, points, lines) = line_shapes.remove(idx);
+            line_points = points;
+            shapes.extend(lines);
+        }
+
+        let aabb = Aabb::from_points(
+            e.groups
+                .iter()
+                .flat_map(|g| &g.polys)
+                .flat_map(|p| &p.0)
+                .map(|i| i.0)
+                .chain(line_points)
+                .map(|i| &vertices[i]),
+        );
+        models.push(Model::new(e.name.clone(), transform.clone(), shapes).with_aabb(aabb));
+    }
+
+    // objects which are nothing but lines
+    for (object, points, shapes) in line_shapes {
+        let aabb = Aabb::from_points(points.iter().map(|&i| &vertices[i]));
+        models.push(Model::new(object, transform.clone(), shapes).with_aabb(aabb));
+    }
+
+    if options.fix_winding {
+        log_info!("flipped winding of {flipped_tris} triangles in {flipped_shapes} shapes");
+    }
+    if options.weld_tolerance.is_some() {
+        log_info!("welded away {welded_verts} vertices");
+    }
+
+    Ok(models)
+}
+
+/// Build the material for a shape, `vertex_colours` is whether the shape's
+/// vertices carry colours of their own.
+fn make_material(props: &MtlProps, vertex_colours: bool, cull_mode: CullMode) -> Material {
+    // vertex colours are multiplied into the material colour, so it needs to
+    // be something for them to show up
+    let colour = match &props.colour {
+        None if vertex_colours => Some(Colour::WHITE),
+        colour => colour.clone(),
+    };
+    let blend_mode = if props.alpha < 1.0 {
+        BlendMode::AlphaBlend
+    } else {
+        BlendMode::Opaque
+    };
+    // MTL textures tile by default, but a padded texture would repeat its
+    // padding too so those are left clamped
+    let sampling = if props.texture.as_ref().is_some_and(|t| !t.is_padded()) {
+        TextureSampling::REPEAT
+    } else {
+        TextureSampling::default()
+    };
+    Material::builder()
+        .texture(props.texture.clone())
+        .ambient_texture(props.ambient_texture.clone())
+        .diffuse(colour)
+        .emission(props.emission.clone())
+        .specular(props.specular.clone(), props.shininess)
+        .vertex_colours(true)
+        .blend_mode(blend_mode)
+        .cull_mode(cull_mode)
+        .sampling(sampling)
+        .build()
+}
+
+fn colour_from_rgba(rgb: [f32; 3], alpha: f32) -> Colour {
+    Colour::new(
+        (rgb[0] * 255.0) as u8,
+        (rgb[1] * 255.0) as u8,
+        (rgb[2] * 255.0) as u8,
+        (alpha * 255.0) as u8,
+    )
+}
+
+/// Look up a material referenced by name in the loaded material libraries.
+fn find_material<'a>(libs: &'a [obj::Mtl], name: &str) -> Option<&'a obj::Material> {
+    libs.iter()
+        .flat_map(|lib| &lib.materials)
+        .find(|m| m.name == name)
+        .map(|m| m.as_ref())
+}
+
+/// Resolve a name referenced by a model against the directory it was loaded
+/// from. Names with a device prefix (e.g. `romfs:/`) or a leading `/` are
+/// used as-is.
+fn resolve_path(base: &Path, name: &str) -> PathBuf {
+    if name.contains(":/") || name.starts_with('/') {
+        PathBuf::from(name)
+    } else {
+        base.join(name)
+    }
+}
+
+/// Load the texture referenced by a material, preferring a pre-compressed
+/// sibling `.ktx` if `options` allow it. Anything else is decoded as an image.
+fn load_texture(
+    path: &str,
+    options: &LoadOptions,
+    resolver: impl Fn(&str) -> Option<Vec<u8>>,
+) -> Result<Texture, LoadError> {
+    let texture_err = |err| LoadError::Texture {
+        path: path.to_owned(),
+        err,
+    };
+    let sibling = |ext| {
+        Path::new(path)
+            .with_extension(ext)
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    if options.prefer_ktx {
+        let ktx_path = sibling("ktx");
+        if let Some(bytes) = resolver(&ktx_path) {
+            match ktx::parse_ktx(&bytes) {
+                Ok(tex) => return Ok(tex),
+                Err(e) => log_warn!("failed to load {ktx_path}: {e}"),
+            }
+        }
+    }
+
+    let bytes = resolver(path).ok_or_else(|| LoadError::Missing(path.to_owned()))?;
+    if options.prefer_etc1 {
+        let etc1_path = sibling("etc1");
+        if let Some(etc1) = resolver(&etc1_path) {
+            match (etc1::parse_etc1(&etc1), decode::image_size(&bytes)) {
+                (Ok(tex), Some((w, h)))
+                    if (padded_size(w), padded_size(h))
+                        == (tex.width() as u32, tex.height() as u32) =>
+                {
+                    return Ok(tex.with_image_size(w as u16, h as u16));
+                }
+                (Ok(tex), Some((w, h))) => log_warn!(
+                    "{etc1_path} is {}x{} which doesn't fit {w}x{h} {path}, ignoring it",
+                    tex.width(),
+                    tex.height()
+                ),
+                (Ok(_), None) => {
+                    log_warn!("can't read the size of {path} to check {etc1_path} against")
+                }
+                (Err(e), _) => log_warn!("failed to load {etc1_path}: {e}"),
+            }
+        }
+    }
+    if ktx::is_ktx(&bytes) {
+        return ktx::parse_ktx(&bytes).map_err(texture_err);
+    }
+    let image = decode::decode(&bytes).map_err(texture_err)?;
+    Texture::from_image(&image, TextureFormat::for_image(&image), options.mipmaps)
+        .map_err(texture_err)
+}
+
+/// Flat normal of the triangle formed by the first three vertices of a face,
+/// for faces which don't reference a `vn` record.
+fn face_normal(verts: &[Vec3]) -> Vec3 {
+    match verts {
+        [a, b, c, ..] => b
+            .sub(a)
+            .cross(&c.sub(a))
+            .normalized()
+            .unwrap_or(PLACEHOLDER_NORMAL),
+        _ => PLACEHOLDER_NORMAL,
+    }
 }
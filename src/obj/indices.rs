@@ -0,0 +1,61 @@
+//! Resolution of relative (negative) indices in `f` records.
+
+use super::{raw::RawFace, LoadError};
+
+/// Rewrite the indices of every face in `data` from the ones in the file,
+/// resolving relative ones, and check they are all in range so a bad file is
+/// reported rather than panicking on lookup. `raw_faces` is empty when they
+/// don't line up with the parsed ones, which are then only checked.
+pub(super) fn resolve_indices(
+    raw_faces: &[RawFace],
+    data: &mut obj::ObjData,
+) -> Result<(), LoadError> {
+    let totals = [data.position.len(), data.texture.len(), data.normal.len()];
+    let polys = data
+        .objects
+        .iter_mut()
+        .flat_map(|o| &mut o.groups)
+        .flat_map(|g| &mut g.polys)
+        .collect::<Vec<_>>();
+
+    for (face, poly) in polys.into_iter().enumerate() {
+        for (c, corner) in poly.0.iter_mut().enumerate() {
+            let mut indices = [Some(corner.0), corner.1, corner.2];
+            if let Some(raw) = raw_faces.get(face) {
+                if let Some(raw_corner) = raw.corners.get(c) {
+                    for ((index, raw_index), count) in
+                        indices.iter_mut().zip(raw_corner).zip(raw.counts)
+                    {
+                        *index = match *raw_index {
+                            Some(idx) if idx < 0 => {
+                                Some(usize::try_from(count as isize + idx).map_err(|_| {
+                                    LoadError::IndexOutOfRange {
+                                        face: face + 1,
+                                        index: idx,
+                                    }
+                                })?)
+                            }
+                            Some(idx) => Some((idx - 1) as usize),
+                            None => *index,
+                        };
+                    }
+                }
+            }
+
+            for (index, total) in indices.iter().zip(totals) {
+                if let Some(index) = *index {
+                    if index >= total {
+                        return Err(LoadError::IndexOutOfRange {
+                            face: face + 1,
+                            index: index as isize + 1,
+                        });
+                    }
+                }
+            }
+            // UNWRAP: the position index started out as Some and is only
+            // ever replaced with another Some
+            *corner = obj::IndexTuple(indices[0].unwrap(), indices[1], indices[2]);
+        }
+    }
+    Ok(())
+}
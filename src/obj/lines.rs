@@ -0,0 +1,51 @@
+//! `l` polylines, drawn as crossed ribbons as the PICA200 can't draw lines.
+
+use crate::{Vec2, Vec3, VertNorm};
+
+/// Vertices each segment expands to, two double sided quads
+const VERTS_PER_SEGMENT: usize = 8;
+
+/// Most segments which fit in a single indexed shape
+pub(super) const MAX_SEGMENTS: usize = super::MAX_INDEXED_VERTS / VERTS_PER_SEGMENT;
+
+#[derive(Debug)]
+pub(super) struct Polyline {
+    /// Name of the object (`o` record) the line is in
+    pub object: String,
+    /// Material in use (`usemtl` record) when the line was defined
+    pub material: Option<String>,
+    /// Position indices of the points along the line
+    pub points: Vec<usize>,
+}
+
+/// Expand line segments into ribbons `width` across. There must be no more
+/// than [`MAX_SEGMENTS`] of them.
+pub(super) fn ribbons(segments: &[(Vec3, Vec3)], width: f32) -> (Vec<VertNorm>, Vec<u16>) {
+    let mut verts = Vec::with_capacity(segments.len() * VERTS_PER_SEGMENT);
+    let mut indices = Vec::with_capacity(segments.len() * 24);
+    for (a, b) in segments {
+        let dir = b.sub(a).normalized().unwrap_or(Vec3::new(0.0, 0.0, 1.0));
+        let side = dir
+            .cross(&Vec3::new(0.0, 1.0, 0.0))
+            .normalized()
+            .unwrap_or(Vec3::new(1.0, 0.0, 0.0));
+        let up = side.cross(&dir);
+
+        for offset in [side, up] {
+            let base = verts.len() as u16;
+            let half = offset.scale(width / 2.0);
+            for p in [a.add(&half), a.sub(&half), b.add(&half), b.sub(&half)] {
+                verts.push(VertNorm {
+                    pos: p,
+                    tex: Vec2::new(0.0, 0.0),
+                    norm: offset.clone(),
+                });
+            }
+            // both windings, so the ribbon isn't culled from either side
+            for tri in [[0, 1, 2], [2, 1, 3], [0, 2, 1], [2, 3, 1]] {
+                indices.extend(tri.map(|i| base + i));
+            }
+        }
+    }
+    (verts, indices)
+}
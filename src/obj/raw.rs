@@ -0,0 +1,178 @@
+//! One pass over the file for everything the obj crate drops.
+
+use crate::{logging::log_warn, model::colour::Colour};
+
+use super::lines::Polyline;
+
+/// Indices of one face corner as written in the file, 1-based or negative.
+pub(super) type RawCorner = [Option<isize>; 3];
+
+pub(super) struct RawFace {
+    pub corners: Vec<RawCorner>,
+    /// Number of `v`, `vt` and `vn` records before the face
+    pub counts: [usize; 3],
+    /// Smoothing group, 0 is `s off`
+    pub smoothing: u32,
+}
+
+#[derive(Default)]
+pub(super) struct RawObj {
+    /// Every face in the order they appear, which is the order the obj crate
+    /// parses them in
+    pub faces: Vec<RawFace>,
+    /// Colour of every position, `None` if no `v` record has one or they
+    /// weren't asked for. Positions without one are white.
+    pub colours: Option<Vec<Colour>>,
+    /// Polylines, skipping any referencing positions which don't exist
+    pub lines: Vec<Polyline>,
+}
+
+/// Read everything the obj crate doesn't from `obj`. `colours` is whether to
+/// collect vertex colours.
+pub(super) fn scan(obj: &[u8], colours: bool) -> RawObj {
+    let mut raw = RawObj::default();
+    let mut counts = [0; 3];
+    let mut smoothing = 0;
+    let mut object = String::from("default");
+    let mut material = None;
+    let mut vertex_colours = Vec::new();
+    let mut any_colour = false;
+    for (line_no, line) in obj.split(|&b| b == b'\n').enumerate() {
+        let line = String::from_utf8_lossy(line);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                counts[0] += 1;
+                if colours {
+                    let colour = parse_colour(words);
+                    any_colour |= colour.is_some();
+                    vertex_colours.push(colour.unwrap_or(Colour::WHITE));
+                }
+            }
+            Some("vt") => counts[1] += 1,
+            Some("vn") => counts[2] += 1,
+            Some("o") => object = words.collect::<Vec<_>>().join(" "),
+            Some("usemtl") => material = words.next().map(str::to_owned),
+            Some("s") => {
+                smoothing = match words.next() {
+                    Some("off") | None => 0,
+                    Some(n) => n.parse().unwrap_or_else(|_| {
+                        log_warn!("invalid smoothing group {n}, turning smoothing off");
+                        0
+                    }),
+                }
+            }
+            Some("f") => raw.faces.push(RawFace {
+                corners: words
+                    .take_while(|w| !w.starts_with('#'))
+                    .map(parse_corner)
+                    .collect(),
+                counts,
+                smoothing,
+            }),
+            Some("l") => {
+                let positions = counts[0];
+                let points = words
+                    .take_while(|w| !w.starts_with('#'))
+                    .map(|w| {
+                        let idx = w.split('/').next()?.parse::<isize>().ok()?;
+                        let idx = if idx < 0 {
+                            positions as isize + idx
+                        } else {
+                            idx - 1
+                        };
+                        usize::try_from(idx).ok().filter(|&i| i < positions)
+                    })
+                    .collect::<Option<Vec<_>>>();
+                match points {
+                    Some(points) if points.len() >= 2 => raw.lines.push(Polyline {
+                        object: object.clone(),
+                        material: material.clone(),
+                        points,
+                    }),
+                    _ => log_warn!("skipping invalid line on line {}", line_no + 1),
+                }
+            }
+            _ => {}
+        }
+    }
+    raw.colours = any_colour.then_some(vertex_colours);
+    raw
+}
+
+/// Parse a `v`, `v/vt`, `v//vn` or `v/vt/vn` corner.
+fn parse_corner(word: &str) -> RawCorner {
+    let mut parts = word.split('/').map(|p| p.parse::<isize>().ok());
+    [
+        parts.next().flatten(),
+        parts.next().flatten(),
+        parts.next().flatten(),
+    ]
+}
+
+/// The non-standard `v x y z r g b` extension written by MeshLab and a lot of
+/// scanning software, given the words after `v`.
+fn parse_colour<'a>(words: impl Iterator<Item = &'a str>) -> Option<Colour> {
+    let rgb = words
+        .skip(3)
+        .take(3)
+        .map(|w| w.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+    match rgb.as_deref() {
+        Ok(&[r, g, b]) => Some(Colour::new(to_byte(r), to_byte(g), to_byte(b), 0xFF)),
+        _ => None,
+    }
+}
+
+fn to_byte(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OBJ: &[u8] = b"\
+v 0 0 0 1 0 0
+v 1 0 0
+vt 0 0
+o thing
+usemtl red
+s 1
+f 1/1 2/1 -1/-1
+v 0 1 0
+s off
+f -3 -2 -1 # comment
+l 1 -1
+";
+
+    #[test]
+    fn collects_everything_in_one_pass() {
+        let raw = scan(OBJ, true);
+
+        assert_eq!(raw.faces.len(), 2);
+        assert_eq!(raw.faces[0].counts, [2, 1, 0]);
+        assert_eq!(raw.faces[0].corners[2], [Some(-1), Some(-1), None]);
+        assert_eq!(raw.faces[0].smoothing, 1);
+        assert_eq!(raw.faces[1].counts, [3, 1, 0]);
+        assert_eq!(raw.faces[1].corners.len(), 3);
+        assert_eq!(raw.faces[1].smoothing, 0);
+
+        let colours = raw.colours.unwrap();
+        assert_eq!(colours.len(), 3);
+        let rgba = |c: &Colour| [c.r(), c.g(), c.b(), c.a()];
+        assert_eq!(rgba(&colours[0]), [0xFF, 0, 0, 0xFF]);
+        assert_eq!(rgba(&colours[1]), [0xFF; 4]);
+
+        assert_eq!(raw.lines.len(), 1);
+        assert_eq!(raw.lines[0].object, "thing");
+        assert_eq!(raw.lines[0].material.as_deref(), Some("red"));
+        assert_eq!(raw.lines[0].points, [0, 2]);
+    }
+
+    #[test]
+    fn colours_only_when_asked_for() {
+        assert!(scan(OBJ, false).colours.is_none());
+        assert!(scan(b"v 0 0 0\n", true).colours.is_none());
+    }
+}
@@ -0,0 +1,42 @@
+//! Smooth normals from `s` smoothing groups for faces without `vn`.
+
+use std::collections::HashMap;
+
+use crate::Vec3;
+
+use super::PLACEHOLDER_NORMAL;
+
+/// Average the normals of the faces around each position within its
+/// smoothing group. Faces are weighted by area so slivers don't skew the
+/// result. `flip_winding` reverses the faces, as they'll be emitted that way.
+pub(super) fn smooth_normals<'a>(
+    faces: impl Iterator<Item = (&'a obj::SimplePolygon, u32)>,
+    vertices: &[Vec3],
+    flip_winding: bool,
+) -> HashMap<(usize, u32), Vec3> {
+    let mut normals: HashMap<(usize, u32), Vec3> = HashMap::new();
+    for (face, group) in faces {
+        if group == 0 || face.0.iter().all(|i| i.2.is_some()) {
+            continue;
+        }
+        let [a, b, c] = match face.0.as_slice() {
+            [a, b, c, ..] => [a, b, c].map(|i| &vertices[i.0]),
+            _ => continue,
+        };
+        let mut normal = b.sub(a).cross(&c.sub(a));
+        if flip_winding {
+            normal = normal.scale(-1.0);
+        }
+        for corner in face.0.iter().take(3) {
+            let sum = normals
+                .entry((corner.0, group))
+                .or_insert(Vec3::new(0.0, 0.0, 0.0));
+            *sum = sum.add(&normal);
+        }
+    }
+
+    for normal in normals.values_mut() {
+        *normal = normal.normalized().unwrap_or(PLACEHOLDER_NORMAL);
+    }
+    normals
+}
@@ -0,0 +1,242 @@
+//! Consistent counter-clockwise winding for closed shapes.
+
+use std::collections::HashMap;
+
+use super::{IndexedMesh, NormKey};
+use crate::Vec3;
+
+/// Fraction of edges which must be shared by exactly two triangles for a
+/// shape to count as closed. Open surfaces have no inside or outside so
+/// there's nothing sensible to compare against.
+const MIN_SOLIDITY: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindingResult {
+    /// Shape was closed, this many triangles were flipped to face outward
+    Checked { flipped: usize },
+    /// Shape looks like an open surface so was left alone
+    Open,
+}
+
+/// Make neighbouring triangles agree on their winding, then flip whichever
+/// side is the minority. Each connected part of the shape is taken as
+/// inside out if its signed volume is negative, so unlike comparing against
+/// the centroid this works for concave shapes too.
+pub fn correct_winding(mesh: &mut IndexedMesh) -> WindingResult {
+    if solidity(mesh) < MIN_SOLIDITY {
+        return WindingResult::Open;
+    }
+
+    let tris = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]].map(|i| mesh.keys[i as usize].pos))
+        .collect::<Vec<_>>();
+    // triangles along each edge, and whether they go round it from the lower
+    // position to the higher
+    let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+    for (t, pos) in tris.iter().enumerate() {
+        for (a, b) in tri_edges(pos) {
+            edges
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push((t, a < b));
+        }
+    }
+
+    let mut flip: Vec<Option<bool>> = vec![None; tris.len()];
+    for seed in 0..tris.len() {
+        if flip[seed].is_some() {
+            continue;
+        }
+        // walk the connected part, flipping each neighbour to go round their
+        // shared edge the opposite way to the triangle it was reached from
+        flip[seed] = Some(false);
+        let mut part = Vec::new();
+        let mut stack = vec![seed];
+        while let Some(t) = stack.pop() {
+            part.push(t);
+            // UNWRAP: triangles are only pushed once they've been assigned
+            let flip_t = flip[t].unwrap();
+            for (a, b) in tri_edges(&tris[t]) {
+                let ascending = (a < b) != flip_t;
+                for &(u, u_ascending) in &edges[&(a.min(b), a.max(b))] {
+                    if flip[u].is_none() {
+                        flip[u] = Some(u_ascending == ascending);
+                        stack.push(u);
+                    }
+                }
+            }
+        }
+
+        let volume = part
+            .iter()
+            .map(|&t| {
+                let [a, b, c] = triangle(mesh, t);
+                let det = a.dot(&b.cross(&c));
+                // UNWRAP: every triangle in the part has been assigned
+                if flip[t].unwrap() {
+                    -det
+                } else {
+                    det
+                }
+            })
+            .sum::<f32>();
+        if volume < 0.0 {
+            for &t in &part {
+                flip[t] = flip[t].map(|f| !f);
+            }
+        }
+    }
+
+    let mut flipped = 0;
+    for (t, f) in flip.into_iter().enumerate() {
+        if f != Some(true) {
+            continue;
+        }
+        let tri = &mut mesh.indices[t * 3..t * 3 + 3];
+        tri.swap(1, 2);
+        // computed face normals were built from the old winding, authored
+        // ones are left as they are
+        for &i in tri.iter() {
+            if let NormKey::Face(_) = mesh.keys[i as usize].norm {
+                let flipped_norm = mesh.verts[i as usize].norm.scale(-1.0);
+                mesh.verts[i as usize].norm = flipped_norm;
+            }
+        }
+        flipped += 1;
+    }
+
+    WindingResult::Checked { flipped }
+}
+
+fn tri_edges(pos: &[usize; 3]) -> [(usize, usize); 3] {
+    [(pos[0], pos[1]), (pos[1], pos[2]), (pos[2], pos[0])]
+}
+
+/// Positions of the corners of triangle `t`
+fn triangle(mesh: &IndexedMesh, t: usize) -> [&Vec3; 3] {
+    let tri = &mesh.indices[t * 3..t * 3 + 3];
+    [tri[0], tri[1], tri[2]].map(|i| &mesh.verts[i as usize].pos)
+}
+
+/// Fraction of edges shared by exactly two triangles, edges are compared by
+/// position so seams in UVs or normals don't count as holes.
+fn solidity(mesh: &IndexedMesh) -> f32 {
+    let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+    for tri in mesh.indices.chunks_exact(3) {
+        let pos = [tri[0], tri[1], tri[2]].map(|i| mesh.keys[i as usize].pos);
+        for (a, b) in tri_edges(&pos) {
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+
+    if edges.is_empty() {
+        return 0.0;
+    }
+    let shared = edges.values().filter(|&&n| n == 2).count();
+    shared as f32 / edges.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Vec2, obj::VertKey, VertNorm};
+
+    /// Unit cube as triangles of corner indices, wound counter-clockwise
+    /// from outside
+    const CUBE: [[usize; 3]; 12] = [
+        [0, 2, 1],
+        [0, 3, 2],
+        [4, 5, 6],
+        [4, 6, 7],
+        [0, 1, 5],
+        [0, 5, 4],
+        [3, 6, 2],
+        [3, 7, 6],
+        [0, 4, 7],
+        [0, 7, 3],
+        [1, 2, 6],
+        [1, 6, 5],
+    ];
+
+    fn corner(i: usize) -> Vec3 {
+        Vec3::new(
+            ((i & 1) ^ ((i >> 1) & 1)) as f32,
+            ((i >> 1) & 1) as f32,
+            (i >> 2) as f32,
+        )
+    }
+
+    fn mesh(tris: &[[usize; 3]], mirror_x: bool) -> IndexedMesh {
+        let mut mesh = IndexedMesh::default();
+        for (face, tri) in tris.iter().enumerate() {
+            for &pos in tri {
+                let key = VertKey {
+                    pos,
+                    tex: None,
+                    norm: NormKey::Face(face),
+                };
+                let mut p = corner(pos);
+                if mirror_x {
+                    p.x = -p.x;
+                }
+                mesh.push(key, || VertNorm {
+                    pos: p,
+                    tex: Vec2::new(0.0, 0.0),
+                    norm: Vec3::new(0.0, 0.0, 0.0),
+                });
+            }
+        }
+        mesh
+    }
+
+    fn signed_volume(mesh: &IndexedMesh) -> f32 {
+        (0..mesh.indices.len() / 3)
+            .map(|t| {
+                let [a, b, c] = triangle(mesh, t);
+                a.dot(&b.cross(&c))
+            })
+            .sum()
+    }
+
+    #[test]
+    fn outward_cube_is_left_alone() {
+        let mut cube = mesh(&CUBE, false);
+        assert!(signed_volume(&cube) > 0.0);
+        assert_eq!(
+            correct_winding(&mut cube),
+            WindingResult::Checked { flipped: 0 }
+        );
+    }
+
+    #[test]
+    fn mirrored_cube_is_turned_the_right_way_out() {
+        let mut cube = mesh(&CUBE, true);
+        assert!(signed_volume(&cube) < 0.0);
+        assert_eq!(
+            correct_winding(&mut cube),
+            WindingResult::Checked { flipped: 12 }
+        );
+        assert!(signed_volume(&cube) > 0.0);
+    }
+
+    #[test]
+    fn mirrored_face_is_flipped_to_match() {
+        let mut tris = CUBE;
+        tris[2].swap(1, 2);
+        tris[3].swap(1, 2);
+        let mut cube = mesh(&tris, false);
+        assert_eq!(
+            correct_winding(&mut cube),
+            WindingResult::Checked { flipped: 2 }
+        );
+        assert_eq!(&cube.indices[6..12], &[6, 8, 7, 9, 11, 10]);
+    }
+
+    #[test]
+    fn open_box_is_left_alone() {
+        let mut open = mesh(&CUBE[2..], false);
+        assert_eq!(correct_winding(&mut open), WindingResult::Open);
+    }
+}
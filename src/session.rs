@@ -0,0 +1,126 @@
+//! App state saved between launches as `key=value` lines. Unknown keys are
+//! ignored and missing ones defaulted, so only changes in meaning bump
+//! [`VERSION`].
+
+use std::{fmt::Write, fs};
+
+use crate::{
+    hud::HudPage,
+    logging::{log_info, log_warn},
+    math::Vec3,
+};
+
+const SESSION_DIR: &str = "sdmc:/draw-trongle";
+const SESSION_PATH: &str = "sdmc:/draw-trongle/session.txt";
+const SESSION_TMP_PATH: &str = "sdmc:/draw-trongle/session.txt.tmp";
+
+/// Current version of the session format. Version 1 stored the camera as the
+/// offset and rotation applied to the world rather than the eye's own
+const VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    /// Paths of the scenes which were loaded, in load order
+    pub scenes: Vec<String>,
+    /// `None` in files from before it was the eye's position
+    pub cam_pos: Option<Vec3>,
+    pub cam_rot: Vec3,
+    /// Name of the model being manipulated
+    pub selected_model: Option<String>,
+    pub hud_page: HudPage,
+    /// 3D tuning, `None` when the file is from before it was stored
+    pub screen_depth: Option<f32>,
+    pub interocular_scale: Option<f32>,
+    /// Angle L and R spin the selected model by
+    pub spin_snap: Option<f32>,
+}
+
+impl Session {
+    /// Read the session saved by the last run, if there is one.
+    pub fn load() -> Option<Self> {
+        let text = fs::read_to_string(SESSION_PATH).ok()?;
+        let session = Self::parse(&text);
+        if session.is_none() {
+            log_warn!("ignoring unreadable session file {SESSION_PATH}");
+        }
+        session
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines().filter_map(|l| l.split_once('='));
+        let version = match lines.next()? {
+            ("version", v) => v.trim().parse::<u32>().ok()?,
+            _ => return None,
+        };
+        if version > VERSION {
+            log_info!("session file is version {version}, reading what is understood");
+        }
+
+        let mut session = Session::default();
+        for (key, value) in lines {
+            let value = value.trim();
+            match key.trim() {
+                "scene" => session.scenes.push(value.to_owned()),
+                "cam_pos" if version >= 2 => session.cam_pos = parse_vec3(value),
+                "cam_rot" if version >= 2 => {
+                    session.cam_rot = parse_vec3(value).unwrap_or_default()
+                }
+                "selected" => session.selected_model = Some(value.to_owned()),
+                "hud_page" => session.hud_page = HudPage::from_name(value).unwrap_or_default(),
+                "screen_depth" => session.screen_depth = value.parse().ok(),
+                "interocular_scale" => session.interocular_scale = value.parse().ok(),
+                "spin_snap" => session.spin_snap = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(session)
+    }
+
+    /// Write the session out. Goes through a temporary file so a crash while
+    /// saving doesn't lose the previous session.
+    pub fn save(&self) {
+        let mut text = format!("version={VERSION}\n");
+        for scene in &self.scenes {
+            let _ = writeln!(text, "scene={scene}");
+        }
+        if let Some(Vec3 { x, y, z }) = &self.cam_pos {
+            let _ = writeln!(text, "cam_pos={x} {y} {z}");
+        }
+        let Vec3 { x, y, z } = &self.cam_rot;
+        let _ = writeln!(text, "cam_rot={x} {y} {z}");
+        if let Some(selected) = &self.selected_model {
+            let _ = writeln!(text, "selected={selected}");
+        }
+        let _ = writeln!(text, "hud_page={}", self.hud_page.name());
+        for (key, value) in [
+            ("screen_depth", self.screen_depth),
+            ("interocular_scale", self.interocular_scale),
+            ("spin_snap", self.spin_snap),
+        ] {
+            if let Some(value) = value {
+                let _ = writeln!(text, "{key}={value}");
+            }
+        }
+
+        let result = fs::create_dir_all(SESSION_DIR)
+            .and_then(|_| fs::write(SESSION_TMP_PATH, text))
+            .and_then(|_| {
+                // renaming over an existing file fails on the SD card
+                let _ = fs::remove_file(SESSION_PATH);
+                fs::rename(SESSION_TMP_PATH, SESSION_PATH)
+            });
+        if let Err(e) = result {
+            log_warn!("failed to save session: {e}");
+        }
+    }
+}
+
+fn parse_vec3(s: &str) -> Option<Vec3> {
+    let mut parts = s.split_whitespace().map(|p| p.parse::<f32>());
+    let v = Vec3::new(
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    parts.next().is_none().then_some(v)
+}